@@ -0,0 +1,222 @@
+use std::{
+    fmt, io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    str::FromStr,
+    vec,
+};
+
+/// Port used when none is given, the default Source and Gold Source query port
+pub const DEFAULT_PORT: u16 = 27015;
+
+const STEAM_CONNECT: &str = "steam://connect/";
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/**
+Address of a server given as an IP address or hostname with a port.
+
+Parses `"1.2.3.4:27015"`, `"[2001:db8::1]:27015"`, `"play.example.com:27015"` and `steam://connect/...` URLs,
+if the port is left out [`DEFAULT_PORT`] is used. Any password in a `steam://connect` URL is ignored.
+Implements [`ToSocketAddrs`] so it can be used anywhere a socket address is accepted.
+
+# Examples
+```
+use a2s_parse::address::ServerAddress;
+
+let address: ServerAddress = "steam://connect/play.example.com:27016/hunter2".parse().unwrap();
+
+assert_eq!("play.example.com", address.host());
+assert_eq!(27016, address.port());
+```
+*/
+pub struct ServerAddress {
+    host: String,
+    port: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Reasons a [`ServerAddress`] can fail to parse
+pub enum AddressError {
+    /// No host was given
+    MissingHost,
+    /// The port is not a number between 0 and 65535
+    InvalidPort(String),
+    /// The host is not a valid IP address or hostname
+    InvalidHost(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::MissingHost => write!(f, "no host given"),
+            AddressError::InvalidPort(port) => write!(f, "invalid port: {}", port),
+            AddressError::InvalidHost(host) => write!(f, "invalid host: {}", host),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+impl ServerAddress {
+    /// Creates an address from a host, either an IP address or hostname, and a port
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        ServerAddress {
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// IP address or hostname of the server, IPv6 addresses are returned without brackets
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port of the server
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Resolves the address, hostnames may resolve to several addresses
+    pub fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        self.to_socket_addrs().map(|addrs| addrs.collect())
+    }
+}
+
+impl FromStr for ServerAddress {
+    type Err = AddressError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+
+        let input = match input.get(..STEAM_CONNECT.len()) {
+            Some(scheme) if scheme.eq_ignore_ascii_case(STEAM_CONNECT) => {
+                let rest = &input[STEAM_CONNECT.len()..];
+                // Drop the optional password
+                rest.split('/').next().unwrap_or("")
+            }
+            _ => input,
+        };
+
+        let (host, port) = if let Some(rest) = input.strip_prefix('[') {
+            // Bracketed IPv6 address, optionally followed by a port
+            let end = rest
+                .find(']')
+                .ok_or_else(|| AddressError::InvalidHost(input.to_string()))?;
+            let port = match &rest[end + 1..] {
+                "" => None,
+                port => Some(
+                    port.strip_prefix(':')
+                        .ok_or_else(|| AddressError::InvalidHost(input.to_string()))?,
+                ),
+            };
+
+            (&rest[..end], port)
+        } else if input.parse::<IpAddr>().is_ok() {
+            // Bare IPv4 or IPv6 address without a port
+            (input, None)
+        } else {
+            match input.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (input, None),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(AddressError::MissingHost);
+        }
+        // Colons are only valid in IPv6 addresses
+        let invalid = |c: char| c == ':' || c == '/' || c.is_whitespace();
+        if host.contains(invalid) && host.parse::<IpAddr>().is_err() {
+            return Err(AddressError::InvalidHost(host.to_string()));
+        }
+
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| AddressError::InvalidPort(port.to_string()))?,
+            None => DEFAULT_PORT,
+        };
+
+        Ok(ServerAddress::new(host, port))
+    }
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "[{}]:{}", self.host, self.port),
+            false => write!(f, "{}:{}", self.host, self.port),
+        }
+    }
+}
+
+impl From<SocketAddr> for ServerAddress {
+    fn from(input: SocketAddr) -> Self {
+        ServerAddress::new(input.ip().to_string(), input.port())
+    }
+}
+
+impl ToSocketAddrs for ServerAddress {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        (self.host.as_str(), self.port).to_socket_addrs()
+    }
+}
+
+// # Tests
+#[test]
+fn parse_addresses() {
+    let cases = [
+        ("1.2.3.4:27016", ServerAddress::new("1.2.3.4", 27016)),
+        ("1.2.3.4", ServerAddress::new("1.2.3.4", 27015)),
+        (
+            "[2001:db8::1]:27016",
+            ServerAddress::new("2001:db8::1", 27016),
+        ),
+        ("2001:db8::1", ServerAddress::new("2001:db8::1", 27015)),
+        (
+            "play.example.com:27016",
+            ServerAddress::new("play.example.com", 27016),
+        ),
+        (
+            "steam://connect/1.2.3.4:27016",
+            ServerAddress::new("1.2.3.4", 27016),
+        ),
+        (
+            "STEAM://connect/[::1]:27016/password",
+            ServerAddress::new("::1", 27016),
+        ),
+    ];
+
+    for (input, expected) in cases.iter() {
+        assert_eq!(Ok(expected.clone()), input.parse(), "{}", input);
+    }
+}
+
+#[test]
+fn invalid_addresses() {
+    assert_eq!(
+        Err(AddressError::InvalidPort("99999".to_string())),
+        "1.2.3.4:99999".parse::<ServerAddress>()
+    );
+    assert_eq!(
+        Err(AddressError::MissingHost),
+        "steam://connect/".parse::<ServerAddress>()
+    );
+    assert_eq!(
+        Err(AddressError::InvalidHost("[::1".to_string())),
+        "[::1".parse::<ServerAddress>()
+    );
+}
+
+#[test]
+fn display_and_resolve() {
+    let address = ServerAddress::new("::1", 27015);
+
+    assert_eq!("[::1]:27015", address.to_string());
+    assert_eq!(
+        vec!["[::1]:27015".parse::<SocketAddr>().unwrap()],
+        address.resolve().unwrap()
+    );
+}
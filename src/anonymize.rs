@@ -0,0 +1,122 @@
+use crate::info_source::SourceResponseInfo;
+use crate::parser_util::fnv1a;
+use crate::player::ResponsePlayer;
+
+/// Replaces player names when redacting
+pub const REDACTED: &str = "[redacted]";
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// How identifying values are scrubbed
+pub enum Mode {
+    /// Names are replaced with [`REDACTED`] and Steam IDs are removed
+    Redact,
+    /// Names and Steam IDs are replaced with a salted hash, the same value always results in the same hash for a given salt
+    Hash(String),
+}
+
+/**
+Scrubs player names and Steam IDs from responses before they are logged or exported,
+for operators that have to store query results under privacy constraints.
+
+Hashing keeps values comparable across polls without storing them, it is pseudonymization and not encryption.
+The hash is not cryptographic so the salt has to be kept secret, otherwise common names can be recovered by guessing.
+
+# Examples
+```
+use a2s_parse::anonymize::{Mode, Scrubber};
+
+let scrubber = Scrubber::new(Mode::Hash("secret salt".to_string()));
+
+assert_eq!(scrubber.name("Alice"), scrubber.name("Alice"));
+assert_ne!("Alice", scrubber.name("Alice"));
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scrubber {
+    mode: Mode,
+}
+
+impl Scrubber {
+    /// Creates a scrubber using the given mode
+    pub fn new(mode: Mode) -> Self {
+        Scrubber { mode }
+    }
+
+    /// Scrubs a single player name
+    pub fn name(&self, name: &str) -> String {
+        match &self.mode {
+            Mode::Redact => REDACTED.to_string(),
+            Mode::Hash(salt) => {
+                format!("player-{:016x}", fnv1a(&[salt.as_bytes(), name.as_bytes()]))
+            }
+        }
+    }
+
+    /// Scrubs a Steam ID, redacting removes it entirely
+    pub fn steam_id(&self, steam_id: u64) -> Option<u64> {
+        match &self.mode {
+            Mode::Redact => None,
+            Mode::Hash(salt) => Some(fnv1a(&[salt.as_bytes(), &steam_id.to_le_bytes()])),
+        }
+    }
+
+    /// Returns a copy of the response with every player name scrubbed
+    pub fn players(&self, response: &ResponsePlayer) -> ResponsePlayer {
+        let mut response = response.clone();
+        for player in &mut response.player_data {
+            player.name = self.name(&player.name);
+        }
+
+        response
+    }
+
+    /// Returns a copy of the response with the server Steam ID scrubbed
+    pub fn source_info(&self, response: &SourceResponseInfo) -> SourceResponseInfo {
+        let mut response = response.clone();
+        let fields = &mut response.extra_data_fields;
+        fields.steam_id = fields.steam_id.and_then(|id| self.steam_id(id));
+        // Keep the flag consistent with the fields that are left
+        if fields.steam_id.is_none() {
+            response.extra_data_flag &= !0x10;
+        }
+
+        response
+    }
+}
+
+// # Tests
+#[test]
+fn redact_players() {
+    use crate::player::PlayerData;
+
+    let response = ResponsePlayer {
+        players: 2,
+        player_data: vec![PlayerData {
+            index: 0,
+            name: "Alice".to_string(),
+            score: 3,
+            duration: 10.0,
+            ship_data: None,
+        }],
+    };
+
+    let scrubbed = Scrubber::new(Mode::Redact).players(&response);
+
+    assert_eq!(REDACTED, scrubbed.player_data[0].name);
+    assert_eq!(3, scrubbed.player_data[0].score);
+    assert_eq!(
+        None,
+        Scrubber::new(Mode::Redact).steam_id(76561197960287930)
+    );
+}
+
+#[test]
+fn hash_depends_on_salt() {
+    let a = Scrubber::new(Mode::Hash("a".to_string()));
+    let b = Scrubber::new(Mode::Hash("b".to_string()));
+
+    assert_eq!(a.name("Alice"), a.name("Alice"));
+    assert_ne!(a.name("Alice"), b.name("Alice"));
+    assert_ne!(a.steam_id(1), b.steam_id(1));
+}
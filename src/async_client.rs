@@ -0,0 +1,1083 @@
+use crate::challenge::ChallengeResponse;
+use crate::client::{
+    is_timeout, skip_unanswered, Answer, Attempt, ClientError, OptionalQuery, QueryOptions,
+    QueryPolicy, QuerySpan, QueryStats, ResponseFilter, ServerSnapshot, BUFFER_SIZE,
+    MAX_CHALLENGES,
+};
+use crate::info_source::SourceResponseInfo;
+use crate::message::A2sMessage;
+use crate::player::ResponsePlayer;
+use crate::requests::{build_info_request, build_player_request, build_rules_request};
+use crate::rules::ResponseRule;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+
+/// Boxed future returned by [`AsyncDatagramTransport`] methods
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+// # Traits
+/**
+Datagram socket connected to a server, implemented for the socket of whichever async runtime is used.
+
+Implementations for `tokio::net::UdpSocket` and `async_std::net::UdpSocket` are provided with the `tokio` and
+`async-std` features, implement it for other runtimes or I/O stacks to use [`AsyncA2SClient`] with them.
+*/
+pub trait AsyncDatagramTransport: Send + Sync {
+    /// Sends the datagram to the server
+    fn send<'a>(&'a self, datagram: &'a [u8]) -> TransportFuture<'a, ()>;
+
+    /// Receives a datagram from the server into the buffer and returns its size,
+    /// fails with [`io::ErrorKind::TimedOut`] if none arrives before the deadline
+    fn recv_until<'a>(
+        &'a self,
+        buffer: &'a mut [u8],
+        deadline: Instant,
+    ) -> TransportFuture<'a, usize>;
+
+    /// Address of the server, [`QueryOptions::cache`] and [`QueryOptions::capture`] only work with transports that
+    /// know it. None by default
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Local address the transport is bound to, captures use the unspecified address if it isn't known. None by default
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Waits until the deadline. The default receives and drops datagrams until the deadline passes,
+    /// override it with the runtime's timer
+    fn sleep_until(&self, deadline: Instant) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let mut buffer = vec![0; BUFFER_SIZE];
+            while Instant::now() < deadline {
+                match self.recv_until(&mut buffer, deadline).await {
+                    Err(e) if is_timeout(&e) => break,
+                    result => drop(result?),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// # Structs
+/**
+Async counterpart to [`A2SClient`](crate::client::A2SClient), generic over the transport so it works with any runtime.
+
+Challenges, split responses and the [`QueryOptions`] are handled the same way: requests that time out are resent
+after the backoff, the rate limit applies to every request sent and fresh responses are answered from the cache.
+The waits use [`AsyncDatagramTransport::sleep_until`] rather than the clock of the options, which only measures the
+[`QueryStats`].
+
+# Examples
+```no_run
+# #[cfg(feature = "tokio")]
+# async fn run() -> Result<(), Box<dyn std::error::Error>> {
+use a2s_parse::async_client::AsyncA2SClient;
+use tokio::net::UdpSocket;
+
+let socket = UdpSocket::bind("0.0.0.0:0").await?;
+socket.connect("127.0.0.1:27015").await?;
+
+let client = AsyncA2SClient::new(socket);
+let info = client.info().await?;
+println!("{} on {}", info.name, info.map);
+# Ok(())
+# }
+```
+*/
+#[derive(Debug)]
+pub struct AsyncA2SClient<T> {
+    transport: T,
+    policy: QueryPolicy,
+}
+
+impl<T: AsyncDatagramTransport> AsyncA2SClient<T> {
+    /// Same as [`AsyncA2SClient::with_options`] with the default [`QueryOptions`]
+    pub fn new(transport: T) -> Self {
+        AsyncA2SClient::with_options(transport, QueryOptions::default())
+    }
+
+    /// Creates a client sending over the transport, which has to be connected to the server already
+    pub fn with_options(transport: T, options: QueryOptions) -> Self {
+        AsyncA2SClient {
+            transport,
+            policy: QueryPolicy::new(options),
+        }
+    }
+
+    /// The transport the client sends over
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Statistics of the last query, failed ones included. [`AsyncA2SClient::query_all`] counts as one query
+    pub fn last_stats(&self) -> QueryStats {
+        self.policy.last_stats()
+    }
+
+    /// Statistics of all queries made by the client
+    pub fn total_stats(&self) -> QueryStats {
+        self.policy.total_stats()
+    }
+
+    /// Queries A2S_INFO
+    pub async fn info(&self) -> Result<SourceResponseInfo, ClientError> {
+        self.measured("info", build_info_request).await
+    }
+
+    /// Queries A2S_PLAYER
+    pub async fn players(&self) -> Result<ResponsePlayer, ClientError> {
+        self.measured("players", |challenge| {
+            build_player_request(challenge.unwrap_or(-1))
+        })
+        .await
+    }
+
+    /// Queries A2S_RULES
+    pub async fn rules(&self) -> Result<ResponseRule, ClientError> {
+        self.measured("rules", |challenge| {
+            build_rules_request(challenge.unwrap_or(-1))
+        })
+        .await
+    }
+
+    /// Queries A2S_INFO, A2S_PLAYER and A2S_RULES one after the other, sharing the challenge,
+    /// see [`A2SClient::query_all`](crate::client::A2SClient::query_all)
+    pub async fn query_all(&self) -> Result<ServerSnapshot, ClientError> {
+        let span = QuerySpan::new("all", || self.addresses());
+        let start = self.policy.now();
+        let mut stats = QueryStats::default();
+        let result = span.instrument(self.snapshot(&mut stats)).await;
+        span.finish(&stats, &result);
+        self.policy
+            .record(start, stats, &result, || self.addresses());
+        result
+    }
+
+    /**
+    Stream of snapshots from [`AsyncA2SClient::query_all`], the first right away and then one every interval.
+
+    Ticks missed because a query took longer than the interval are skipped. Failed queries are yielded as errors
+    and the stream carries on, it never ends.
+
+    # Examples
+    ```no_run
+    # #[cfg(feature = "tokio")]
+    # async fn run(client: a2s_parse::async_client::AsyncA2SClient<tokio::net::UdpSocket>) {
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    let mut snapshots = client.watch(Duration::from_secs(10));
+    while let Some(snapshot) = snapshots.next().await {
+        match snapshot {
+            Ok(snapshot) => println!("{} players", snapshot.info.players),
+            Err(e) => println!("{}", e),
+        }
+    }
+    # }
+    ```
+    */
+    pub fn watch(&self, interval: Duration) -> Watch<'_, T> {
+        Watch {
+            client: self,
+            interval,
+            next_tick: Instant::now(),
+            pending: None,
+        }
+    }
+
+    /// Runs a single query within its [`QuerySpan`] collecting its statistics
+    async fn measured<R: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        name: &'static str,
+        build_request: impl Fn(Option<i32>) -> Vec<u8>,
+    ) -> Result<R, ClientError> {
+        let span = QuerySpan::new(name, || self.addresses());
+        let start = self.policy.now();
+        let mut stats = QueryStats::default();
+        let result = span
+            .instrument(self.query(build_request, &mut None, &mut stats))
+            .await;
+        span.finish(&stats, &result);
+        self.policy
+            .record(start, stats, &result, || self.addresses());
+        result
+    }
+
+    /// Queries the snapshot for [`AsyncA2SClient::query_all`]
+    async fn snapshot(&self, stats: &mut QueryStats) -> Result<ServerSnapshot, ClientError> {
+        let mut challenge = None;
+        let mut unanswered = Vec::new();
+
+        let info = self
+            .query(build_info_request, &mut challenge, stats)
+            .await?;
+        let players = self
+            .query(
+                |challenge| build_player_request(challenge.unwrap_or(-1)),
+                &mut challenge,
+                stats,
+            )
+            .await;
+        let players = skip_unanswered(
+            OptionalQuery::Players,
+            players,
+            &self.policy.optional,
+            &mut unanswered,
+        )?;
+        let rules = self
+            .query(
+                |challenge| build_rules_request(challenge.unwrap_or(-1)),
+                &mut challenge,
+                stats,
+            )
+            .await;
+        let rules = skip_unanswered(
+            OptionalQuery::Rules,
+            rules,
+            &self.policy.optional,
+            &mut unanswered,
+        )?;
+
+        Ok(ServerSnapshot {
+            info,
+            players,
+            rules,
+            unanswered,
+        })
+    }
+
+    /// Sends the request and resends it with the challenge the server answers with if any, the challenge is updated
+    /// if the server hands out a new one. A fresh cached response is returned without querying the server
+    async fn query<R: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        build_request: impl Fn(Option<i32>) -> Vec<u8>,
+        challenge: &mut Option<i32>,
+        stats: &mut QueryStats,
+    ) -> Result<R, ClientError> {
+        let server = self.transport.peer_addr();
+        if let Some(response) = self.policy.cached(server) {
+            return Ok(response);
+        }
+
+        for _ in 0..=MAX_CHALLENGES {
+            let filter = ResponseFilter::answer(R::HEADER);
+            let (rtt, payload) = self
+                .exchange(&build_request(*challenge), &filter, stats)
+                .await?;
+
+            match self.policy.answer::<R>(&payload, rtt, stats)? {
+                Answer::Challenge(value) => {
+                    *challenge = Some(value);
+                }
+                Answer::Response(response) => {
+                    self.policy.store(server, &response);
+                    return Ok(response);
+                }
+            }
+        }
+
+        Err(ClientError::UnexpectedHeader(ChallengeResponse::HEADER))
+    }
+
+    /// Sends the request and receives the response passing the filter, resending it after timeouts until the retries run out.
+    /// Returns the time from sending the answered request until the response along with the response
+    async fn exchange(
+        &self,
+        request: &[u8],
+        filter: &ResponseFilter,
+        stats: &mut QueryStats,
+    ) -> Result<(Duration, Vec<u8>), ClientError> {
+        let mut retries = self.policy.retries;
+
+        loop {
+            self.throttle().await?;
+            let start = self.policy.now();
+            self.transport.send(request).await?;
+            stats.sent(request);
+            self.policy.sent(|| self.addresses(), request);
+
+            let deadline = Instant::now() + self.policy.timeout;
+            let result = self.receive(filter.clone(), deadline, stats).await;
+            match self
+                .policy
+                .attempted(result, &mut retries, stats, || self.addresses())
+            {
+                Attempt::Retry(delay) => self.transport.sleep_until(Instant::now() + delay).await?,
+                Attempt::Done(result) => {
+                    let rtt = self.policy.now().saturating_duration_since(start);
+                    return result.map(|payload| (rtt, payload));
+                }
+            }
+        }
+    }
+
+    /// Local address and address of the server, the local address is unspecified if the transport doesn't know it
+    fn addresses(&self) -> Option<(SocketAddr, SocketAddr)> {
+        let server = self.transport.peer_addr()?;
+        let local = self.transport.local_addr().unwrap_or_else(|| match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        });
+
+        Some((local, server))
+    }
+
+    /// Waits until the rate limit allows sending a request
+    async fn throttle(&self) -> io::Result<()> {
+        loop {
+            match self.policy.try_throttle() {
+                wait if wait == Duration::from_secs(0) => return Ok(()),
+                wait => self.transport.sleep_until(Instant::now() + wait).await?,
+            }
+        }
+    }
+
+    /// Receives datagrams until one passes the filter and returns its payload following the `-1` prefix,
+    /// split responses are reassembled first
+    async fn receive(
+        &self,
+        mut filter: ResponseFilter,
+        deadline: Instant,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<u8>, ClientError> {
+        let mut buffer = vec![0; BUFFER_SIZE];
+
+        loop {
+            let received = self.transport.recv_until(&mut buffer, deadline).await?;
+            stats.received(&buffer[..received]);
+            self.policy
+                .received(|| self.addresses(), &buffer[..received]);
+            if let Some(payload) = self
+                .policy
+                .filter(&mut filter, &buffer[..received], stats)?
+            {
+                return Ok(payload);
+            }
+        }
+    }
+}
+
+/// Query in flight for the next snapshot of a [`Watch`]
+type SnapshotFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<ServerSnapshot, ClientError>> + Send + 'a>>;
+
+/// Stream returned by [`AsyncA2SClient::watch`]
+pub struct Watch<'a, T> {
+    client: &'a AsyncA2SClient<T>,
+    interval: Duration,
+    next_tick: Instant,
+    pending: Option<SnapshotFuture<'a>>,
+}
+
+impl<T> std::fmt::Debug for Watch<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watch")
+            .field("interval", &self.interval)
+            .field("next_tick", &self.next_tick)
+            .finish()
+    }
+}
+
+impl<T: AsyncDatagramTransport> Stream for Watch<'_, T> {
+    type Item = Result<ServerSnapshot, ClientError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let pending = match &mut this.pending {
+            Some(pending) => pending,
+            None => {
+                let client = this.client;
+                let tick = this.next_tick.max(Instant::now());
+                this.next_tick = tick + this.interval;
+
+                this.pending.get_or_insert(Box::pin(async move {
+                    client.transport.sleep_until(tick).await?;
+                    client.query_all().await
+                }))
+            }
+        };
+
+        match pending.as_mut().poll(cx) {
+            Poll::Ready(result) => {
+                this.pending = None;
+                Poll::Ready(Some(result))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/**
+Queries every server with [`AsyncA2SClient::query_all`], running at most `concurrency_limit` queries at once,
+and yields each server with its result as soon as it completes.
+
+`connect` creates the client for a server, which is where the transport is bound and the [`QueryOptions`] are set.
+Share a [`RateLimiter`](crate::rate_limit::RateLimiter) between the options of all clients to limit the scan as a whole.
+The queries are polled by the stream itself so no tasks are spawned, they only make progress while the stream is polled.
+
+The servers are queued with [`Priority::Normal`], more can be queued while the scan runs through [`Scan::queue`], e.g.
+to refresh the server a user clicked on ahead of the rest of a crawl. The stream ends once every queued server was
+queried and no [`ScanQueue`] is left.
+
+# Examples
+```no_run
+# #[cfg(feature = "tokio")]
+# async fn run(servers: Vec<std::net::SocketAddr>) {
+use a2s_parse::async_client::{scan, AsyncA2SClient};
+use futures::StreamExt;
+use tokio::net::UdpSocket;
+
+let mut results = scan(servers, 256, |server| async move {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+    Ok(AsyncA2SClient::new(socket))
+});
+while let Some((server, result)) = results.next().await {
+    println!("{}: {:?}", server, result.map(|snapshot| snapshot.info.name));
+}
+# }
+```
+*/
+pub fn scan<F, Fut, T>(
+    servers: impl IntoIterator<Item = SocketAddr>,
+    concurrency_limit: usize,
+    connect: F,
+) -> Scan<F>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<AsyncA2SClient<T>>> + Send + 'static,
+    T: AsyncDatagramTransport + 'static,
+{
+    let scan = Scan {
+        waiting: Arc::new(Mutex::new(Waiting::default())),
+        concurrency_limit: concurrency_limit.max(1),
+        connect,
+        in_flight: Vec::new(),
+    };
+    let queue = scan.queue();
+    for server in servers {
+        queue.push(server, Priority::Normal, None);
+    }
+    drop(queue);
+
+    scan
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Priority of a server queued in a [`Scan`], servers of a higher priority are queried first
+pub enum Priority {
+    /// Queried once nothing else is waiting, e.g. a background crawl of the full server list
+    Low,
+    /// The priority of the servers passed to [`scan`]
+    Normal,
+    /// Queried before anything else that is waiting, e.g. a server the user asked to refresh
+    High,
+}
+
+/**
+Cancels queries queued in a [`Scan`], shared by all of its clones.
+
+Cancelled servers that are still waiting are never queried and queries in flight are dropped, neither is yielded by the
+scan. The rate limit and the concurrency limit are then left to the servers that are still wanted.
+
+# Examples
+```
+use a2s_parse::async_client::CancellationToken;
+
+let token = CancellationToken::new();
+let shared = token.clone();
+
+token.cancel();
+assert!(shared.is_cancelled());
+```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    shared: Arc<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    /// Woken when the token is cancelled
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    /// Token that isn't cancelled yet
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Cancels every query of the token and its clones
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, AtomicOrdering::SeqCst);
+        let wakers =
+            std::mem::take(&mut *self.shared.wakers.lock().unwrap_or_else(|e| e.into_inner()));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// True once the token or one of its clones was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Future completing once the token is cancelled, to race it against a single query
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled { token: self }
+    }
+
+    /// Wakes the task when the token is cancelled
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.shared.wakers.lock().unwrap_or_else(|e| e.into_inner());
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+/// Future returned by [`CancellationToken::cancelled`]
+#[derive(Debug)]
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.token.register(cx.waker());
+        match self.token.is_cancelled() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Handle queueing servers in a running [`Scan`], returned by [`Scan::queue`]
+#[derive(Clone, Debug)]
+pub struct ScanQueue {
+    shared: Arc<Mutex<Waiting>>,
+}
+
+impl ScanQueue {
+    /// Queues the server to be queried before the waiting servers of a lower priority and after those of the same
+    /// priority. It is skipped if the token is cancelled before its query completes
+    pub fn push(&self, server: SocketAddr, priority: Priority, token: Option<CancellationToken>) {
+        let mut waiting = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        let order = waiting.pushed;
+        waiting.pushed += 1;
+        waiting.servers.push(Queued {
+            server,
+            priority,
+            order,
+            token,
+        });
+        waiting.wake();
+    }
+}
+
+impl Drop for ScanQueue {
+    /// The scan may end once the last queue is gone
+    fn drop(&mut self) {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner()).wake();
+    }
+}
+
+/// Servers waiting to be queried by a [`Scan`]
+#[derive(Debug, Default)]
+struct Waiting {
+    servers: BinaryHeap<Queued>,
+    /// Number of servers pushed so far, keeps servers of the same priority in order
+    pushed: u64,
+    /// Woken when a server is queued or a queue is dropped
+    waker: Option<Waker>,
+}
+
+impl Waiting {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Server waiting in a [`Scan`], the greatest is queried first
+#[derive(Debug)]
+struct Queued {
+    server: SocketAddr,
+    priority: Priority,
+    order: u64,
+    token: Option<CancellationToken>,
+}
+
+impl Queued {
+    fn is_cancelled(&self) -> bool {
+        matches!(&self.token, Some(token) if token.is_cancelled())
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.priority, Reverse(self.order)).cmp(&(other.priority, Reverse(other.order)))
+    }
+}
+
+/// Query of one server in flight in a [`Scan`]
+type ScanFuture =
+    Pin<Box<dyn Future<Output = (SocketAddr, Result<ServerSnapshot, ClientError>)> + Send>>;
+
+/// Stream returned by [`scan`]
+pub struct Scan<F> {
+    waiting: Arc<Mutex<Waiting>>,
+    concurrency_limit: usize,
+    connect: F,
+    in_flight: Vec<(Option<CancellationToken>, ScanFuture)>,
+}
+
+impl<F> Scan<F> {
+    /// Handle queueing more servers while the scan runs, the scan doesn't end while one exists
+    pub fn queue(&self) -> ScanQueue {
+        ScanQueue {
+            shared: Arc::clone(&self.waiting),
+        }
+    }
+}
+
+impl<F> std::fmt::Debug for Scan<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let waiting = self.waiting.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("Scan")
+            .field("remaining", &waiting.servers.len())
+            .field("in_flight", &self.in_flight.len())
+            .finish()
+    }
+}
+
+impl<F, Fut, T> Stream for Scan<F>
+where
+    F: FnMut(SocketAddr) -> Fut + Unpin,
+    Fut: Future<Output = io::Result<AsyncA2SClient<T>>> + Send + 'static,
+    T: AsyncDatagramTransport + 'static,
+{
+    type Item = (SocketAddr, Result<ServerSnapshot, ClientError>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        this.in_flight
+            .retain(|(token, _)| !matches!(token, Some(token) if token.is_cancelled()));
+
+        let mut waiting = this.waiting.lock().unwrap_or_else(|e| e.into_inner());
+        while this.in_flight.len() < this.concurrency_limit {
+            let queued = match waiting.servers.pop() {
+                Some(queued) if queued.is_cancelled() => continue,
+                Some(queued) => queued,
+                None => break,
+            };
+            let server = queued.server;
+            let client = (this.connect)(server);
+            this.in_flight.push((
+                queued.token,
+                Box::pin(async move {
+                    let result = match client.await {
+                        Ok(client) => client.query_all().await,
+                        Err(e) => Err(e.into()),
+                    };
+                    (server, result)
+                }),
+            ));
+        }
+        waiting.waker = Some(cx.waker().clone());
+        drop(waiting);
+
+        if this.in_flight.is_empty() && Arc::strong_count(&this.waiting) == 1 {
+            return Poll::Ready(None);
+        }
+
+        for index in 0..this.in_flight.len() {
+            let (token, query) = &mut this.in_flight[index];
+            if let Some(token) = token {
+                token.register(cx.waker());
+            }
+            if let Poll::Ready(item) = query.as_mut().poll(cx) {
+                drop(this.in_flight.swap_remove(index));
+                return Poll::Ready(Some(item));
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+// # Runtime adapters
+#[cfg(feature = "tokio")]
+impl AsyncDatagramTransport for tokio::net::UdpSocket {
+    fn send<'a>(&'a self, datagram: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            tokio::net::UdpSocket::send(self, datagram)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    fn recv_until<'a>(
+        &'a self,
+        buffer: &'a mut [u8],
+        deadline: Instant,
+    ) -> TransportFuture<'a, usize> {
+        Box::pin(async move {
+            match tokio::time::timeout_at(deadline.into(), self.recv(buffer)).await {
+                Ok(result) => result,
+                Err(_) => Err(io::ErrorKind::TimedOut.into()),
+            }
+        })
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        tokio::net::UdpSocket::peer_addr(self).ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        tokio::net::UdpSocket::local_addr(self).ok()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl AsyncDatagramTransport for async_std::net::UdpSocket {
+    fn send<'a>(&'a self, datagram: &'a [u8]) -> TransportFuture<'a, ()> {
+        Box::pin(async move {
+            async_std::net::UdpSocket::send(self, datagram)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    fn recv_until<'a>(
+        &'a self,
+        buffer: &'a mut [u8],
+        deadline: Instant,
+    ) -> TransportFuture<'a, usize> {
+        Box::pin(async move {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match async_std::future::timeout(remaining, self.recv(buffer)).await {
+                Ok(result) => result,
+                Err(_) => Err(io::ErrorKind::TimedOut.into()),
+            }
+        })
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        async_std::net::UdpSocket::peer_addr(self).ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        async_std::net::UdpSocket::local_addr(self).ok()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            async_std::task::sleep(deadline.saturating_duration_since(Instant::now())).await;
+            Ok(())
+        })
+    }
+}
+
+// # Tests
+/// Transport answering each request with the next list of datagrams, an empty list times out
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct ScriptedTransport {
+    responses: std::sync::Mutex<std::collections::VecDeque<Vec<Vec<u8>>>>,
+    pending: std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>,
+    pub(crate) requests: std::sync::Mutex<Vec<Vec<u8>>>,
+    /// Time left until each deadline slept until
+    sleeps: std::sync::Mutex<Vec<Duration>>,
+    /// Advanced by every sleep
+    clock: crate::clock::ManualClock,
+}
+
+#[cfg(test)]
+impl ScriptedTransport {
+    pub(crate) fn new(responses: Vec<Vec<Vec<u8>>>) -> Self {
+        ScriptedTransport {
+            responses: std::sync::Mutex::new(responses.into()),
+            ..ScriptedTransport::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl AsyncDatagramTransport for ScriptedTransport {
+    fn send<'a>(&'a self, datagram: &'a [u8]) -> TransportFuture<'a, ()> {
+        self.requests.lock().unwrap().push(datagram.to_vec());
+        let datagrams = self.responses.lock().unwrap().pop_front();
+        self.pending
+            .lock()
+            .unwrap()
+            .extend(datagrams.into_iter().flatten());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn recv_until<'a>(
+        &'a self,
+        buffer: &'a mut [u8],
+        _deadline: Instant,
+    ) -> TransportFuture<'a, usize> {
+        let datagram = self.pending.lock().unwrap().pop_front();
+        Box::pin(async move {
+            let datagram = datagram.ok_or(io::ErrorKind::TimedOut)?;
+            buffer[..datagram.len()].copy_from_slice(&datagram);
+            Ok(datagram.len())
+        })
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::from(([127, 0, 0, 1], 27015)))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> TransportFuture<'_, ()> {
+        let duration = deadline.saturating_duration_since(Instant::now());
+        self.sleeps.lock().unwrap().push(duration);
+        // Rounded up to make up for the time that passed since the deadline was set
+        self.clock
+            .advance(Duration::from_millis(duration.as_millis() as u64 + 1));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[test]
+fn async_query_all() {
+    use crate::challenge::build_challenge_response;
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().map("cp_badlands").build();
+    let transport = ScriptedTransport::new(vec![
+        vec![build_challenge_response(3)],
+        // Dropped, the request is resent
+        vec![],
+        vec![info.to_framed_bytes()],
+        vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00]],
+        vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00]],
+    ]);
+
+    let client = AsyncA2SClient::new(transport);
+    let query = client.query_all();
+    // Has to be spawnable on multi threaded runtimes
+    fn assert_send<F: Send>(_: &F) {}
+    assert_send(&query);
+    let snapshot = futures::executor::block_on(query).unwrap();
+
+    assert_eq!(info, snapshot.info);
+    let requests = client.transport().requests.lock().unwrap();
+    assert_eq!(5, requests.len());
+    assert_eq!(build_info_request(Some(3)), requests[2]);
+    assert_eq!(build_rules_request(3), requests[4]);
+}
+
+#[test]
+fn async_query_options() {
+    use crate::backoff::Fixed;
+    use crate::cache::ResponseCache;
+    use crate::info_source::ServerInfoBuilder;
+    use crate::rate_limit::RateLimiter;
+    use std::sync::Arc;
+
+    let info = ServerInfoBuilder::new().build();
+    let transport = ScriptedTransport::new(vec![vec![], vec![info.to_framed_bytes()]]);
+    let clock = Arc::new(transport.clock.clone());
+    let options = QueryOptions {
+        backoff: Box::new(Fixed(Duration::from_millis(200))),
+        clock: clock.clone(),
+        rate_limit: Some(Arc::new(RateLimiter::with_clock(1, 1, clock))),
+        cache: Some(Arc::new(ResponseCache::new(Duration::from_secs(60)))),
+        ..QueryOptions::default()
+    };
+    let client = AsyncA2SClient::with_options(transport, options);
+
+    assert_eq!(info, futures::executor::block_on(client.info()).unwrap());
+    let stats = client.last_stats();
+    assert_eq!(2, stats.datagrams_sent);
+    assert_eq!(1, stats.datagrams_received);
+    assert_eq!(1, stats.retries);
+    assert!(stats.elapsed >= Duration::from_millis(900));
+    // The backoff delays the resent request, the rate limit holds it back for the rest of the second
+    let sleeps = client.transport().sleeps.lock().unwrap().clone();
+    assert_eq!(2, sleeps.len());
+    assert!(sleeps[0] > Duration::from_millis(150) && sleeps[0] <= Duration::from_millis(200));
+    assert!(sleeps[1] > Duration::from_millis(700));
+
+    // Answered from the cache
+    assert_eq!(info, futures::executor::block_on(client.info()).unwrap());
+    assert_eq!(0, client.last_stats().datagrams_sent);
+    assert_eq!(2, client.total_stats().datagrams_sent);
+}
+
+#[test]
+fn watch_snapshots() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    let poll = vec![
+        vec![info.to_framed_bytes()],
+        vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00]],
+        vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00]],
+    ];
+    let mut responses = poll.clone();
+    // The second poll times out on A2S_INFO
+    responses.push(vec![]);
+    responses.extend(poll);
+    let transport = ScriptedTransport::new(responses);
+
+    let options = QueryOptions {
+        retries: 0,
+        ..QueryOptions::default()
+    };
+    let client = AsyncA2SClient::with_options(transport, options);
+    let snapshots: Vec<_> =
+        futures::executor::block_on_stream(client.watch(Duration::from_millis(1)))
+            .take(3)
+            .collect();
+
+    assert!(snapshots[0].is_ok());
+    assert!(matches!(&snapshots[1], Err(ClientError::Io(e)) if is_timeout(e)));
+    assert_eq!(info, snapshots[2].as_ref().unwrap().info);
+}
+
+#[test]
+fn scan_servers() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    let servers: Vec<SocketAddr> = (1..=5)
+        .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+        .collect();
+
+    let results = scan(servers.clone(), 2, |server| {
+        let responses = match server.port() {
+            // Never answers
+            3 => vec![],
+            _ => vec![
+                vec![info.to_framed_bytes()],
+                vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00]],
+                vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00]],
+            ],
+        };
+        let options = QueryOptions {
+            retries: 0,
+            ..QueryOptions::default()
+        };
+        let client = AsyncA2SClient::with_options(ScriptedTransport::new(responses), options);
+        async move { Ok(client) }
+    });
+    let mut results: Vec<_> = futures::executor::block_on_stream(results).collect();
+    results.sort_by_key(|(server, _)| *server);
+
+    assert_eq!(
+        servers,
+        results
+            .iter()
+            .map(|(server, _)| *server)
+            .collect::<Vec<_>>()
+    );
+    assert!(results[2].1.is_err());
+    assert_eq!(
+        4,
+        results.iter().filter(|(_, result)| result.is_ok()).count()
+    );
+}
+
+#[test]
+fn scan_priorities() {
+    use crate::info_source::ServerInfoBuilder;
+    use futures::task::noop_waker;
+
+    let info = ServerInfoBuilder::new().build();
+    let server = |port| SocketAddr::from(([127, 0, 0, 1], port));
+    let connect = |server: SocketAddr| -> Pin<
+        Box<dyn Future<Output = io::Result<AsyncA2SClient<ScriptedTransport>>> + Send>,
+    > {
+        let responses = vec![
+            vec![info.to_framed_bytes()],
+            vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00]],
+            vec![vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00]],
+        ];
+        match server.port() {
+            // Never connects
+            7 => Box::pin(futures::future::pending()),
+            _ => Box::pin(futures::future::ready(Ok(AsyncA2SClient::new(
+                ScriptedTransport::new(responses),
+            )))),
+        }
+    };
+
+    let results = scan(vec![server(1), server(2)], 1, connect);
+    let queue = results.queue();
+    let abandoned = CancellationToken::new();
+    queue.push(server(3), Priority::Low, None);
+    queue.push(server(4), Priority::High, None);
+    queue.push(server(5), Priority::High, Some(abandoned.clone()));
+    abandoned.cancel();
+    drop(queue);
+
+    let order: Vec<SocketAddr> = futures::executor::block_on_stream(results)
+        .map(|(server, result)| {
+            assert!(result.is_ok());
+            server
+        })
+        .collect();
+    assert_eq!(vec![server(4), server(1), server(2), server(3)], order);
+
+    // A query in flight is dropped once cancelled
+    let mut results = scan(Vec::new(), 1, connect);
+    let token = CancellationToken::new();
+    results
+        .queue()
+        .push(server(7), Priority::Normal, Some(token.clone()));
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    assert!(Pin::new(&mut results).poll_next(&mut cx).is_pending());
+    token.cancel();
+    assert!(matches!(
+        Pin::new(&mut results).poll_next(&mut cx),
+        Poll::Ready(None)
+    ));
+    futures::executor::block_on(token.cancelled());
+}
@@ -0,0 +1,174 @@
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// # Traits
+/**
+Decides how long to wait before retrying, used for query retries and challenge re-requests.
+
+Implement it to tune retry behaviour or to wrap an existing backoff crate.
+
+# Examples
+```
+use a2s_parse::backoff::{Backoff, Exponential};
+use std::time::Duration;
+
+let mut backoff = Exponential::new(Duration::from_millis(100), Duration::from_secs(1));
+
+assert_eq!(Duration::from_millis(100), backoff.next_delay());
+assert_eq!(Duration::from_millis(200), backoff.next_delay());
+assert_eq!(Duration::from_millis(400), backoff.next_delay());
+```
+*/
+pub trait Backoff: Debug + Send {
+    /// Delay before the next retry, each call is one more failed attempt
+    fn next_delay(&mut self) -> Duration;
+
+    /// Starts over after a successful attempt
+    fn reset(&mut self);
+}
+
+// # Structs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Waits the same duration before every retry
+pub struct Fixed(pub Duration);
+
+impl Backoff for Fixed {
+    fn next_delay(&mut self) -> Duration {
+        self.0
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Doubles the delay after every retry up to a maximum
+pub struct Exponential {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Exponential {
+    /// Starts at `base` and doubles up to `max`
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Exponential {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+}
+
+impl Backoff for Exponential {
+    fn next_delay(&mut self) -> Duration {
+        let delay = 2u32
+            .checked_pow(self.attempt)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max));
+        self.attempt = self.attempt.saturating_add(1);
+
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/**
+Randomised delay between `base` and three times the previous delay, capped at a maximum.
+
+Spreads out retries from many clients that failed at the same time, see
+[Exponential Backoff And Jitter](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/).
+The random numbers are not cryptographic.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecorrelatedJitter {
+    base: Duration,
+    max: Duration,
+    previous: Duration,
+    state: u64,
+}
+
+impl DecorrelatedJitter {
+    /// Creates a backoff seeded from the current time
+    pub fn new(base: Duration, max: Duration) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        DecorrelatedJitter::with_seed(base, max, seed)
+    }
+
+    /// Creates a backoff with a fixed seed so the delays are reproducible
+    pub fn with_seed(base: Duration, max: Duration, seed: u64) -> Self {
+        DecorrelatedJitter {
+            base,
+            max,
+            previous: base,
+            state: seed_random(seed),
+        }
+    }
+}
+
+impl Backoff for DecorrelatedJitter {
+    fn next_delay(&mut self) -> Duration {
+        let low = self.base.as_nanos() as u64;
+        let high = (self.previous.as_nanos() as u64).saturating_mul(3).max(low);
+        let range = high - low;
+        let nanos = match range {
+            0 => low,
+            _ => low + next_random(&mut self.state) % range,
+        };
+
+        self.previous = Duration::from_nanos(nanos).min(self.max);
+        self.previous
+    }
+
+    fn reset(&mut self) {
+        self.previous = self.base;
+    }
+}
+
+// # Private helper functions
+/// Turns a seed into a state for [`next_random`], xorshift gets stuck on 0
+pub(crate) fn seed_random(seed: u64) -> u64 {
+    seed | 1
+}
+
+/// Advances the state and returns the next random number, xorshift64*. The numbers are not cryptographic
+pub(crate) fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+}
+
+// # Tests
+#[test]
+fn exponential_caps_and_resets() {
+    let mut backoff = Exponential::new(Duration::from_secs(1), Duration::from_secs(5));
+
+    let delays: Vec<u64> = (0..40).map(|_| backoff.next_delay().as_secs()).collect();
+    backoff.reset();
+
+    assert_eq!(vec![1, 2, 4, 5, 5], delays[..5].to_vec());
+    assert_eq!(5, delays[39]);
+    assert_eq!(Duration::from_secs(1), backoff.next_delay());
+}
+
+#[test]
+fn jitter_stays_in_bounds() {
+    let base = Duration::from_millis(100);
+    let max = Duration::from_secs(2);
+    let mut backoff = DecorrelatedJitter::with_seed(base, max, 42);
+
+    for _ in 0..100 {
+        let delay = backoff.next_delay();
+        assert!(delay >= base && delay <= max);
+    }
+    assert_eq!(
+        DecorrelatedJitter::with_seed(base, max, 7).next_delay(),
+        DecorrelatedJitter::with_seed(base, max, 7).next_delay()
+    );
+}
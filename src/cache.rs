@@ -0,0 +1,245 @@
+use crate::clock::{Clock, SystemClock};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::parser_util::fnv1a;
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Number of payloads kept by [`ParseCache::new`]
+pub const DEFAULT_CAPACITY: usize = 256;
+
+// # Structs
+/**
+Cache of parsed messages keyed by a hash of their payload.
+
+Polling a stable server every few seconds mostly returns byte identical responses, the cache skips parsing those
+and returns the previously parsed message. Payloads are compared in full on a hash match so collisions can't
+return the wrong message. Once full the oldest payload is evicted.
+
+# Examples
+```
+use a2s_parse::cache::ParseCache;
+use a2s_parse::ping::PingResponse;
+use std::sync::Arc;
+
+let mut cache = ParseCache::<PingResponse>::new();
+
+let first = cache.parse(b"00000000000000\0").unwrap();
+let second = cache.parse(b"00000000000000\0").unwrap();
+
+assert!(Arc::ptr_eq(&first, &second));
+```
+*/
+#[derive(Clone, Debug)]
+pub struct ParseCache<T> {
+    entries: HashMap<u64, (Vec<u8>, Arc<T>)>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl<T: A2sMessage> ParseCache<T> {
+    /// Creates a cache holding up to [`DEFAULT_CAPACITY`] payloads
+    pub fn new() -> Self {
+        ParseCache::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a cache holding up to `capacity` payloads
+    pub fn with_capacity(capacity: usize) -> Self {
+        ParseCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Number of payloads currently cached
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if no payloads are cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every cached payload
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Parses the payload following the header byte, returning the cached message if the same payload was parsed before.
+    /// Payloads that fail to parse are not cached.
+    pub fn parse(&mut self, input: &[u8]) -> Result<Arc<T>, A2SError> {
+        let key = fnv1a(&[input]);
+        if let Some((payload, message)) = self.entries.get(&key) {
+            if payload.as_slice() == input {
+                return Ok(Arc::clone(message));
+            }
+        }
+
+        let message = Arc::new(T::parse(input)?);
+        if self.capacity == 0 {
+            return Ok(message);
+        }
+        // A colliding payload replaces the entry but keeps its place in the eviction order
+        if self
+            .entries
+            .insert(key, (input.to_vec(), Arc::clone(&message)))
+            .is_none()
+        {
+            self.order.push_back(key);
+        }
+        while self.entries.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        Ok(message)
+    }
+}
+
+impl<T: A2sMessage> Default for ParseCache<T> {
+    fn default() -> Self {
+        ParseCache::new()
+    }
+}
+
+/**
+Cache of responses keyed by the server and the type of query, entries expire after a time to live.
+
+Set it in [`QueryOptions`](crate::client::QueryOptions) so queries made within the time to live return the cached
+response instead of querying the server again. Share it between clients to serve many viewers of the same server.
+
+# Examples
+```
+use a2s_parse::cache::ResponseCache;
+use a2s_parse::ping::PingResponse;
+use std::time::Duration;
+
+let cache = ResponseCache::new(Duration::from_secs(5));
+let server = "127.0.0.1:27015".parse().unwrap();
+let response = PingResponse { payload: String::new() };
+
+cache.insert(server, response.clone());
+
+assert_eq!(Some(response), cache.get::<PingResponse>(server));
+```
+*/
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+    entries: Mutex<Responses>,
+}
+
+/// Responses by server and header byte with the time they were stored
+type Responses = HashMap<(SocketAddr, u8), (Instant, Arc<dyn Any + Send + Sync>)>;
+
+impl ResponseCache {
+    /// Creates a cache whose entries expire after `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        ResponseCache::with_clock(ttl, Arc::new(SystemClock))
+    }
+
+    /// Same as [`ResponseCache::new`] measuring time with the clock
+    pub fn with_clock(ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        ResponseCache {
+            ttl,
+            clock,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of responses currently cached, including expired ones that haven't been removed yet
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// True if no responses are cached
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    /// Removes every cached response
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    /// The response of the server to queries returning `T`, None if there is none or it expired
+    pub fn get<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        server: SocketAddr,
+    ) -> Option<T> {
+        let now = self.clock.now();
+        let entries = self.entries();
+        let (stored, response) = entries.get(&(server, T::HEADER))?;
+
+        match now.saturating_duration_since(*stored) < self.ttl {
+            true => response.downcast_ref::<T>().cloned(),
+            false => None,
+        }
+    }
+
+    /// Stores the response of the server, replacing any previous one and removing expired entries
+    pub fn insert<T: A2sMessage + Send + Sync + 'static>(&self, server: SocketAddr, response: T) {
+        let now = self.clock.now();
+        let ttl = self.ttl;
+        let mut entries = self.entries();
+
+        entries.retain(|_, (stored, _)| now.saturating_duration_since(*stored) < ttl);
+        entries.insert((server, T::HEADER), (now, Arc::new(response)));
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Responses> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// # Tests
+#[test]
+fn evicts_oldest() {
+    use crate::ping::PingResponse;
+
+    let mut cache = ParseCache::<PingResponse>::with_capacity(2);
+    let first = cache.parse(b"a\0").unwrap();
+    cache.parse(b"b\0").unwrap();
+    cache.parse(b"c\0").unwrap();
+
+    assert_eq!(2, cache.len());
+    assert!(!Arc::ptr_eq(&first, &cache.parse(b"a\0").unwrap()));
+    assert!(cache.parse(&[0xFF]).is_err());
+    assert_eq!(2, cache.len());
+}
+
+#[test]
+fn responses_expire() {
+    use crate::clock::ManualClock;
+    use crate::ping::PingResponse;
+    use crate::player::ResponsePlayer;
+
+    let clock = ManualClock::new();
+    let cache = ResponseCache::with_clock(Duration::from_secs(5), Arc::new(clock.clone()));
+    let server = "127.0.0.1:27015".parse().unwrap();
+    let ping = PingResponse {
+        payload: String::new(),
+    };
+
+    cache.insert(server, ping.clone());
+    clock.advance(Duration::from_secs(4));
+
+    assert_eq!(Some(ping), cache.get::<PingResponse>(server));
+    assert_eq!(None, cache.get::<ResponsePlayer>(server));
+    assert_eq!(
+        None,
+        cache.get::<PingResponse>("127.0.0.1:27016".parse().unwrap())
+    );
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(None, cache.get::<PingResponse>(server));
+}
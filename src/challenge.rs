@@ -0,0 +1,160 @@
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::parser_util::{all_consuming, ByteReader, ReadError};
+
+use std::convert::TryFrom;
+
+// # Structs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Challenge number sent by servers in response to a request, the request has to be resent with it
+before the server answers. See [the wiki](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format).
+
+# Examples
+```
+use a2s_parse::challenge::{append_challenge, Challenge};
+
+// A2S_PLAYER request asking for a challenge
+let mut request = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x55];
+append_challenge(&mut request, Challenge::REQUEST);
+
+assert_eq!(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x55, 0xFF, 0xFF, 0xFF, 0xFF], request);
+```
+*/
+pub struct Challenge(pub i32);
+
+impl Challenge {
+    /// Sent in place of a challenge to ask the server for one, -1
+    pub const REQUEST: Challenge = Challenge(-1);
+    /// [`REQUEST`](Challenge::REQUEST) as it is sent on the wire, `0xFFFFFFFF`
+    pub const SENTINEL: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    /// True if this is the -1 value used to request a challenge
+    pub fn is_request(self) -> bool {
+        self == Challenge::REQUEST
+    }
+
+    /// Little endian bytes of the challenge as they are sent on the wire
+    pub fn to_bytes(self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [S2C_CHALLENGE Response](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format_5) sent by servers
+/// that require a challenge, the request has to be resent with it
+pub struct ChallengeResponse {
+    /// Challenge value to send with the request
+    pub challenge: i32,
+}
+
+impl From<i32> for Challenge {
+    fn from(input: i32) -> Self {
+        Challenge(input)
+    }
+}
+
+impl From<Challenge> for i32 {
+    fn from(input: Challenge) -> Self {
+        input.0
+    }
+}
+
+// # Exposed functions
+/// Reads a challenge from the start of the input, any data after the challenge is ignored
+pub fn parse_challenge(input: &[u8]) -> Result<Challenge, A2SError> {
+    challenge(&mut ByteReader::new(input)).map_err(A2SError::from)
+}
+
+/// Appends the challenge to a request, every request that takes a challenge ends with it
+pub fn append_challenge(request: &mut Vec<u8>, challenge: Challenge) {
+    request.extend_from_slice(&challenge.to_bytes());
+}
+
+/**
+Builds a complete S2C_CHALLENGE datagram including the `-1` prefix and 'A' header, for servers answering requests.
+
+# Examples
+```
+use a2s_parse::challenge::{build_challenge_response, parse_challenge_response};
+
+let datagram = build_challenge_response(-362936310);
+
+assert_eq!(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x41, 0x0A, 0x08, 0x5E, 0xEA], datagram);
+assert_eq!(-362936310, parse_challenge_response(&datagram[5..]).unwrap().challenge);
+```
+*/
+pub fn build_challenge_response(challenge: i32) -> Vec<u8> {
+    ChallengeResponse { challenge }.to_framed_bytes()
+}
+
+/// Parses the payload of an S2C_CHALLENGE response following the 'A' header, the payload has to be exactly the challenge.
+/// Use [`A2sMessage::parse_framed`] on [`ChallengeResponse`] to parse the complete datagram.
+pub fn parse_challenge_response(input: &[u8]) -> Result<ChallengeResponse, A2SError> {
+    match all_consuming(input, challenge) {
+        Ok(challenge) => Ok(ChallengeResponse {
+            challenge: challenge.into(),
+        }),
+        Err(e) => Err(A2SError::from(e).with_field(&[ChallengeResponse::HEADER], input)),
+    }
+}
+
+// # Parsing helper functions
+/// Reads the 4 byte little endian challenge
+pub(crate) fn challenge(reader: &mut ByteReader) -> Result<Challenge, ReadError> {
+    reader.i32().map(Challenge)
+}
+
+// # Message implementation
+impl A2sMessage for ChallengeResponse {
+    const HEADER: u8 = 0x41;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_challenge_response(input)
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        append_challenge(output, Challenge(self.challenge));
+    }
+}
+
+/// Parses the payload following the header byte, same as [`parse_challenge_response`]
+impl<'a> TryFrom<&'a [u8]> for ChallengeResponse {
+    type Error = A2SError;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_challenge_response(input)
+    }
+}
+
+// # Tests
+#[test]
+fn parse_wiki_challenge() {
+    // Challenge from the A2S_INFO example on the wiki
+    let payload: [u8; 4] = [0x0A, 0x08, 0x5E, 0xEA];
+
+    let challenge = parse_challenge(&payload).unwrap();
+
+    assert_eq!(Challenge(-362936310), challenge);
+    assert_eq!(payload, challenge.to_bytes());
+    assert!(!challenge.is_request());
+}
+
+#[test]
+fn request_is_sentinel() {
+    assert_eq!(Challenge::SENTINEL, Challenge::REQUEST.to_bytes());
+    assert!(parse_challenge(&Challenge::SENTINEL).unwrap().is_request());
+}
+
+#[test]
+fn challenge_response_round_trip() {
+    let datagram = build_challenge_response(12345);
+
+    assert_eq!(
+        ChallengeResponse { challenge: 12345 },
+        ChallengeResponse::parse_framed(&datagram).unwrap()
+    );
+    assert!(parse_challenge_response(&[0x01, 0x02, 0x03]).is_err());
+}
@@ -0,0 +1,2145 @@
+use crate::backoff::{Backoff, Exponential};
+use crate::cache::ResponseCache;
+use crate::challenge::ChallengeResponse;
+use crate::clock::{Clock, SystemClock};
+use crate::error::A2SError;
+use crate::info_source::SourceResponseInfo;
+use crate::message::A2sMessage;
+use crate::observer::QueryObserver;
+use crate::packet::{
+    parse_framing, parse_multi_packet_auto, AssembleError, DatagramAssembler, FeedResult, Framing,
+    SplitPacket,
+};
+use crate::pcap::Capture;
+use crate::ping::PingResponse;
+use crate::player::ResponsePlayer;
+use crate::rate_limit::RateLimiter;
+use crate::requests::{build_info_request, build_player_request, build_rules_request, PingRequest};
+use crate::rules::ResponseRule;
+use crate::schedule::Schedule;
+
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Size of the receive buffer, the largest payload a UDP datagram can carry over IPv6 without jumbograms and 20 bytes
+/// more than over IPv4, so the operating system never cuts a datagram off
+pub const BUFFER_SIZE: usize = 65_527;
+
+/// Time to wait for a response before giving up
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Number of times a request is resent after a timeout by default
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Number of challenges accepted for one query before giving up, guards against servers that never stop challenging
+pub(crate) const MAX_CHALLENGES: usize = 3;
+
+// # Structs / Enums
+#[derive(Debug)]
+/// Reasons a query can fail
+pub enum ClientError {
+    /// Sending or receiving failed, timeouts are reported as [`io::ErrorKind::WouldBlock`] or [`io::ErrorKind::TimedOut`]
+    /// depending on the platform
+    Io(io::Error),
+    /// The server's host answered that nothing listens on the port, the server is down rather than slow to respond
+    PortClosed,
+    /// The response couldn't be parsed
+    Parse(A2SError),
+    /// The response is not the one that was asked for, holds the header byte. Responses with other headers are dropped
+    /// as late answers to earlier requests, so this is returned for servers that keep answering with challenges
+    UnexpectedHeader(u8),
+    /// The response didn't start with the single or split packet prefix
+    UnexpectedFraming,
+    /// A split response is compressed but the `compression` feature is disabled
+    Compressed,
+    /// The decompressed response doesn't match the size and checksum sent by the server
+    ChecksumMismatch,
+    /// The packets of a split response don't fit together, e.g. a packet number beyond the total or a response
+    /// exceeding the [`AssemblyLimits`](crate::packet::AssemblyLimits)
+    Assemble(AssembleError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "io error: {}", e),
+            ClientError::PortClosed => write!(f, "port is closed"),
+            ClientError::Parse(e) => write!(f, "failed to parse response: {}", e),
+            ClientError::UnexpectedHeader(header) => {
+                write!(f, "unexpected response header 0x{:02X}", header)
+            }
+            ClientError::UnexpectedFraming => write!(f, "response is not framed"),
+            ClientError::Compressed => {
+                write!(f, "response is compressed, enable the compression feature")
+            }
+            ClientError::ChecksumMismatch => write!(f, "decompressed response failed the checksum"),
+            ClientError::Assemble(e) => write!(f, "failed to reassemble split response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(e) => Some(e),
+            ClientError::Parse(e) => Some(e),
+            ClientError::Assemble(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    /// Refused connections are reported as [`ClientError::PortClosed`], every other error as [`ClientError::Io`]
+    fn from(input: io::Error) -> Self {
+        match is_port_closed(&input) {
+            true => ClientError::PortClosed,
+            false => ClientError::Io(input),
+        }
+    }
+}
+
+impl From<A2SError> for ClientError {
+    fn from(input: A2SError) -> Self {
+        ClientError::Parse(input)
+    }
+}
+
+impl From<AssembleError> for ClientError {
+    /// Invalid bzip2 data is reported as [`io::ErrorKind::InvalidData`], packets that don't fit the response as
+    /// [`ClientError::Assemble`]
+    fn from(input: AssembleError) -> Self {
+        match input {
+            AssembleError::Compressed => ClientError::Compressed,
+            AssembleError::ChecksumMismatch => ClientError::ChecksumMismatch,
+            AssembleError::Malformed(e) => ClientError::Parse(e),
+            AssembleError::Decompress(e) => {
+                ClientError::Io(io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            e => ClientError::Assemble(e),
+        }
+    }
+}
+
+/// Lets queries be mixed with other socket code in functions returning [`io::Result`].
+/// Io errors are returned as they are, closed ports as [`io::ErrorKind::ConnectionRefused`], parse errors are converted
+/// like an [`A2SError`] and anything else is reported as [`io::ErrorKind::InvalidData`]
+impl From<ClientError> for io::Error {
+    fn from(input: ClientError) -> Self {
+        match input {
+            ClientError::Io(e) => e,
+            ClientError::PortClosed => io::ErrorKind::ConnectionRefused.into(),
+            ClientError::Parse(e) => e.into(),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/**
+How long the client waits for responses and how it retries requests that timed out.
+
+Only timeouts are retried, responses that fail to parse are returned as errors straight away.
+
+# Examples
+```no_run
+use a2s_parse::backoff::Fixed;
+use a2s_parse::client::{A2SClient, QueryOptions};
+use std::time::Duration;
+
+let options = QueryOptions {
+    timeout: Duration::from_millis(500),
+    retries: 5,
+    backoff: Box::new(Fixed(Duration::from_millis(200))),
+    ..QueryOptions::default()
+};
+let client = A2SClient::with_options("127.0.0.1:27015", options)?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+#[derive(Debug)]
+pub struct QueryOptions {
+    /// Time to wait for each response
+    pub timeout: Duration,
+    /// Number of times a request is resent after a timeout
+    pub retries: u32,
+    /// Delay between a timeout and resending the request
+    pub backoff: Box<dyn Backoff>,
+    /// Clock used to wait between retries and to measure [`QueryStats::elapsed`],
+    /// [`AsyncA2SClient`](crate::async_client::AsyncA2SClient) waits with its transport instead
+    pub clock: Arc<dyn Clock>,
+    /// Limits how fast requests are sent, share it to limit several clients and pools together
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// Answers queries with cached responses while they are fresh, share it to cache across clients and pools
+    pub cache: Option<Arc<ResponseCache>>,
+    /// Records every datagram sent and received, e.g. to a [`PcapWriter`](crate::pcap::PcapWriter)
+    pub capture: Option<Arc<dyn Capture>>,
+    /// Notified of every datagram, retry and parse error, e.g. to count them in the application's metrics
+    pub observer: Option<Arc<dyn QueryObserver>>,
+    /// Queries [`A2SClient::query_all`] leaves out of the snapshot if they time out, the others have to be answered
+    pub optional: Vec<OptionalQuery>,
+    /// Which of the addresses a hostname resolves to [`A2SClient`] queries
+    pub addresses: AddressStrategy,
+}
+
+impl Default for QueryOptions {
+    /// [`DEFAULT_TIMEOUT`] and [`DEFAULT_RETRIES`] with an [`Exponential`] backoff from 100ms up to 1s without rate limit,
+    /// cache, capture or observer, A2S_RULES is optional and only the first address is queried
+    fn default() -> Self {
+        QueryOptions {
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            backoff: Box::new(Exponential::new(
+                Duration::from_millis(100),
+                Duration::from_secs(1),
+            )),
+            clock: Arc::new(SystemClock),
+            rate_limit: None,
+            cache: None,
+            capture: None,
+            observer: None,
+            optional: vec![OptionalQuery::Rules],
+            addresses: AddressStrategy::First,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Responses to all queries of a server, returned by [`A2SClient::query_all`]
+pub struct ServerSnapshot {
+    /// Response to A2S_INFO
+    pub info: SourceResponseInfo,
+    /// Response to A2S_PLAYER, None if the query is optional and timed out
+    pub players: Option<ResponsePlayer>,
+    /// Response to A2S_RULES, None if the query is optional and timed out
+    pub rules: Option<ResponseRule>,
+    /// Optional queries the server didn't answer, e.g. as the game disabled them. Kept apart from the responses so
+    /// servers without rules can be told from servers whose rules weren't asked for
+    pub unanswered: Vec<OptionalQuery>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Queries a server answers, returned by [`A2SClient::probe`]
+pub struct ServerCapabilities {
+    /// The server answers A2S_PLAYER
+    pub players: bool,
+    /// The server answers A2S_RULES
+    pub rules: bool,
+}
+
+impl ServerCapabilities {
+    /// Queries the server doesn't answer, to set as [`QueryOptions::optional`] so they don't fail every query
+    pub fn unanswered(&self) -> Vec<OptionalQuery> {
+        let mut unanswered = Vec::new();
+        if !self.players {
+            unanswered.push(OptionalQuery::Players);
+        }
+        if !self.rules {
+            unanswered.push(OptionalQuery::Rules);
+        }
+
+        unanswered
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Queries of [`A2SClient::query_all`] that can be left out of the [`ServerSnapshot`], see [`QueryOptions::optional`]
+pub enum OptionalQuery {
+    /// A2S_PLAYER, some servers hide their players
+    Players,
+    /// A2S_RULES, many games stopped answering it
+    Rules,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/**
+How [`A2SClient`] picks among the addresses a hostname resolves to, e.g. the A and AAAA records of a server reachable
+over IPv4 and IPv6, set as [`QueryOptions::addresses`].
+
+Except for [`AddressStrategy::First`] the addresses are reordered so IPv6 and IPv4 take turns, starting with the family
+of the first address the resolver returned. Queries go to the addresses until one succeeds, the client then sticks to
+the address that answered, see [`A2SClient::answered_by`].
+
+# Examples
+```no_run
+use a2s_parse::client::{A2SClient, AddressStrategy, QueryOptions};
+use std::time::Duration;
+
+let options = QueryOptions {
+    addresses: AddressStrategy::Race(Duration::from_millis(250)),
+    ..QueryOptions::default()
+};
+let client = A2SClient::with_options("play.example.com:27015", options)?;
+
+let info = client.info()?;
+println!("{} answered over {:?}", info.name, client.answered_by());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub enum AddressStrategy {
+    /// Only the first address that can be connected to is queried
+    First,
+    /// The addresses are queried one after the other, each one once the query to the one before failed
+    Sequential,
+    /// The addresses are queried at the same time, each one starting the delay after the one before unless one
+    /// answered by then. The first success is returned
+    Race(Duration),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/**
+Protocol overhead of queries, returned by [`A2SClient::last_stats`] and [`A2SClient::total_stats`].
+
+Datagrams and bytes include the `-1` and `-2` prefixes, every packet of a split response counts as one datagram.
+Queries answered from the cache don't send or receive anything.
+
+The round trip times tell the network latency and the time the server took to answer apart from the time spent on this
+side reassembling and parsing, they only cover the attempt that was answered and not the timed out ones before it.
+*/
+pub struct QueryStats {
+    /// Number of datagrams sent, including resent requests
+    pub datagrams_sent: u64,
+    /// Number of datagrams received
+    pub datagrams_received: u64,
+    /// Number of requests resent after a timeout
+    pub retries: u64,
+    /// Number of bytes sent
+    pub bytes_sent: u64,
+    /// Number of bytes received
+    pub bytes_received: u64,
+    /// Number of challenges the server answered with, each costs a round trip
+    pub challenges: u64,
+    /// Time from starting the query until it returned
+    pub elapsed: Duration,
+    /// Time from sending the requests answered with a challenge until the challenge arrived
+    pub challenge_rtt: Duration,
+    /// Time from sending the request answered with the response until the response was complete
+    pub response_rtt: Duration,
+    /// Number of packets the split responses arrived in, zero if none was split
+    pub fragments: u64,
+    /// Time from receiving the first packet of a split response until it was reassembled, included in the round trip
+    pub reassembly: Duration,
+    /// Time spent parsing the responses
+    pub parse: Duration,
+}
+
+impl QueryStats {
+    pub(crate) fn sent(&mut self, datagram: &[u8]) {
+        self.datagrams_sent += 1;
+        self.bytes_sent += datagram.len() as u64;
+    }
+
+    pub(crate) fn received(&mut self, datagram: &[u8]) {
+        self.datagrams_received += 1;
+        self.bytes_received += datagram.len() as u64;
+    }
+}
+
+impl AddAssign for QueryStats {
+    fn add_assign(&mut self, other: Self) {
+        self.datagrams_sent += other.datagrams_sent;
+        self.datagrams_received += other.datagrams_received;
+        self.retries += other.retries;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.challenges += other.challenges;
+        self.elapsed += other.elapsed;
+        self.challenge_rtt += other.challenge_rtt;
+        self.response_rtt += other.response_rtt;
+        self.fragments += other.fragments;
+        self.reassembly += other.reassembly;
+        self.parse += other.parse;
+    }
+}
+
+/**
+Blocking client querying a single server over UDP.
+
+Sends the requests, waits for the response and returns the parsed payload, the framing and header are checked and stripped.
+If the server answers with a challenge the request is resent with it, only the final response is returned.
+Requests that time out are resent as configured by [`QueryOptions`], which can also limit the request rate and cache responses.
+Responses split over several packets are reassembled, compressed ones are decompressed if the `compression` feature is enabled.
+The [`QueryStats`] of the last query and of all queries so far show the protocol overhead.
+
+# Examples
+```no_run
+use a2s_parse::client::A2SClient;
+
+let client = A2SClient::connect("127.0.0.1:27015")?;
+
+let info = client.info()?;
+let players = client.players()?;
+println!("{} on {} with {} players", info.name, info.map, players.player_data.len());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug)]
+pub struct A2SClient {
+    /// Connection to each address of the server, a single one unless [`QueryOptions::addresses`] says otherwise
+    connections: Vec<Arc<Connection>>,
+    /// Index of the connection that answered, queries go to it once it is known
+    answered: Mutex<Option<usize>>,
+    strategy: AddressStrategy,
+    policy: Arc<QueryPolicy>,
+}
+
+impl A2SClient {
+    /// Same as [`A2SClient::with_options`] with the default [`QueryOptions`]
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        A2SClient::with_options(address, QueryOptions::default())
+    }
+
+    /// Binds a local socket and connects it to the server, no data is sent until a query is made.
+    ///
+    /// Hostnames like `"play.example.com:27015"` are resolved and each address is tried in order until one can be
+    /// connected to, the socket is bound to the same IP version as that address. The error of the last address is
+    /// returned if none work. As UDP has no handshake an address that connects isn't necessarily reachable.
+    ///
+    /// Unless [`QueryOptions::addresses`] is [`AddressStrategy::First`] a socket is connected to every address, IPv6
+    /// and IPv4 addresses taking turns, and the queries go to all of them until one answers.
+    pub fn with_options(address: impl ToSocketAddrs, options: QueryOptions) -> io::Result<Self> {
+        let strategy = options.addresses;
+        let mut addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        if strategy != AddressStrategy::First {
+            addresses = alternate_families(addresses);
+        }
+
+        let policy = Arc::new(QueryPolicy::new(options));
+        let mut connections = Vec::new();
+        let mut last_error = None;
+        for address in addresses {
+            match connect_socket(address) {
+                Ok(socket) => connections.push(Arc::new(Connection {
+                    socket,
+                    policy: policy.clone(),
+                })),
+                Err(e) => last_error = Some(e),
+            }
+            if strategy == AddressStrategy::First && !connections.is_empty() {
+                break;
+            }
+        }
+
+        if connections.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+            }));
+        }
+
+        Ok(A2SClient {
+            connections,
+            answered: Mutex::new(None),
+            strategy,
+            policy,
+        })
+    }
+
+    /// Address of the server the client queries: the one that answered if the client has several, the first one
+    /// until any did
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.connection().socket.peer_addr()
+    }
+
+    /// Address of the server that answered the first successful query, None until a query succeeded
+    pub fn answered_by(&self) -> Option<SocketAddr> {
+        let answered = *self.answered.lock().unwrap_or_else(|e| e.into_inner());
+        self.connections[answered?].socket.peer_addr().ok()
+    }
+
+    /// Statistics of the last query, failed ones included. [`A2SClient::query_all`] counts as one query
+    pub fn last_stats(&self) -> QueryStats {
+        self.policy.last_stats()
+    }
+
+    /// Statistics of all queries made by the client
+    pub fn total_stats(&self) -> QueryStats {
+        self.policy.total_stats()
+    }
+
+    /// Queries A2S_INFO
+    pub fn info(&self) -> Result<SourceResponseInfo, ClientError> {
+        self.measure("info", |connection, stats| {
+            connection.query(build_info_request, stats)
+        })
+    }
+
+    /// Queries A2S_PLAYER
+    pub fn players(&self) -> Result<ResponsePlayer, ClientError> {
+        self.measure("players", |connection, stats| {
+            connection.query(
+                |challenge| build_player_request(challenge.unwrap_or(-1)),
+                stats,
+            )
+        })
+    }
+
+    /// Queries A2S_RULES
+    pub fn rules(&self) -> Result<ResponseRule, ClientError> {
+        self.measure("rules", |connection, stats| {
+            connection.query(
+                |challenge| build_rules_request(challenge.unwrap_or(-1)),
+                stats,
+            )
+        })
+    }
+
+    /**
+    Measures the round trip time to the server with A2A_PING.
+
+    Most servers ignore the deprecated ping, if it times out an A2S_INFO request is timed instead, a challenge counts as an answer.
+    Neither request is retried so the measurement doesn't include any backoff.
+    */
+    pub fn query_ping(&self) -> Result<Duration, ClientError> {
+        self.measure("ping", |connection, stats| {
+            let ping = ResponseFilter::exact(PingResponse::HEADER);
+            match connection.round_trip(&PingRequest.to_framed_bytes(), ping, stats) {
+                Ok((elapsed, payload)) => {
+                    parse_payload::<PingResponse>(&payload)?;
+                    Ok(elapsed)
+                }
+                Err(ClientError::Io(e)) if is_timeout(&e) => {
+                    let info = ResponseFilter::answer(SourceResponseInfo::HEADER);
+                    connection
+                        .round_trip(&build_info_request(None), info, stats)
+                        .map(|(elapsed, _)| elapsed)
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /**
+    Queries A2S_INFO, A2S_PLAYER and A2S_RULES one after the other.
+
+    The challenge the server hands out for the first query is reused for the others, saving a round trip per query.
+    Queries listed in [`QueryOptions::optional`] that time out are left out of the snapshot and noted in
+    [`ServerSnapshot::unanswered`], by default A2S_RULES as some games never answer it. Fails if A2S_INFO or a required
+    query fails, or if an optional one fails for any other reason than a timeout.
+
+    # Examples
+    ```no_run
+    use a2s_parse::client::{A2SClient, OptionalQuery, QueryOptions};
+
+    let options = QueryOptions {
+        optional: vec![OptionalQuery::Players, OptionalQuery::Rules],
+        ..QueryOptions::default()
+    };
+    let snapshot = A2SClient::with_options("127.0.0.1:27015", options)?.query_all()?;
+
+    if snapshot.unanswered.contains(&OptionalQuery::Rules) {
+        println!("{} doesn't answer rules queries", snapshot.info.name);
+    }
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    pub fn query_all(&self) -> Result<ServerSnapshot, ClientError> {
+        self.measure("all", |connection, stats| {
+            connection.snapshot(&connection.policy.optional, stats)
+        })
+    }
+
+    /**
+    Finds out which queries the server answers, as some games disable A2S_PLAYER or A2S_RULES entirely.
+
+    Runs [`A2SClient::query_all`] with both queries optional, a query that still times out after all retries counts as
+    unanswered. Fails if A2S_INFO isn't answered as the server is most likely down, or if a query fails for any other
+    reason than a timeout.
+
+    # Examples
+    ```no_run
+    use a2s_parse::client::{A2SClient, QueryOptions};
+
+    let capabilities = A2SClient::connect("127.0.0.1:27015")?.probe()?;
+
+    // Stop waiting for the queries the server never answers
+    let options = QueryOptions {
+        optional: capabilities.unanswered(),
+        ..QueryOptions::default()
+    };
+    let snapshot = A2SClient::with_options("127.0.0.1:27015", options)?.query_all()?;
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    pub fn probe(&self) -> Result<ServerCapabilities, ClientError> {
+        let snapshot = self.measure("probe", |connection, stats| {
+            let optional = [OptionalQuery::Players, OptionalQuery::Rules];
+            connection.snapshot(&optional, stats)
+        })?;
+
+        Ok(ServerCapabilities {
+            players: !snapshot.unanswered.contains(&OptionalQuery::Players),
+            rules: !snapshot.unanswered.contains(&OptionalQuery::Rules),
+        })
+    }
+
+    /// Runs the query within its [`QuerySpan`] collecting its statistics, which are stored as the last ones and added
+    /// to the total
+    fn measure<R: Send + 'static>(
+        &self,
+        name: &'static str,
+        query: Query<R>,
+    ) -> Result<R, ClientError> {
+        let span = QuerySpan::new(name, || self.connection().addresses());
+        let start = self.policy.now();
+        let mut stats = QueryStats::default();
+        let result = span.in_scope(|| self.run(query, &mut stats));
+        span.finish(&stats, &result);
+        self.policy
+            .record(start, stats, &result, || self.connection().addresses());
+        result
+    }
+
+    /// Runs the query on the connection that answered before, or else on the connections as the [`AddressStrategy`]
+    /// says until one answers
+    fn run<R: Send + 'static>(
+        &self,
+        query: Query<R>,
+        stats: &mut QueryStats,
+    ) -> Result<R, ClientError> {
+        let answered = *self.answered.lock().unwrap_or_else(|e| e.into_inner());
+        match (answered, self.strategy) {
+            (Some(index), _) => query(&self.connections[index], stats),
+            (None, AddressStrategy::Race(delay)) if self.connections.len() > 1 => {
+                self.race(query, delay, stats)
+            }
+            (None, _) => self.sequence(query, stats),
+        }
+    }
+
+    /// Runs the query on one connection after the other until one succeeds, returns the error of the last one if none do
+    fn sequence<R>(&self, query: Query<R>, stats: &mut QueryStats) -> Result<R, ClientError> {
+        let mut last_error = None;
+        for (index, connection) in self.connections.iter().enumerate() {
+            match query(connection, stats) {
+                Ok(response) => {
+                    self.select(index);
+                    return Ok(response);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotConnected).into()))
+    }
+
+    /// Runs the query on every connection in its own thread, each starting `delay` after the one before unless one
+    /// succeeded by then. The first success is returned, queries still running after it are left to finish on their own
+    /// and aren't counted in the statistics
+    fn race<R: Send + 'static>(
+        &self,
+        query: Query<R>,
+        delay: Duration,
+        stats: &mut QueryStats,
+    ) -> Result<R, ClientError> {
+        let done = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        for (index, connection) in self.connections.iter().enumerate() {
+            let connection = connection.clone();
+            let done = done.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                connection.policy.clock.sleep(delay * index as u32);
+                if done.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let mut stats = QueryStats::default();
+                let result = query(&connection, &mut stats);
+                let _ = sender.send((index, stats, result));
+            });
+        }
+        drop(sender);
+
+        let mut last_error = None;
+        for (index, attempt, result) in receiver {
+            *stats += attempt;
+            match result {
+                Ok(response) => {
+                    done.store(true, Ordering::Relaxed);
+                    self.select(index);
+                    return Ok(response);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotConnected).into()))
+    }
+
+    /// Sends the following queries to the connection that answered
+    fn select(&self, index: usize) {
+        *self.answered.lock().unwrap_or_else(|e| e.into_inner()) = Some(index);
+    }
+
+    /// Connection that answered, or the first one until any did
+    fn connection(&self) -> &Connection {
+        let answered = *self.answered.lock().unwrap_or_else(|e| e.into_inner());
+        &self.connections[answered.unwrap_or(0)]
+    }
+}
+
+/// Query run by [`A2SClient::measure`] on one of the connections of the client
+type Query<R> = fn(&Connection, &mut QueryStats) -> Result<R, ClientError>;
+
+/// Socket connected to one address of the server, the [`A2SClient`] has one for each address it queries
+#[derive(Debug)]
+struct Connection {
+    socket: UdpSocket,
+    policy: Arc<QueryPolicy>,
+}
+
+impl Connection {
+    /// Queries the snapshot for [`A2SClient::query_all`] leaving out the optional queries that time out
+    fn snapshot(
+        &self,
+        optional: &[OptionalQuery],
+        stats: &mut QueryStats,
+    ) -> Result<ServerSnapshot, ClientError> {
+        let mut challenge = None;
+        let mut unanswered = Vec::new();
+
+        let info = self.query_with_challenge(build_info_request, &mut challenge, stats)?;
+        let players = self.query_with_challenge(
+            |challenge| build_player_request(challenge.unwrap_or(-1)),
+            &mut challenge,
+            stats,
+        );
+        let players = skip_unanswered(OptionalQuery::Players, players, optional, &mut unanswered)?;
+        let rules = self.query_with_challenge(
+            |challenge| build_rules_request(challenge.unwrap_or(-1)),
+            &mut challenge,
+            stats,
+        );
+        let rules = skip_unanswered(OptionalQuery::Rules, rules, optional, &mut unanswered)?;
+
+        Ok(ServerSnapshot {
+            info,
+            players,
+            rules,
+            unanswered,
+        })
+    }
+
+    /// Sends the request built without a challenge, resending it with the challenge the server answers with if any,
+    /// and parses the final response as `T`
+    fn query<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        build_request: impl Fn(Option<i32>) -> Vec<u8>,
+        stats: &mut QueryStats,
+    ) -> Result<T, ClientError> {
+        self.query_with_challenge(build_request, &mut None, stats)
+    }
+
+    /// Same as [`Connection::query`] starting with a known challenge, the challenge is updated if the server hands out a new one.
+    /// A fresh cached response is returned without querying the server
+    fn query_with_challenge<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        build_request: impl Fn(Option<i32>) -> Vec<u8>,
+        challenge: &mut Option<i32>,
+        stats: &mut QueryStats,
+    ) -> Result<T, ClientError> {
+        let server = Some(self.socket.peer_addr()?);
+        if let Some(response) = self.policy.cached(server) {
+            return Ok(response);
+        }
+
+        for _ in 0..=MAX_CHALLENGES {
+            let filter = ResponseFilter::answer(T::HEADER);
+            let (rtt, payload) = self.exchange(&build_request(*challenge), &filter, stats)?;
+
+            match self.policy.answer::<T>(&payload, rtt, stats)? {
+                Answer::Challenge(value) => {
+                    *challenge = Some(value);
+                }
+                Answer::Response(response) => {
+                    self.policy.store(server, &response);
+                    return Ok(response);
+                }
+            }
+        }
+
+        Err(ClientError::UnexpectedHeader(ChallengeResponse::HEADER))
+    }
+
+    /// Sends the request and receives the response passing the filter, resending it after timeouts until the retries run out.
+    /// Returns the time from sending the answered request until the response along with the response
+    fn exchange(
+        &self,
+        request: &[u8],
+        filter: &ResponseFilter,
+        stats: &mut QueryStats,
+    ) -> Result<(Duration, Vec<u8>), ClientError> {
+        let mut retries = self.policy.retries;
+
+        loop {
+            self.throttle();
+            let start = self.policy.now();
+            self.socket.send(request)?;
+            stats.sent(request);
+            self.policy.sent(|| self.addresses(), request);
+
+            let result = self.receive(filter.clone(), stats);
+            match self
+                .policy
+                .attempted(result, &mut retries, stats, || self.addresses())
+            {
+                Attempt::Retry(delay) => self.policy.clock.sleep(delay),
+                Attempt::Done(result) => {
+                    let rtt = self.policy.now().saturating_duration_since(start);
+                    return result.map(|payload| (rtt, payload));
+                }
+            }
+        }
+    }
+
+    /// Local address and address of the server
+    fn addresses(&self) -> Option<(SocketAddr, SocketAddr)> {
+        Some((
+            self.socket.local_addr().ok()?,
+            self.socket.peer_addr().ok()?,
+        ))
+    }
+
+    /// Waits until the rate limit allows sending a request
+    fn throttle(&self) {
+        if let Some(limiter) = &self.policy.rate_limit {
+            limiter.acquire();
+        }
+    }
+
+    /// Sends the request once and returns the time until the response arrived along with the response
+    fn round_trip(
+        &self,
+        request: &[u8],
+        filter: ResponseFilter,
+        stats: &mut QueryStats,
+    ) -> Result<(Duration, Vec<u8>), ClientError> {
+        self.throttle();
+        let start = self.policy.now();
+        self.socket.send(request)?;
+        stats.sent(request);
+        self.policy.sent(|| self.addresses(), request);
+        let payload = self.receive(filter, stats)?;
+
+        Ok((self.policy.now() - start, payload))
+    }
+
+    /// Receives datagrams until one passes the filter and returns its payload following the `-1` prefix, split responses
+    /// are reassembled first. Times out once the timeout passed since the call, no matter how many datagrams were dropped
+    fn receive(
+        &self,
+        mut filter: ResponseFilter,
+        stats: &mut QueryStats,
+    ) -> Result<Vec<u8>, ClientError> {
+        let deadline = Instant::now() + self.policy.timeout;
+        let mut buffer = [0; BUFFER_SIZE];
+
+        loop {
+            match deadline.saturating_duration_since(Instant::now()) {
+                remaining if remaining == Duration::from_secs(0) => {
+                    return Err(io::Error::from(io::ErrorKind::TimedOut).into())
+                }
+                remaining => self.socket.set_read_timeout(Some(remaining))?,
+            }
+            let received = self.socket.recv(&mut buffer)?;
+            stats.received(&buffer[..received]);
+            self.policy
+                .received(|| self.addresses(), &buffer[..received]);
+            if let Some(payload) = self
+                .policy
+                .filter(&mut filter, &buffer[..received], stats)?
+            {
+                return Ok(payload);
+            }
+        }
+    }
+}
+
+/**
+Handles the [`QueryOptions`] for [`A2SClient`] and [`AsyncA2SClient`](crate::async_client::AsyncA2SClient), which only
+differ in how they send, receive and wait: when to resend a request after a timeout, the rate limit, the cache, the
+capture, the observer and the [`QueryStats`].
+
+The local and server address are passed as a function, they are only looked up if the capture or observer needs them.
+
+The backoff is only locked while picking a delay so it isn't held across the waits of async queries.
+*/
+#[derive(Debug)]
+pub(crate) struct QueryPolicy {
+    pub(crate) timeout: Duration,
+    pub(crate) retries: u32,
+    pub(crate) optional: Vec<OptionalQuery>,
+    backoff: Mutex<Box<dyn Backoff>>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) rate_limit: Option<Arc<RateLimiter>>,
+    cache: Option<Arc<ResponseCache>>,
+    capture: Option<Arc<dyn Capture>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    last_stats: Mutex<QueryStats>,
+    total_stats: Mutex<QueryStats>,
+}
+
+/// What follows an attempt to exchange a request with the server, returned by [`QueryPolicy::attempted`]
+pub(crate) enum Attempt {
+    /// The request timed out, resend it after the delay
+    Retry(Duration),
+    /// The exchange is over, return the result
+    Done(Result<Vec<u8>, ClientError>),
+}
+
+impl QueryPolicy {
+    pub(crate) fn new(options: QueryOptions) -> Self {
+        QueryPolicy {
+            timeout: options.timeout,
+            retries: options.retries,
+            optional: options.optional,
+            backoff: Mutex::new(options.backoff),
+            clock: options.clock,
+            rate_limit: options.rate_limit,
+            cache: options.cache,
+            capture: options.capture,
+            observer: options.observer,
+            last_stats: Mutex::new(QueryStats::default()),
+            total_stats: Mutex::new(QueryStats::default()),
+        }
+    }
+
+    /// Current time of the clock, where the measurement of a query starts
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Stores the statistics of the query started at `start` as the last ones and adds them to the total,
+    /// the observer is notified if the query failed to parse
+    pub(crate) fn record<R>(
+        &self,
+        start: Instant,
+        mut stats: QueryStats,
+        result: &Result<R, ClientError>,
+        addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+    ) {
+        stats.elapsed = self.clock.now().saturating_duration_since(start);
+        if let (Some(observer), Err(ClientError::Parse(e))) = (&self.observer, result) {
+            observer.on_parse_error(server(addresses), e);
+        }
+
+        *self.last_stats.lock().unwrap_or_else(|e| e.into_inner()) = stats;
+        *self.total_stats.lock().unwrap_or_else(|e| e.into_inner()) += stats;
+    }
+
+    pub(crate) fn last_stats(&self) -> QueryStats {
+        *self.last_stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn total_stats(&self) -> QueryStats {
+        *self.total_stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fresh cached response of the server, nothing is cached for unknown servers
+    pub(crate) fn cached<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        server: Option<SocketAddr>,
+    ) -> Option<T> {
+        self.cache.as_ref()?.get(server?)
+    }
+
+    /// Caches the response of the server
+    pub(crate) fn store<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        server: Option<SocketAddr>,
+        response: &T,
+    ) {
+        if let (Some(cache), Some(server)) = (&self.cache, server) {
+            cache.insert(server, response.clone());
+        }
+    }
+
+    /// Hands a datagram sent to the server to the capture and the observer
+    pub(crate) fn sent(
+        &self,
+        addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+        datagram: &[u8],
+    ) {
+        if self.capture.is_none() && self.observer.is_none() {
+            return;
+        }
+
+        let addresses = addresses();
+        if let (Some(capture), Some((local, server))) = (&self.capture, addresses) {
+            capture.sent(local, server, datagram);
+        }
+        if let Some(observer) = &self.observer {
+            observer.on_send(addresses.map(|(_, server)| server), datagram);
+        }
+    }
+
+    /// Hands a datagram received from the server to the capture and the observer
+    pub(crate) fn received(
+        &self,
+        addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+        datagram: &[u8],
+    ) {
+        if self.capture.is_none() && self.observer.is_none() {
+            return;
+        }
+
+        let addresses = addresses();
+        if let (Some(capture), Some((local, server))) = (&self.capture, addresses) {
+            capture.received(local, server, datagram);
+        }
+        if let Some(observer) = &self.observer {
+            observer.on_receive(addresses.map(|(_, server)| server), datagram);
+        }
+    }
+
+    /// Hands a datagram received from the server to the filter, timing the reassembly of split responses
+    pub(crate) fn filter(
+        &self,
+        filter: &mut ResponseFilter,
+        datagram: &[u8],
+        stats: &mut QueryStats,
+    ) -> Result<Option<Vec<u8>>, ClientError> {
+        let arrived = self.clock.now();
+        let fragments = filter.fragments;
+        let payload = filter.receive(datagram)?;
+        if filter.fragments == fragments {
+            return Ok(payload);
+        }
+
+        let first_fragment = *filter.first_fragment.get_or_insert(arrived);
+        if payload.is_some() {
+            stats.fragments += filter.fragments;
+            stats.reassembly += self.clock.now().saturating_duration_since(first_fragment);
+        }
+        Ok(payload)
+    }
+
+    /// Parses the payload received `rtt` after sending the request, timing the parsing of responses
+    pub(crate) fn answer<T: A2sMessage>(
+        &self,
+        payload: &[u8],
+        rtt: Duration,
+        stats: &mut QueryStats,
+    ) -> Result<Answer<T>, ClientError> {
+        let start = self.clock.now();
+        let answer = parse_answer(payload);
+        match answer {
+            Ok(Answer::Challenge(_)) => {
+                stats.challenges += 1;
+                stats.challenge_rtt += rtt;
+            }
+            _ => {
+                stats.response_rtt += rtt;
+                stats.parse += self.clock.now().saturating_duration_since(start);
+            }
+        }
+        answer
+    }
+
+    /// Takes a token of the rate limit, returns zero if the request can be sent or else the time to wait before
+    /// trying again
+    pub(crate) fn try_throttle(&self) -> Duration {
+        match &self.rate_limit {
+            Some(limiter) => limiter.take(),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// Decides what follows receiving the result of an attempt: timeouts are retried after the backoff delay
+    /// while `retries` are left, anything else ends the exchange and resets the backoff
+    pub(crate) fn attempted(
+        &self,
+        result: Result<Vec<u8>, ClientError>,
+        retries: &mut u32,
+        stats: &mut QueryStats,
+        addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+    ) -> Attempt {
+        let mut backoff = self.backoff.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Err(ClientError::Io(e)) if is_timeout(&e) && *retries > 0 => {
+                *retries -= 1;
+                stats.retries += 1;
+                let delay = backoff.next_delay();
+                if let Some(observer) = &self.observer {
+                    observer.on_retry(server(addresses), delay);
+                }
+                Attempt::Retry(delay)
+            }
+            result => {
+                backoff.reset();
+                Attempt::Done(result)
+            }
+        }
+    }
+}
+
+/**
+Span around a query of [`A2SClient`] or [`AsyncA2SClient`](crate::async_client::AsyncA2SClient) with the `tracing`
+feature, doing nothing without it.
+
+The span `a2s_query` is opened with the query and the server, once the query returned the attempts it took (every
+request sent, resent ones and those answering a challenge included), the challenges and the outcome are recorded.
+*/
+#[derive(Debug)]
+pub(crate) struct QuerySpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+impl QuerySpan {
+    pub(crate) fn new(
+        query: &'static str,
+        addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+    ) -> Self {
+        let server = server(addresses).map(tracing::field::display);
+        let span = tracing::info_span!(
+            "a2s_query",
+            query,
+            server,
+            attempts = tracing::field::Empty,
+            challenges = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        );
+
+        QuerySpan { span }
+    }
+
+    /// Runs the blocking query within the span
+    pub(crate) fn in_scope<R>(&self, query: impl FnOnce() -> R) -> R {
+        self.span.in_scope(query)
+    }
+
+    /// Enters the span whenever the async query is polled
+    pub(crate) fn instrument<F: std::future::Future>(
+        &self,
+        query: F,
+    ) -> impl std::future::Future<Output = F::Output> {
+        tracing::Instrument::instrument(query, self.span.clone())
+    }
+
+    /// Records how the query went
+    pub(crate) fn finish<R>(&self, stats: &QueryStats, result: &Result<R, ClientError>) {
+        self.span.record("attempts", stats.datagrams_sent);
+        self.span.record("challenges", stats.challenges);
+        match result {
+            Ok(_) => self.span.record("outcome", "ok"),
+            Err(e) => self.span.record("outcome", tracing::field::display(e)),
+        };
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl QuerySpan {
+    pub(crate) fn new(
+        _query: &'static str,
+        _addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>,
+    ) -> Self {
+        QuerySpan {}
+    }
+
+    pub(crate) fn in_scope<R>(&self, query: impl FnOnce() -> R) -> R {
+        query()
+    }
+
+    pub(crate) fn instrument<F: std::future::Future>(
+        &self,
+        query: F,
+    ) -> impl std::future::Future<Output = F::Output> {
+        query
+    }
+
+    pub(crate) fn finish<R>(&self, _stats: &QueryStats, _result: &Result<R, ClientError>) {}
+}
+
+/**
+Picks the response to a request out of the datagrams received from the server, for every client of the crate.
+
+Split responses are reassembled, in the Source or the Gold Source format as detected by [`parse_multi_packet_auto`].
+Late answers to earlier requests, e.g. the second response to a request that was resent after a timeout, are dropped
+so they can't be taken for the response: payloads with any other header than the expected one, and once a split packet
+arrived, packets of other split responses.
+*/
+#[derive(Clone, Debug)]
+pub(crate) struct ResponseFilter {
+    header: u8,
+    challenge: bool,
+    assembler: DatagramAssembler,
+    /// Packets of the split response received so far
+    fragments: u64,
+    /// When the first packet of the split response arrived, set by [`QueryPolicy::filter`]
+    first_fragment: Option<Instant>,
+}
+
+impl ResponseFilter {
+    /// Expects the response with the header or a challenge to resend the request with
+    pub(crate) fn answer(header: u8) -> Self {
+        ResponseFilter {
+            header,
+            challenge: true,
+            assembler: DatagramAssembler::new(),
+            fragments: 0,
+            first_fragment: None,
+        }
+    }
+
+    /// Expects the response with the header, for requests that are never challenged
+    pub(crate) fn exact(header: u8) -> Self {
+        ResponseFilter {
+            challenge: false,
+            ..ResponseFilter::answer(header)
+        }
+    }
+
+    /// Handles a datagram received from the server, returns the payload following the `-1` prefix once the expected
+    /// response is complete. Errors are returned for datagrams that aren't A2S responses at all
+    pub(crate) fn receive(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>, ClientError> {
+        let payload = match parse_framing(datagram) {
+            Ok((Framing::Single, payload)) => payload.to_vec(),
+            Ok((Framing::Split, _)) => match self.receive_split(datagram)? {
+                Some(payload) => payload,
+                None => return Ok(None),
+            },
+            _ => return Err(ClientError::UnexpectedFraming),
+        };
+
+        match payload.first() {
+            Some(&header) if header != self.header && !self.is_challenge(header) => Ok(None),
+            _ => Ok(Some(payload)),
+        }
+    }
+
+    /// Adds the split packet to the response being assembled, returns its payload once it is complete
+    fn receive_split(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>, ClientError> {
+        let id = parse_multi_packet_auto(datagram)?.id();
+        if matches!(self.assembler.pending(), Some(pending) if pending.id() != id) {
+            return Ok(None);
+        }
+
+        self.fragments += 1;
+        match self.assembler.feed(datagram) {
+            FeedResult::Complete(response) => match parse_framing(&response) {
+                Ok((Framing::Single, payload)) => Ok(Some(payload.to_vec())),
+                _ => Err(ClientError::UnexpectedFraming),
+            },
+            FeedResult::NeedMore => Ok(None),
+            FeedResult::Error(e) => Err(e.into()),
+        }
+    }
+
+    fn is_challenge(&self, header: u8) -> bool {
+        self.challenge && header == ChallengeResponse::HEADER
+    }
+}
+
+/// Payload received for a query expecting a `T`
+pub(crate) enum Answer<T> {
+    /// The server wants the request resent with the challenge
+    Challenge(i32),
+    /// The final response
+    Response(T),
+}
+
+// # Batch queries
+/**
+Runs [`A2SClient::query_all`] for every server, spread over `workers` threads. The results are in the same order as the servers.
+
+The workers take the servers in the order of the schedule. Each server gets its own client with the [`QueryOptions`]
+returned by `options` for it, hand out clones of the same rate limiter, cache or capture to share them between the
+workers. Servers whose worker stopped without a result, e.g. as `options` panicked, fail with an
+[`io::ErrorKind::Other`] error.
+
+# Examples
+```no_run
+use a2s_parse::client::{query_many, QueryOptions};
+use a2s_parse::rate_limit::RateLimiter;
+use a2s_parse::schedule::Interleave;
+use std::sync::Arc;
+use std::time::Duration;
+
+let servers = ["127.0.0.1:27015".parse()?, "127.0.0.1:27016".parse()?];
+// At most 20 requests a second over all workers
+let limiter = Arc::new(RateLimiter::new(20, 20));
+let options = move |_server| QueryOptions {
+    timeout: Duration::from_secs(1),
+    rate_limit: Some(limiter.clone()),
+    ..QueryOptions::default()
+};
+
+for result in query_many(&servers, 8, &Interleave::Network, options) {
+    match result {
+        Ok(snapshot) => println!("{}: {} players", snapshot.info.name, snapshot.info.players),
+        Err(e) => println!("{}", e),
+    }
+}
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub fn query_many(
+    servers: &[SocketAddr],
+    workers: usize,
+    schedule: &dyn Schedule,
+    options: impl Fn(SocketAddr) -> QueryOptions + Send + Sync + 'static,
+) -> Vec<Result<ServerSnapshot, ClientError>> {
+    let options = Arc::new(options);
+    let order = Arc::new(schedule.order(servers));
+    let servers = Arc::new(servers.to_vec());
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..workers.clamp(1, servers.len().max(1)) {
+        let order = Arc::clone(&order);
+        let servers = Arc::clone(&servers);
+        let next = Arc::clone(&next);
+        let options = Arc::clone(&options);
+        let sender = sender.clone();
+
+        std::thread::spawn(move || {
+            while let Some(&index) = order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                let server = servers[index];
+
+                let result = A2SClient::with_options(server, options(server))
+                    .map_err(ClientError::from)
+                    .and_then(|client| client.query_all());
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    let mut results: Vec<Option<Result<ServerSnapshot, ClientError>>> =
+        (0..servers.len()).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+
+    results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| {
+                Err(ClientError::Io(io::Error::other(
+                    "query worker stopped without a result",
+                )))
+            })
+        })
+        .collect()
+}
+
+// # Private helper functions
+/// Binds a socket to any local address of the same IP version as the server and connects it
+fn connect_socket(server: SocketAddr) -> io::Result<UdpSocket> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+
+    Ok(socket)
+}
+
+/// Reorders the addresses so IPv6 and IPv4 take turns, starting with the family of the first one
+fn alternate_families(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (first, second): (Vec<SocketAddr>, Vec<SocketAddr>) = match addresses.first() {
+        Some(first) => {
+            let ipv6 = first.is_ipv6();
+            addresses
+                .into_iter()
+                .partition(|address| address.is_ipv6() == ipv6)
+        }
+        None => return addresses,
+    };
+
+    let mut alternated = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return alternated,
+            (a, b) => alternated.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Address of the server out of the local and server address
+fn server(addresses: impl FnOnce() -> Option<(SocketAddr, SocketAddr)>) -> Option<SocketAddr> {
+    addresses().map(|(_, server)| server)
+}
+
+/// Read timeouts are reported as either kind depending on the platform
+pub(crate) fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// The host answered a datagram with ICMP port unreachable. Only connected sockets report it, on the next send or receive.
+/// Windows reports it as a reset connection
+pub(crate) fn is_port_closed(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Leaves out the response of an optional query that timed out and notes it as unanswered
+pub(crate) fn skip_unanswered<T>(
+    query: OptionalQuery,
+    result: Result<T, ClientError>,
+    optional: &[OptionalQuery],
+    unanswered: &mut Vec<OptionalQuery>,
+) -> Result<Option<T>, ClientError> {
+    match result {
+        Ok(response) => Ok(Some(response)),
+        Err(ClientError::Io(e)) if is_timeout(&e) && optional.contains(&query) => {
+            unanswered.push(query);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses the payload as `T` or as a challenge the request has to be resent with
+pub(crate) fn parse_answer<T: A2sMessage>(payload: &[u8]) -> Result<Answer<T>, ClientError> {
+    match payload.first() {
+        Some(&ChallengeResponse::HEADER) if T::HEADER != ChallengeResponse::HEADER => {
+            let response: ChallengeResponse = parse_payload(payload)?;
+            Ok(Answer::Challenge(response.challenge))
+        }
+        _ => parse_payload(payload).map(Answer::Response),
+    }
+}
+
+/// Checks the header byte of the payload and parses the rest as `T`
+fn parse_payload<T: A2sMessage>(payload: &[u8]) -> Result<T, ClientError> {
+    match payload.split_first() {
+        Some((&header, payload)) if header == T::HEADER => {
+            T::parse(payload).map_err(|e| ClientError::Parse(e.offset_by(1)))
+        }
+        Some((&header, _)) => Err(ClientError::UnexpectedHeader(header)),
+        None => Err(ClientError::Parse(A2SError::TruncatedField {
+            offset: 0,
+            field: Some("header".to_string()),
+        })),
+    }
+}
+
+// # Tests
+/// Answers each request with the next response, returns the requests that were received
+#[cfg(test)]
+pub(crate) fn serve(
+    responses: Vec<Vec<u8>>,
+) -> (SocketAddr, std::thread::JoinHandle<Vec<Vec<u8>>>) {
+    serve_datagrams(
+        responses
+            .into_iter()
+            .map(|response| vec![response])
+            .collect(),
+    )
+}
+
+/// Answers each request with the next list of datagrams, returns the requests that were received
+#[cfg(test)]
+fn serve_datagrams(
+    responses: Vec<Vec<Vec<u8>>>,
+) -> (SocketAddr, std::thread::JoinHandle<Vec<Vec<u8>>>) {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for datagrams in responses {
+            let mut buffer = [0; BUFFER_SIZE];
+            let (received, peer) = server.recv_from(&mut buffer).unwrap();
+            requests.push(buffer[..received].to_vec());
+            for datagram in datagrams {
+                server.send_to(&datagram, peer).unwrap();
+            }
+        }
+        requests
+    });
+
+    (address, handle)
+}
+
+/// Rules response large enough to be split
+#[cfg(test)]
+fn large_rules() -> ResponseRule {
+    use crate::rules::RuleData;
+
+    let rule_data: Vec<RuleData> = (0..200)
+        .map(|i| RuleData {
+            name: format!("rule_{}", i),
+            value: "x".repeat(20),
+        })
+        .collect();
+
+    ResponseRule {
+        rules: rule_data.len() as i16,
+        rule_data,
+        remaining_data: String::new(),
+    }
+}
+
+#[test]
+fn query_players() {
+    let response = vec![
+        0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x01, 0x00, 0x61, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x80, 0x3F,
+    ];
+    let (address, server) = serve(vec![response]);
+
+    let client = A2SClient::connect(address).unwrap();
+    let players = client.players().unwrap();
+
+    assert_eq!("a", players.player_data[0].name);
+    assert_eq!(build_player_request(-1), server.join().unwrap()[0]);
+}
+
+#[test]
+fn receive_large_datagram() {
+    // Some servers send responses above the usual packet size without splitting them
+    let response = large_rules().to_framed_bytes();
+    assert!(response.len() > 4096);
+    let (address, _) = serve(vec![response]);
+
+    let client = A2SClient::connect(address).unwrap();
+
+    assert_eq!(large_rules(), client.rules().unwrap());
+}
+
+#[test]
+fn negotiate_challenge() {
+    use crate::challenge::build_challenge_response;
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().map("de_dust2").build();
+    let (address, server) = serve(vec![
+        build_challenge_response(0x1234),
+        info.to_framed_bytes(),
+    ]);
+
+    let client = A2SClient::connect(address).unwrap();
+    assert_eq!(info, client.info().unwrap());
+
+    let requests = server.join().unwrap();
+    assert_eq!(build_info_request(None), requests[0]);
+    assert_eq!(build_info_request(Some(0x1234)), requests[1]);
+}
+
+#[test]
+fn retry_after_timeout() {
+    use crate::backoff::Fixed;
+    use crate::clock::ManualClock;
+
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut buffer = [0; BUFFER_SIZE];
+        // Drop the first request
+        server.recv_from(&mut buffer).unwrap();
+        let (_, peer) = server.recv_from(&mut buffer).unwrap();
+        server
+            .send_to(&[0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00], peer)
+            .unwrap();
+        server
+    });
+
+    let clock = ManualClock::new();
+    let start = clock.now();
+    let options = QueryOptions {
+        timeout: Duration::from_millis(100),
+        retries: 1,
+        backoff: Box::new(Fixed(Duration::from_secs(1))),
+        clock: Arc::new(clock.clone()),
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+
+    assert!(client.players().unwrap().player_data.is_empty());
+    assert_eq!(Duration::from_secs(1), clock.now() - start);
+    // Keep the socket open so the next request times out instead of being refused, see port_closed
+    let _server = handle.join().unwrap();
+
+    assert_eq!(1, client.last_stats().retries);
+
+    let error = client.players().unwrap_err();
+    assert!(matches!(error, ClientError::Io(e) if is_timeout(&e)));
+}
+
+#[test]
+fn drop_late_responses() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let players = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let rules = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00];
+    let ping = PingResponse {
+        payload: String::new(),
+    }
+    .to_framed_bytes();
+    let (address, server) = serve_datagrams(vec![
+        // Answered twice, e.g. as the request was resent
+        vec![players.clone(), players],
+        vec![rules],
+        // The ping is answered late, while the info request is timed
+        vec![],
+        vec![ping, ServerInfoBuilder::new().build().to_framed_bytes()],
+    ]);
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(200),
+        retries: 0,
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+
+    assert!(client.players().is_ok());
+    assert!(client.rules().is_ok());
+    assert_eq!(2, client.last_stats().datagrams_received);
+    client.query_ping().unwrap();
+    assert_eq!(2, client.last_stats().datagrams_received);
+    server.join().unwrap();
+}
+
+#[test]
+fn port_closed() {
+    let closed = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = closed.local_addr().unwrap();
+    drop(closed);
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(500),
+        retries: 0,
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+    let error = client.info().unwrap_err();
+
+    assert!(matches!(error, ClientError::PortClosed));
+    assert_eq!(
+        io::ErrorKind::ConnectionRefused,
+        io::Error::from(error).kind()
+    );
+}
+
+#[test]
+fn reassemble_split_response() {
+    use crate::packet::split_source_payload;
+
+    let rules = large_rules();
+    let response = rules.to_framed_bytes();
+    let mut datagrams: Vec<Vec<u8>> = split_source_payload(7, &response, 1248)
+        .unwrap()
+        .iter()
+        .map(|packet| packet.to_bytes())
+        .collect();
+    let fragments = datagrams.len() as u64;
+    datagrams.reverse();
+    // A late packet of an earlier response is dropped
+    datagrams.insert(
+        1,
+        split_source_payload(6, &[0xFF; 100], 1248).unwrap()[0].to_bytes(),
+    );
+
+    let (address, _) = serve_datagrams(vec![datagrams]);
+    let client = A2SClient::connect(address).unwrap();
+
+    assert_eq!(rules, client.rules().unwrap());
+    let stats = client.last_stats();
+    assert_eq!(fragments, stats.fragments);
+    assert!(stats.reassembly <= stats.response_rtt);
+}
+
+#[test]
+fn reassemble_goldsource_response() {
+    use crate::packet::split_goldsource_datagrams;
+
+    let rules = large_rules();
+    let response = rules.to_framed_bytes();
+    let mut datagrams = split_goldsource_datagrams(3, &response, 1400);
+    datagrams.reverse();
+
+    let (address, _) = serve_datagrams(vec![datagrams]);
+    let client = A2SClient::connect(address).unwrap();
+
+    assert_eq!(rules, client.rules().unwrap());
+}
+
+#[test]
+fn split_packets_that_dont_fit() {
+    use crate::packet::split_source_payload;
+
+    let response = large_rules().to_framed_bytes();
+    let packets = split_source_payload(7, &response, 1248).unwrap();
+    let resplit = split_source_payload(7, &response, 600).unwrap();
+
+    let (address, _) = serve_datagrams(vec![vec![packets[0].to_bytes(), resplit[1].to_bytes()]]);
+    let client = A2SClient::connect(address).unwrap();
+    let error = client.rules().unwrap_err();
+
+    assert!(matches!(
+        error,
+        ClientError::Assemble(AssembleError::TotalMismatch { .. })
+    ));
+    assert_eq!(io::ErrorKind::InvalidData, io::Error::from(error).kind());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn reassemble_compressed_response() {
+    use crate::packet::{compress_payload, split_compressed_source_payload};
+
+    let rules = large_rules();
+    let (compressed, data) = compress_payload(&rules.to_framed_bytes()).unwrap();
+    let datagrams = split_compressed_source_payload(7, &compressed, data, 200)
+        .unwrap()
+        .iter()
+        .map(|packet| packet.to_bytes())
+        .collect();
+
+    let (address, _) = serve_datagrams(vec![datagrams]);
+    let client = A2SClient::connect(address).unwrap();
+
+    assert_eq!(rules, client.rules().unwrap());
+}
+
+#[test]
+fn ping() {
+    let response = PingResponse {
+        payload: "00000000000000".to_string(),
+    };
+    let (address, server) = serve(vec![response.to_framed_bytes()]);
+
+    let client = A2SClient::connect(address).unwrap();
+    client.query_ping().unwrap();
+
+    assert_eq!(PingRequest.to_framed_bytes(), server.join().unwrap()[0]);
+}
+
+#[test]
+fn ping_falls_back_to_info() {
+    use crate::challenge::build_challenge_response;
+
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut buffer = [0; BUFFER_SIZE];
+        // Ignore the ping like most servers do
+        server.recv_from(&mut buffer).unwrap();
+        let (received, peer) = server.recv_from(&mut buffer).unwrap();
+        server.send_to(&build_challenge_response(1), peer).unwrap();
+        buffer[..received].to_vec()
+    });
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(100),
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+    client.query_ping().unwrap();
+
+    assert_eq!(build_info_request(None), handle.join().unwrap());
+}
+
+#[test]
+fn query_all_shares_challenge() {
+    use crate::challenge::build_challenge_response;
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    let players = ResponsePlayer {
+        players: 0,
+        player_data: Vec::new(),
+    };
+    let rules = ResponseRule {
+        rules: 0,
+        rule_data: Vec::new(),
+        remaining_data: String::new(),
+    };
+    let (address, server) = serve(vec![
+        build_challenge_response(5),
+        info.to_framed_bytes(),
+        players.to_framed_bytes(),
+        rules.to_framed_bytes(),
+    ]);
+
+    let client = A2SClient::connect(address).unwrap();
+    let snapshot = client.query_all().unwrap();
+
+    assert_eq!(info, snapshot.info);
+    assert_eq!(Some(players), snapshot.players);
+    assert_eq!(Some(rules), snapshot.rules);
+    assert!(snapshot.unanswered.is_empty());
+    assert_eq!(build_player_request(5), server.join().unwrap()[2]);
+}
+
+#[test]
+fn query_all_without_rules() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    let players = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    // A2S_RULES is never answered
+    let (address, _server) = serve_datagrams(vec![
+        vec![info.to_framed_bytes()],
+        vec![players.clone()],
+        vec![],
+        vec![info.to_framed_bytes()],
+        vec![],
+    ]);
+
+    let options = |optional| QueryOptions {
+        timeout: Duration::from_millis(100),
+        retries: 0,
+        optional,
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options(vec![OptionalQuery::Rules])).unwrap();
+    let snapshot = client.query_all().unwrap();
+
+    assert_eq!(info, snapshot.info);
+    assert!(snapshot.players.is_some());
+    assert_eq!(None, snapshot.rules);
+    assert_eq!(vec![OptionalQuery::Rules], snapshot.unanswered);
+
+    // Players time out as well, which are required
+    let client = A2SClient::with_options(address, options(vec![])).unwrap();
+    assert!(matches!(client.query_all(), Err(ClientError::Io(e)) if is_timeout(&e)));
+}
+
+#[test]
+fn probe_capabilities() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    let players = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    // A2S_RULES is never answered
+    let (address, _server) =
+        serve_datagrams(vec![vec![info.to_framed_bytes()], vec![players], vec![]]);
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(100),
+        retries: 0,
+        optional: Vec::new(),
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+    let capabilities = client.probe().unwrap();
+
+    assert_eq!(
+        ServerCapabilities {
+            players: true,
+            rules: false
+        },
+        capabilities
+    );
+    assert_eq!(vec![OptionalQuery::Rules], capabilities.unanswered());
+}
+
+#[test]
+fn query_over_ipv6() {
+    let server = UdpSocket::bind("[::1]:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut buffer = [0; BUFFER_SIZE];
+        let (_, peer) = server.recv_from(&mut buffer).unwrap();
+        server
+            .send_to(&[0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00], peer)
+            .unwrap();
+    });
+
+    let client = A2SClient::connect(address).unwrap();
+
+    assert!(client.players().unwrap().player_data.is_empty());
+    assert!(client.peer_addr().unwrap().is_ipv6());
+    handle.join().unwrap();
+}
+
+#[test]
+fn connect_tries_each_address() {
+    let (address, _) = serve(Vec::new());
+    // Multicast without a scope can't be connected to
+    let unusable: SocketAddr = "[ff02::1]:27015".parse().unwrap();
+
+    let client = A2SClient::connect(&[unusable, address][..]).unwrap();
+    assert_eq!(address, client.peer_addr().unwrap());
+
+    let error = A2SClient::connect(&[][..] as &[SocketAddr]).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn alternate_address_families() {
+    let addresses: Vec<SocketAddr> = [
+        "[2001:db8::1]:1",
+        "[2001:db8::2]:1",
+        "[2001:db8::3]:1",
+        "192.0.2.1:1",
+        "192.0.2.2:1",
+    ]
+    .iter()
+    .map(|address| address.parse().unwrap())
+    .collect();
+
+    let alternated = alternate_families(addresses.clone());
+    assert_eq!(
+        vec![
+            addresses[0],
+            addresses[3],
+            addresses[1],
+            addresses[4],
+            addresses[2]
+        ],
+        alternated
+    );
+    assert!(alternate_families(Vec::new()).is_empty());
+}
+
+#[test]
+fn query_each_address_family() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build();
+    // Connected to but never answers
+    let silent = UdpSocket::bind("[::1]:0").unwrap();
+    let addresses = [silent.local_addr().unwrap()];
+
+    for strategy in [
+        AddressStrategy::Sequential,
+        AddressStrategy::Race(Duration::from_millis(50)),
+    ]
+    .iter()
+    {
+        let (address, server) = serve(vec![info.to_framed_bytes(), info.to_framed_bytes()]);
+        let options = QueryOptions {
+            timeout: Duration::from_millis(200),
+            retries: 0,
+            addresses: *strategy,
+            ..QueryOptions::default()
+        };
+        let client = A2SClient::with_options(&[addresses[0], address][..], options).unwrap();
+        assert_eq!(None, client.answered_by());
+
+        assert_eq!(info, client.info().unwrap());
+        assert_eq!(Some(address), client.answered_by());
+        assert_eq!(address, client.peer_addr().unwrap());
+        // Only the address that answered is queried from now on
+        assert_eq!(info, client.info().unwrap());
+        assert_eq!(1, client.last_stats().datagrams_sent);
+        server.join().unwrap();
+    }
+}
+
+#[test]
+fn query_many_servers() {
+    let info = crate::info_source::ServerInfoBuilder::new().build();
+    let responses = vec![
+        info.to_framed_bytes(),
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00],
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00],
+    ];
+    let (first, _) = serve(responses.clone());
+    let (second, _) = serve(responses);
+    // Kept open but never answers
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let results = query_many(
+        &[first, silent.local_addr().unwrap(), second],
+        2,
+        &crate::schedule::Interleave::Host,
+        |_| QueryOptions {
+            timeout: Duration::from_millis(50),
+            ..QueryOptions::default()
+        },
+    );
+
+    assert_eq!(3, results.len());
+    assert_eq!(info, results[0].as_ref().unwrap().info);
+    assert!(matches!(&results[1], Err(ClientError::Io(e)) if is_timeout(e)));
+    assert_eq!(info, results[2].as_ref().unwrap().info);
+}
+
+#[test]
+fn query_many_stopped_worker() {
+    let info = crate::info_source::ServerInfoBuilder::new().build();
+    let (address, _) = serve(vec![
+        info.to_framed_bytes(),
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00],
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00],
+    ]);
+    let broken: SocketAddr = "127.0.0.1:9".parse().unwrap();
+
+    // The worker dies on the broken server, the other one queries the rest
+    let results = query_many(
+        &[broken, address],
+        2,
+        &crate::schedule::InputOrder,
+        move |server| match server == broken {
+            true => panic!("no options for {}", server),
+            false => QueryOptions::default(),
+        },
+    );
+
+    assert_eq!(2, results.len());
+    assert!(matches!(&results[0], Err(ClientError::Io(e)) if e.kind() == io::ErrorKind::Other));
+    assert_eq!(info, results[1].as_ref().unwrap().info);
+}
+
+#[test]
+fn rate_limited_queries() {
+    use crate::clock::ManualClock;
+
+    let clock = ManualClock::new();
+    let start = clock.now();
+    let limiter = Arc::new(RateLimiter::with_clock(2, 1, Arc::new(clock.clone())));
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let (address, _) = serve(vec![response.clone(), response.clone(), response]);
+
+    let options = QueryOptions {
+        rate_limit: Some(limiter),
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+    for _ in 0..3 {
+        client.players().unwrap();
+    }
+
+    assert_eq!(Duration::from_secs(1), clock.now() - start);
+}
+
+#[test]
+fn cached_responses() {
+    let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let (address, server) = serve(vec![response]);
+
+    let options = QueryOptions {
+        cache: Some(Arc::clone(&cache)),
+        ..QueryOptions::default()
+    };
+    let client = A2SClient::with_options(address, options).unwrap();
+    let first = client.players().unwrap();
+    let second = client.players().unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(1, cache.len());
+    assert_eq!(1, server.join().unwrap().len());
+}
+
+#[test]
+fn query_stats() {
+    use crate::challenge::build_challenge_response;
+
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let (address, server) = serve(vec![
+        build_challenge_response(7),
+        response.clone(),
+        response.clone(),
+    ]);
+
+    let client = A2SClient::connect(address).unwrap();
+    client.players().unwrap();
+    let first = client.last_stats();
+
+    assert_eq!(2, first.datagrams_sent);
+    assert_eq!(2, first.datagrams_received);
+    assert_eq!(1, first.challenges);
+    assert_eq!(0, first.retries);
+    assert!(first.challenge_rtt > Duration::from_secs(0));
+    assert!(first.response_rtt > Duration::from_secs(0));
+    assert!(first.challenge_rtt + first.response_rtt <= first.elapsed);
+    assert_eq!(0, first.fragments);
+    assert_eq!(Duration::from_secs(0), first.reassembly);
+    assert_eq!(2 * build_player_request(-1).len() as u64, first.bytes_sent);
+    assert_eq!(
+        (build_challenge_response(7).len() + response.len()) as u64,
+        first.bytes_received
+    );
+
+    client.players().unwrap();
+    assert_eq!(1, client.last_stats().datagrams_sent);
+    assert_eq!(0, client.last_stats().challenges);
+    assert_eq!(3, client.total_stats().datagrams_sent);
+    assert_eq!(
+        first.elapsed + client.last_stats().elapsed,
+        client.total_stats().elapsed
+    );
+    server.join().unwrap();
+}
+
+/// Subscriber listing the fields recorded on spans
+#[cfg(all(test, feature = "tracing"))]
+#[derive(Default)]
+struct SpanFields(Mutex<Vec<String>>);
+
+#[cfg(all(test, feature = "tracing"))]
+impl tracing::field::Visit for &SpanFields {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        let field = format!("{}={:?}", field.name(), value);
+        self.0.lock().unwrap().push(field);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+impl tracing::Subscriber for SpanFields {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        self.0
+            .lock()
+            .unwrap()
+            .push(span.metadata().name().to_string());
+        span.record(&mut &*self);
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        values.record(&mut &*self);
+    }
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn trace_queries() {
+    use crate::challenge::build_challenge_response;
+
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let (address, server) = serve(vec![build_challenge_response(7), response]);
+
+    let fields = Arc::new(SpanFields::default());
+    let client = A2SClient::connect(address).unwrap();
+    tracing::subscriber::with_default(fields.clone(), || client.players().unwrap());
+
+    assert_eq!(
+        vec![
+            "a2s_query".to_string(),
+            "query=\"players\"".to_string(),
+            format!("server={}", address),
+            "attempts=2".to_string(),
+            "challenges=1".to_string(),
+            "outcome=\"ok\"".to_string(),
+        ],
+        *fields.0.lock().unwrap()
+    );
+    server.join().unwrap();
+}
@@ -0,0 +1,100 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// # Traits
+/**
+Source of time for anything that waits or measures elapsed time, such as timeouts, retries and backoff.
+
+Using [`ManualClock`] in place of [`SystemClock`] makes that logic deterministic in tests.
+*/
+pub trait Clock: Debug + Send + Sync {
+    /// Current point in time
+    fn now(&self) -> Instant;
+
+    /// Blocks the current thread for the duration
+    fn sleep(&self, duration: Duration);
+}
+
+// # Structs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// [`Clock`] backed by [`Instant::now`] and [`std::thread::sleep`]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/**
+[`Clock`] that only moves when it is advanced or slept on, sleeping returns immediately.
+
+Clones share the same time so a clone can be handed to the code under test and advanced from the test.
+
+# Examples
+```
+use a2s_parse::clock::{Clock, ManualClock};
+use std::time::Duration;
+
+let clock = ManualClock::new();
+let start = clock.now();
+
+clock.advance(Duration::from_secs(2));
+clock.sleep(Duration::from_secs(1));
+
+assert_eq!(Duration::from_secs(3), clock.now() - start);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at the current time
+    pub fn new() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::from_secs(0))),
+        }
+    }
+
+    /// Moves the clock forward by the duration
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
+
+// # Tests
+#[test]
+fn clones_share_time() {
+    let clock = ManualClock::new();
+    let clone = clock.clone();
+    let start = clock.now();
+
+    clone.sleep(Duration::from_millis(500));
+
+    assert_eq!(Duration::from_millis(500), clock.now() - start);
+}
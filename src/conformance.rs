@@ -0,0 +1,518 @@
+use crate::debug::dissect_with_failure;
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::packet::PayloadHeader;
+use crate::parser_util::{ByteReader, ReadError, Recovery};
+
+use std::convert::TryFrom;
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A single place where a payload deviates from the format documented on the [wiki](https://developer.valvesoftware.com/wiki/Server_queries)
+pub struct Deviation {
+    /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+    pub field: String,
+    /// Offset of the field within the checked payload
+    pub offset: usize,
+    /// What is wrong with the field
+    pub kind: DeviationKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The ways a field can deviate from the wiki
+pub enum DeviationKind {
+    /// The value is known but uses the wrong case for the engine, e.g. 'D' in a Source A2S_INFO response
+    WrongCase {
+        /// Byte found in the payload
+        found: u8,
+        /// Byte the wiki specifies
+        expected: u8,
+    },
+    /// The value is not one listed on the wiki
+    UnknownValue(u8),
+    /// A boolean field was neither 0 nor 1
+    InvalidBool(u8),
+    /// Bits are set in the extra data flag that don't correspond to any field
+    UnknownFlagBits(u8),
+    /// The header byte doesn't correspond to any message
+    UnknownHeader(u8),
+    /// A ping response other than `""` or `"00000000000000"`
+    UnexpectedPingPayload,
+    /// A string or null byte ran to the end of the payload without a null terminator
+    MissingNull,
+    /// The payload ended in the middle of a field
+    Truncated,
+    /// Number of bytes remaining after the last field
+    TrailingData(usize),
+    /// The datagram filled the whole receive buffer so the operating system may have cut it off,
+    /// the buffer should be enlarged before blaming the server
+    PossiblyTruncated,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Something a lenient parse worked around instead of failing, see [`parse_lenient`]
+pub enum ParseWarning {
+    /// The payload deviates from the wiki as reported by [`check`], e.g. data after the last field that was skipped
+    Deviation(Deviation),
+    /// A count or string exceeded its [`ParseLimits`](crate::parser_util::ParseLimits), only as many entries or bytes
+    /// as the limit allows were kept
+    Clamped {
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+        field: Option<String>,
+        /// Offset of the field within the payload
+        offset: usize,
+    },
+    /// An optional extra data field was cut off, it and the extra data fields after it were left out
+    Dropped {
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+        field: Option<String>,
+        /// Offset of the field within the payload
+        offset: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parsed response contradicting itself, returned by the `validate` methods of the responses.
+/// Each value may be fine on its own, so these can't be found by [`check`]
+pub enum Violation {
+    /// More players than the server has slots, some servers count players in reserved slots this way
+    PlayersOverMax {
+        /// Players on the server
+        players: u8,
+        /// Slots of the server
+        max_players: u8,
+    },
+    /// More bots than players, bots are included in the player count
+    BotsOverPlayers {
+        /// Bots on the server
+        bots: u8,
+        /// Players on the server
+        players: u8,
+    },
+    /// The count sent in front of a list doesn't match the number of entries in it
+    CountMismatch {
+        /// Name of the count field, e.g. `players` or `rules`
+        field: String,
+        /// Count sent by the server
+        count: i32,
+        /// Entries in the list
+        entries: usize,
+    },
+    /// An extra data field is present while its bit in the extra data flag is clear, or missing while it is set
+    ExtraDataFlag {
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+        field: String,
+        /// The bit of the field is set in the flag
+        flag_set: bool,
+    },
+}
+
+// # Exposed functions
+/**
+Checks a payload against the strict format documented on the [wiki](https://developer.valvesoftware.com/wiki/Server_queries)
+and returns every deviation found, an empty Vec means the payload conforms.
+
+Intended for server plugin authors verifying their own query implementations, the parsers in this crate accept many of these deviations.
+The payload may include the `-1` framing or start directly at the header byte.
+
+# Examples
+```
+use a2s_parse::conformance::{check, DeviationKind};
+
+// A2S_PING response with extra data after the string
+let payload = [0x6A, 0x00, 0x00];
+
+let deviations = check(&payload);
+
+assert_eq!(DeviationKind::TrailingData(1), deviations[0].kind);
+```
+*/
+pub fn check(input: &[u8]) -> Vec<Deviation> {
+    let (fields, failure) = dissect_with_failure(input);
+    let mut deviations = Vec::new();
+
+    let header = fields
+        .iter()
+        .find(|field| field.name == "header")
+        .map(|field| input[field.range.start]);
+    let (server_types, environments): (&[u8], &[u8]) = match header {
+        // Gold Source uses the uppercase letters and has no MacOS servers
+        Some(0x6D) => (b"DLP", b"LW"),
+        _ => (b"dlp", b"lwmo"),
+    };
+
+    for field in &fields {
+        let byte = input[field.range.start];
+
+        let kind = match field.name.as_str() {
+            "header" => match PayloadHeader::from(byte) {
+                PayloadHeader::Other(b) => Some(DeviationKind::UnknownHeader(b)),
+                _ => None,
+            },
+            "server_type" => letter(byte, server_types),
+            "environment" => letter(byte, environments),
+            "visibility" | "vac" | "mod_half_life" if byte > 1 => {
+                Some(DeviationKind::InvalidBool(byte))
+            }
+            "extra_data_flag" if byte & !0xF1 != 0 => {
+                Some(DeviationKind::UnknownFlagBits(byte & !0xF1))
+            }
+            "the_ship.mode" if byte > 5 => Some(DeviationKind::UnknownValue(byte)),
+            "mod.mod_type" | "mod.dll" if byte > 1 => Some(DeviationKind::UnknownValue(byte)),
+            "payload" if header == Some(0x6A) => match &input[field.range.clone()] {
+                b"\0" | b"00000000000000\0" => None,
+                _ => Some(DeviationKind::UnexpectedPingPayload),
+            },
+            "unparsed" if failure.is_none() => Some(DeviationKind::TrailingData(field.range.len())),
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            deviations.push(Deviation {
+                field: field.name.clone(),
+                offset: field.range.start,
+                kind,
+            });
+        }
+    }
+
+    if let Some(failure) = failure {
+        let kind = match failure.missing_null() {
+            true => DeviationKind::MissingNull,
+            false => DeviationKind::Truncated,
+        };
+        deviations.push(Deviation {
+            field: failure.name,
+            offset: failure.offset,
+            kind,
+        });
+    }
+
+    deviations
+}
+
+/**
+Same as [`check`] for a datagram as it was received, also reporting [`DeviationKind::PossiblyTruncated`]
+if the datagram exactly filled the receive buffer of `buffer_size` bytes.
+
+UDP datagrams larger than the buffer are silently cut off to its size, so a datagram that fills it is
+more likely to be missing data than to coincidentally be the same size.
+
+# Examples
+```
+use a2s_parse::conformance::{check_received, DeviationKind};
+
+// A2S_PING response received into a 3 byte buffer
+let datagram = [0x6A, 0x30, 0x30];
+
+let deviations = check_received(&datagram, 3);
+
+assert_eq!(DeviationKind::PossiblyTruncated, deviations.last().unwrap().kind);
+```
+*/
+pub fn check_received(datagram: &[u8], buffer_size: usize) -> Vec<Deviation> {
+    let mut deviations = check(datagram);
+
+    if datagram.len() >= buffer_size {
+        deviations.push(Deviation {
+            field: "datagram".to_string(),
+            offset: datagram.len(),
+            kind: DeviationKind::PossiblyTruncated,
+        });
+    }
+
+    deviations
+}
+
+/**
+Parses a payload following the header byte as best as possible and returns the message along with warnings for
+everything that was worked around and every deviation from the [wiki](https://developer.valvesoftware.com/wiki/Server_queries).
+
+Data after the last field is skipped. The info, player and rules responses also clamp counts and strings exceeding the
+default [`ParseLimits`](crate::parser_util::ParseLimits) to the limits, and Source info responses leave out extra data
+fields that are cut off. The `parse_*_lenient` functions of those modules take the limits. Unknown enum values are
+accepted by every parser and only reported as deviations. Offsets count from the start of the payload, payloads that
+can't be parsed even so still fail.
+
+# Examples
+```
+use a2s_parse::conformance::{parse_lenient, DeviationKind, ParseWarning};
+use a2s_parse::ping::PingResponse;
+
+// "00" followed by a stray byte
+let (ping, warnings) = parse_lenient::<PingResponse>(&[0x30, 0x30, 0x00, 0x30]).unwrap();
+
+assert_eq!("00", ping.payload);
+assert!(matches!(&warnings[0], ParseWarning::Deviation(d) if d.kind == DeviationKind::UnexpectedPingPayload));
+assert!(matches!(&warnings[1], ParseWarning::Deviation(d) if d.kind == DeviationKind::TrailingData(1)));
+```
+*/
+pub fn parse_lenient<T: A2sMessage>(payload: &[u8]) -> Result<(T, Vec<ParseWarning>), A2SError> {
+    T::parse_lenient(payload)
+}
+
+// # Private helper functions
+/// Deviations of the payload following the header byte, with offsets counting from the start of the payload
+pub(crate) fn payload_deviations(header: u8, payload: &[u8]) -> Vec<ParseWarning> {
+    check(&[&[header], payload].concat())
+        .into_iter()
+        .map(|deviation| {
+            ParseWarning::Deviation(Deviation {
+                offset: deviation.offset.saturating_sub(1),
+                ..deviation
+            })
+        })
+        .collect()
+}
+
+/// Runs the parser with a lenient reader, returns the message with warnings for what the reader recovered from
+/// followed by the deviations of the payload
+pub(crate) fn parse_with_warnings<'a, T>(
+    header: u8,
+    payload: &'a [u8],
+    parser: impl FnOnce(&mut ByteReader<'a>) -> Result<T, ReadError>,
+) -> Result<(T, Vec<ParseWarning>), A2SError> {
+    let mut reader = ByteReader::lenient(payload);
+    let message =
+        parser(&mut reader).map_err(|e| A2SError::from(e).with_field(&[header], payload))?;
+
+    let (fields, failure) = dissect_with_failure(&[&[header], payload].concat());
+    let field_at = |offset: usize| match &failure {
+        Some(failure) if failure.offset == offset + 1 => Some(failure.name.clone()),
+        _ => fields
+            .iter()
+            .find(|field| field.range.contains(&(offset + 1)))
+            .map(|field| field.name.clone()),
+    };
+    let mut warnings: Vec<ParseWarning> = reader
+        .into_recovered()
+        .into_iter()
+        .map(|(offset, recovery)| match recovery {
+            Recovery::Clamped => ParseWarning::Clamped {
+                field: field_at(offset),
+                offset,
+            },
+            Recovery::Dropped => ParseWarning::Dropped {
+                field: field_at(offset),
+                offset,
+            },
+        })
+        .collect();
+    warnings.extend(payload_deviations(header, payload));
+
+    Ok((message, warnings))
+}
+
+/// Checks the player and bot counts shared by Source and Gold Source info responses
+pub(crate) fn player_count_violations(players: u8, max_players: u8, bots: u8) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if players > max_players {
+        violations.push(Violation::PlayersOverMax {
+            players,
+            max_players,
+        });
+    }
+    if bots > players {
+        violations.push(Violation::BotsOverPlayers { bots, players });
+    }
+
+    violations
+}
+
+/// Checks that a count matches the length of the list following it
+pub(crate) fn count_violation(field: &str, count: i32, entries: usize) -> Option<Violation> {
+    match usize::try_from(count) {
+        Ok(count) if count == entries => None,
+        _ => Some(Violation::CountMismatch {
+            field: field.to_string(),
+            count,
+            entries,
+        }),
+    }
+}
+
+/// Checks the byte is one of the expected letters, if it isn't the case is checked before reporting it as unknown
+fn letter(byte: u8, expected: &[u8]) -> Option<DeviationKind> {
+    let swapped = match byte.is_ascii_uppercase() {
+        true => byte.to_ascii_lowercase(),
+        false => byte.to_ascii_uppercase(),
+    };
+
+    if expected.contains(&byte) {
+        None
+    } else if expected.contains(&swapped) {
+        Some(DeviationKind::WrongCase {
+            found: byte,
+            expected: swapped,
+        })
+    } else {
+        Some(DeviationKind::UnknownValue(byte))
+    }
+}
+
+// # Tests
+#[test]
+fn conforming_source_info() {
+    let payload: [u8; 26] = [
+        0xFF, 0xFF, 0xFF, 0xFF, 0x49, 0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0x0A,
+        0x00, 0x01, 0x02, 0x00, 0x64, 0x6C, 0x00, 0x01, 0x31, 0x00, 0x00,
+    ];
+
+    assert_eq!(Vec::<Deviation>::new(), check(&payload));
+}
+
+#[test]
+fn source_info_deviations() {
+    // Uppercase server type, vac of 2, undefined flag bit and an unterminated keywords string
+    let payload: [u8; 25] = [
+        0x49, 0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0x0A, 0x00, 0x01, 0x02, 0x00,
+        0x44, 0x6C, 0x00, 0x02, 0x31, 0x00, 0x22, 0x61, 0x62, 0x63,
+    ];
+
+    let deviations = check(&payload);
+
+    assert_eq!(
+        vec![
+            Deviation {
+                field: "server_type".to_string(),
+                offset: 15,
+                kind: DeviationKind::WrongCase {
+                    found: b'D',
+                    expected: b'd'
+                },
+            },
+            Deviation {
+                field: "vac".to_string(),
+                offset: 18,
+                kind: DeviationKind::InvalidBool(2),
+            },
+            Deviation {
+                field: "extra_data_flag".to_string(),
+                offset: 21,
+                kind: DeviationKind::UnknownFlagBits(0x02),
+            },
+            Deviation {
+                field: "keywords".to_string(),
+                offset: 22,
+                kind: DeviationKind::MissingNull,
+            },
+        ],
+        deviations
+    );
+}
+
+#[test]
+fn full_buffer_possibly_truncated() {
+    let payload: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+
+    assert!(check_received(&payload, 1400).is_empty());
+    assert_eq!(
+        vec![Deviation {
+            field: "datagram".to_string(),
+            offset: 6,
+            kind: DeviationKind::PossiblyTruncated,
+        }],
+        check_received(&payload, 6)
+    );
+}
+
+#[test]
+fn lenient_rules_parse() {
+    use crate::rules::ResponseRule;
+
+    // One rule followed by a stray byte
+    let payload: [u8; 7] = [0x01, 0x00, 0x61, 0x00, 0x31, 0x00, 0xFF];
+
+    assert!(ResponseRule::parse(&payload).is_err());
+    let (rules, warnings) = parse_lenient::<ResponseRule>(&payload).unwrap();
+
+    assert_eq!("1", rules.rule_data[0].value);
+    assert!(rules.remaining_data.is_empty());
+    assert_eq!(
+        vec![ParseWarning::Deviation(Deviation {
+            field: "unparsed".to_string(),
+            offset: 6,
+            kind: DeviationKind::TrailingData(1),
+        })],
+        warnings
+    );
+    assert!(parse_lenient::<ResponseRule>(&payload[..1]).is_err());
+}
+
+#[test]
+fn validate_responses() {
+    use crate::info_source::ServerInfoBuilder;
+    use crate::player::{PlayerData, ResponsePlayer};
+    use crate::rules::{ResponseRule, RuleData};
+
+    let mut info = ServerInfoBuilder::new()
+        .players(4)
+        .max_players(8)
+        .bots(5)
+        .keywords("secure")
+        .build();
+    info.extra_data_flag = 0x80 | 0x20;
+
+    assert_eq!(
+        vec![
+            Violation::BotsOverPlayers {
+                bots: 5,
+                players: 4
+            },
+            Violation::ExtraDataFlag {
+                field: "port".to_string(),
+                flag_set: true
+            },
+        ],
+        info.validate()
+    );
+    assert!(ServerInfoBuilder::new()
+        .port(27015)
+        .build()
+        .validate()
+        .is_empty());
+
+    let players = ResponsePlayer {
+        players: 0,
+        player_data: vec![PlayerData {
+            index: 0,
+            name: "a".to_string(),
+            score: 0,
+            duration: 0.0,
+            ship_data: None,
+        }],
+    };
+    let rules = ResponseRule {
+        rules: -1,
+        rule_data: vec![RuleData {
+            name: "a".to_string(),
+            value: "1".to_string(),
+        }],
+        remaining_data: String::new(),
+    };
+
+    assert_eq!(
+        vec![Violation::CountMismatch {
+            field: "players".to_string(),
+            count: 0,
+            entries: 1
+        }],
+        players.validate()
+    );
+    // Connecting players are counted but not listed
+    assert!(ResponsePlayer {
+        players: 2,
+        player_data: Vec::new(),
+    }
+    .validate()
+    .is_empty());
+    assert_eq!(
+        vec![Violation::CountMismatch {
+            field: "rules".to_string(),
+            count: -1,
+            entries: 1
+        }],
+        rules.validate()
+    );
+}
@@ -0,0 +1,393 @@
+use crate::client::DEFAULT_TIMEOUT;
+use crate::clock::{Clock, SystemClock};
+use crate::error::A2SError;
+use crate::packet::{DatagramAssembler, FeedResult};
+use crate::requests::{parse_request, Request};
+use crate::response::{parse_any, Response};
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of queries a server can have outstanding by default before the oldest is dropped
+pub const DEFAULT_MAX_OUTSTANDING: usize = 16;
+
+// # Structs / Enums
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Type of query a request starts, identifying which responses answer it
+pub enum QueryKind {
+    /// A2S_INFO, answered by a Source or Gold Source info response
+    Info,
+    /// A2S_PLAYER
+    Players,
+    /// A2S_RULES
+    Rules,
+    /// A2A_PING
+    Ping,
+    /// A2S_SERVERQUERY_GETCHALLENGE, answered by the challenge itself
+    GetChallenge,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// What a response datagram means for the queries sent to its server, returned by [`Correlator::response`]
+pub enum Correlation {
+    /// The response completes the oldest query of the kind, which is no longer outstanding
+    Answer(QueryKind),
+    /// The server wants the query resent with the challenge, the query stays outstanding
+    Challenge {
+        /// Query the challenge was sent for
+        query: QueryKind,
+        /// Challenge to resend the request with
+        challenge: i32,
+    },
+    /// A packet of a split response, the response is correlated once its last packet arrives
+    Partial,
+    /// A valid response no outstanding query asked for
+    Unsolicited,
+    /// The datagram isn't a valid response
+    Invalid,
+}
+
+/// Query sent to a server that wasn't answered yet
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Outstanding {
+    kind: QueryKind,
+    challenge_pending: bool,
+    /// When the request was last sent
+    sent: Instant,
+}
+
+/**
+Matches responses to the requests sent to a server, for proxies that forward or cache traffic without querying servers themselves.
+
+Feed it every request sent to a server with [`request`](Correlator::request) and every datagram received from the server
+with [`response`](Correlator::response). Queries of the same kind are answered in the order they were sent, a request
+resent after a challenge is the same query. Split responses are reassembled before they are correlated.
+
+Servers don't answer every query, so queries that weren't answered within the timeout of their last request are dropped,
+as are split responses whose last packet arrived longer ago. A server can have [`DEFAULT_MAX_OUTSTANDING`] queries
+outstanding or as many as [`with_max_outstanding`](Correlator::with_max_outstanding) allows, sending another drops the oldest.
+
+# Examples
+```
+use a2s_parse::challenge::build_challenge_response;
+use a2s_parse::correlator::{Correlation, Correlator, QueryKind};
+use a2s_parse::message::A2sMessage;
+use a2s_parse::player::ResponsePlayer;
+use a2s_parse::requests::build_player_request;
+
+let server = "127.0.0.1:27015".parse().unwrap();
+let mut correlator = Correlator::new();
+
+correlator.request(server, &build_player_request(-1)).unwrap();
+assert_eq!(
+    Correlation::Challenge { query: QueryKind::Players, challenge: 7 },
+    correlator.response(server, &build_challenge_response(7))
+);
+assert!(correlator.is_challenge_pending(server, QueryKind::Players));
+
+correlator.request(server, &build_player_request(7)).unwrap();
+let players = ResponsePlayer { players: 0, player_data: Vec::new() };
+assert_eq!(
+    Correlation::Answer(QueryKind::Players),
+    correlator.response(server, &players.to_framed_bytes())
+);
+assert!(correlator.outstanding(server).is_empty());
+```
+*/
+#[derive(Clone, Debug)]
+pub struct Correlator {
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+    max_outstanding: usize,
+    queries: HashMap<SocketAddr, VecDeque<Outstanding>>,
+    /// Split response in progress of each server with the time its latest packet arrived
+    assemblers: HashMap<SocketAddr, (Instant, DatagramAssembler)>,
+}
+
+impl QueryKind {
+    /// Kind of query the request starts
+    pub fn of(request: &Request) -> Self {
+        match request {
+            Request::Info(_) => QueryKind::Info,
+            Request::Players(_) => QueryKind::Players,
+            Request::Rules(_) => QueryKind::Rules,
+            Request::Ping(_) => QueryKind::Ping,
+            Request::GetChallenge(_) => QueryKind::GetChallenge,
+        }
+    }
+}
+
+impl Default for Correlator {
+    fn default() -> Self {
+        Correlator::new()
+    }
+}
+
+impl Correlator {
+    /// Correlator without outstanding queries, dropping queries that aren't answered within [`DEFAULT_TIMEOUT`]
+    pub fn new() -> Self {
+        Correlator::with_clock(DEFAULT_TIMEOUT, Arc::new(SystemClock))
+    }
+
+    /// Drops queries that aren't answered `timeout` after their last request, measuring time with the clock
+    pub fn with_clock(timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Correlator {
+            timeout,
+            clock,
+            max_outstanding: DEFAULT_MAX_OUTSTANDING,
+            queries: HashMap::new(),
+            assemblers: HashMap::new(),
+        }
+    }
+
+    /// Replaces [`DEFAULT_MAX_OUTSTANDING`], at least one query is kept
+    pub fn with_max_outstanding(mut self, max_outstanding: usize) -> Self {
+        self.max_outstanding = max_outstanding.max(1);
+        self
+    }
+
+    /// Records a request datagram sent to the server and returns the kind of query it starts.
+    /// A request of a kind waiting for its challenge to be answered resends that query instead of starting another one.
+    /// Expired queries are removed first, the oldest query is dropped if the server has too many outstanding
+    pub fn request(&mut self, server: SocketAddr, datagram: &[u8]) -> Result<QueryKind, A2SError> {
+        let kind = QueryKind::of(&parse_request(datagram)?);
+        self.expire();
+
+        let now = self.clock.now();
+        let queries = self.queries.entry(server).or_default();
+        match queries
+            .iter_mut()
+            .find(|query| query.kind == kind && query.challenge_pending)
+        {
+            Some(query) => {
+                query.challenge_pending = false;
+                query.sent = now;
+            }
+            None => {
+                if queries.len() >= self.max_outstanding {
+                    queries.pop_front();
+                }
+                queries.push_back(Outstanding {
+                    kind,
+                    challenge_pending: false,
+                    sent: now,
+                })
+            }
+        }
+        Ok(kind)
+    }
+
+    /// Classifies a datagram received from the server and updates the outstanding queries accordingly.
+    /// Expired queries are removed first
+    pub fn response(&mut self, server: SocketAddr, datagram: &[u8]) -> Correlation {
+        self.expire();
+
+        let now = self.clock.now();
+        let (updated, assembler) = self
+            .assemblers
+            .entry(server)
+            .or_insert_with(|| (now, DatagramAssembler::new()));
+        *updated = now;
+
+        let assembled;
+        let datagram = match assembler.feed(datagram) {
+            FeedResult::Complete(response) => {
+                self.assemblers.remove(&server);
+                assembled = response;
+                &assembled[..]
+            }
+            FeedResult::NeedMore => return Correlation::Partial,
+            FeedResult::Error(_) => {
+                self.assemblers.remove(&server);
+                return Correlation::Invalid;
+            }
+        };
+
+        let kind = match parse_any(datagram) {
+            Ok(Response::SourceInfo(_)) | Ok(Response::GoldSourceInfo(_)) => QueryKind::Info,
+            Ok(Response::Players(_)) => QueryKind::Players,
+            Ok(Response::Rules(_)) => QueryKind::Rules,
+            Ok(Response::Ping(_)) => QueryKind::Ping,
+            Ok(Response::Challenge(challenge)) => {
+                return self.challenge(server, challenge.challenge)
+            }
+            Ok(Response::Split(_)) | Err(_) => return Correlation::Invalid,
+        };
+        match self.answer(server, |query| query.kind == kind) {
+            Some(_) => Correlation::Answer(kind),
+            None => Correlation::Unsolicited,
+        }
+    }
+
+    /// Queries sent to the server that weren't answered yet, oldest first
+    pub fn outstanding(&self, server: SocketAddr) -> Vec<QueryKind> {
+        self.queries
+            .get(&server)
+            .map(|queries| queries.iter().map(|query| query.kind).collect())
+            .unwrap_or_default()
+    }
+
+    /// True if the server answered a query of the kind with a challenge and the request wasn't resent yet
+    pub fn is_challenge_pending(&self, server: SocketAddr, kind: QueryKind) -> bool {
+        self.queries
+            .get(&server)
+            .into_iter()
+            .flatten()
+            .any(|query| query.kind == kind && query.challenge_pending)
+    }
+
+    /// Drops the outstanding queries and buffered packets of the server, e.g. once the proxy gave up waiting for it
+    pub fn forget(&mut self, server: SocketAddr) {
+        self.queries.remove(&server);
+        self.assemblers.remove(&server);
+    }
+
+    /// Removes the queries and split responses that timed out, returns how many queries were removed
+    pub fn expire(&mut self) -> usize {
+        let now = self.clock.now();
+        let timeout = self.timeout;
+        let mut expired = 0;
+
+        self.queries.retain(|_, queries| {
+            let before = queries.len();
+            queries.retain(|query| now.saturating_duration_since(query.sent) < timeout);
+            expired += before - queries.len();
+            !queries.is_empty()
+        });
+        self.assemblers
+            .retain(|_, (updated, _)| now.saturating_duration_since(*updated) < timeout);
+        expired
+    }
+
+    /// Attributes a challenge to the oldest query still waiting for one. GetChallenge queries are answered by it
+    fn challenge(&mut self, server: SocketAddr, challenge: i32) -> Correlation {
+        let queries = match self.queries.get_mut(&server) {
+            Some(queries) => queries,
+            None => return Correlation::Unsolicited,
+        };
+
+        match queries.iter().position(|query| !query.challenge_pending) {
+            Some(index) if queries[index].kind == QueryKind::GetChallenge => {
+                queries.remove(index);
+                Correlation::Answer(QueryKind::GetChallenge)
+            }
+            Some(index) => {
+                queries[index].challenge_pending = true;
+                Correlation::Challenge {
+                    query: queries[index].kind,
+                    challenge,
+                }
+            }
+            None => Correlation::Unsolicited,
+        }
+    }
+
+    /// Removes and returns the oldest outstanding query matching the predicate
+    fn answer(
+        &mut self,
+        server: SocketAddr,
+        matches: impl Fn(&Outstanding) -> bool,
+    ) -> Option<Outstanding> {
+        let queries = self.queries.get_mut(&server)?;
+        let index = queries.iter().position(matches)?;
+
+        queries.remove(index)
+    }
+}
+
+// # Tests
+#[test]
+fn correlate_interleaved_queries() {
+    use crate::info_source::ServerInfoBuilder;
+    use crate::message::A2sMessage;
+    use crate::packet::split_source_payload;
+    use crate::requests::{build_info_request, build_rules_request};
+    use crate::rules::{ResponseRule, RuleData};
+
+    let server = "127.0.0.1:27015".parse().unwrap();
+    let other = "127.0.0.1:27016".parse().unwrap();
+    let info = ServerInfoBuilder::new().build().to_framed_bytes();
+    let rules = ResponseRule {
+        rules: 1,
+        rule_data: vec![RuleData {
+            name: "mp_friendlyfire".to_string(),
+            value: "0".to_string(),
+        }],
+        remaining_data: String::new(),
+    }
+    .to_framed_bytes();
+    let packets = split_source_payload(3, &rules, 30).unwrap();
+    let mut correlator = Correlator::new();
+
+    assert_eq!(
+        Ok(QueryKind::Rules),
+        correlator.request(server, &build_rules_request(5))
+    );
+    assert_eq!(
+        Ok(QueryKind::Info),
+        correlator.request(server, &build_info_request(None))
+    );
+    assert_eq!(
+        vec![QueryKind::Rules, QueryKind::Info],
+        correlator.outstanding(server)
+    );
+
+    assert_eq!(
+        Correlation::Answer(QueryKind::Info),
+        correlator.response(server, &info)
+    );
+    assert_eq!(Correlation::Unsolicited, correlator.response(other, &info));
+    assert_eq!(
+        Correlation::Partial,
+        correlator.response(server, &packets[0].to_bytes())
+    );
+    assert_eq!(
+        Correlation::Answer(QueryKind::Rules),
+        correlator.response(server, &packets[1].to_bytes())
+    );
+    assert_eq!(
+        Correlation::Invalid,
+        correlator.response(server, &[0xFF, 0xFF, 0xFF, 0xFF, 0x00])
+    );
+    assert!(correlator.outstanding(server).is_empty());
+}
+
+#[test]
+fn queries_expire_and_are_capped() {
+    use crate::clock::ManualClock;
+    use crate::packet::split_source_payload;
+    use crate::requests::{build_info_request, build_player_request};
+
+    let clock = ManualClock::new();
+    let server = "127.0.0.1:27015".parse().unwrap();
+    let mut correlator = Correlator::with_clock(Duration::from_secs(3), Arc::new(clock.clone()))
+        .with_max_outstanding(2);
+
+    correlator
+        .request(server, &build_info_request(None))
+        .unwrap();
+    clock.advance(Duration::from_secs(2));
+    correlator
+        .request(server, &build_player_request(-1))
+        .unwrap();
+    correlator
+        .request(server, &build_player_request(-1))
+        .unwrap();
+    assert_eq!(
+        vec![QueryKind::Players, QueryKind::Players],
+        correlator.outstanding(server)
+    );
+
+    let packets = split_source_payload(1, &[0xFF; 100], 60).unwrap();
+    assert_eq!(
+        Correlation::Partial,
+        correlator.response(server, &packets[0].to_bytes())
+    );
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(2, correlator.expire());
+    assert!(correlator.outstanding(server).is_empty());
+    assert!(correlator.queries.is_empty());
+    assert!(correlator.assemblers.is_empty());
+}
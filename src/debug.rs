@@ -0,0 +1,645 @@
+use std::{collections::HashMap, fmt::Debug, ops::Range};
+
+use crate::info_goldsource::{dll, mod_type};
+use crate::info_source::{
+    game_id, keywords, port, source_tv_name, source_tv_port, steam_id, TheShipGameMode,
+};
+use crate::packet::PayloadHeader;
+use crate::parser_util::{environment, server_type, ByteReader, ReadError, ReadErrorKind};
+
+// # Structs
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A single field found while dissecting a datagram
+pub struct Field {
+    /// Name of the field, repeated fields are indexed e.g. `player[0].name`
+    pub name: String,
+    /// Byte range of the field within the dissected datagram
+    pub range: Range<usize>,
+    /// Human readable value of the field
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A field that differs between two payloads, see [`diff`]
+pub struct FieldDiff {
+    /// Name of the field
+    pub name: String,
+    /// The field in the left payload, None if it is only present in the right payload
+    pub left: Option<Field>,
+    /// The field in the right payload, None if it is only present in the left payload
+    pub right: Option<Field>,
+}
+
+/// The field a dissection stopped at
+pub(crate) struct Failure {
+    /// Name of the field that couldn't be read
+    pub(crate) name: String,
+    /// Offset the field started at
+    pub(crate) offset: usize,
+    /// Why the field couldn't be read
+    pub(crate) kind: ReadErrorKind,
+}
+
+#[cfg(feature = "debug-errors")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A step of a [`trace`], one for each field the parse reached in the order they were read
+pub enum TraceStep {
+    /// The field was read
+    Parsed(Field),
+    /// The field couldn't be read, always the last step of a trace
+    Failed {
+        /// Name of the field, e.g. `player[2].score`
+        name: String,
+        /// Offset the field started at
+        offset: usize,
+        /// Why the field couldn't be read, e.g. `missing null terminator`
+        reason: String,
+    },
+}
+
+impl Failure {
+    /// The field was a string or null byte and no null terminator was found
+    pub(crate) fn missing_null(&self) -> bool {
+        matches!(
+            self.kind,
+            ReadErrorKind::MissingNull | ReadErrorKind::NotNull
+        )
+    }
+
+    /// Human readable reason the field couldn't be read
+    #[cfg(feature = "debug-errors")]
+    fn reason(&self) -> String {
+        match self.kind {
+            ReadErrorKind::MissingNull | ReadErrorKind::NotNull => {
+                "missing null terminator".to_string()
+            }
+            ReadErrorKind::Eof => "input ends inside the field".to_string(),
+            ReadErrorKind::TooLarge => "invalid value (exceeds the parse limit)".to_string(),
+            ReadErrorKind::Trailing(len) => format!("invalid value ({} bytes left over)", len),
+        }
+    }
+}
+
+/// Walks the input while keeping track of the current offset and every field read so far
+struct Dissector<'a> {
+    input: &'a [u8],
+    offset: usize,
+    fields: Vec<Field>,
+    failure: Option<Failure>,
+}
+
+impl<'a> Dissector<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Dissector {
+            input,
+            offset: 0,
+            fields: Vec::new(),
+            failure: None,
+        }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.input[self.offset..]
+    }
+
+    /// Runs the parser at the current offset and records the bytes it consumed as a field.
+    /// Once a parser has failed every following field is skipped.
+    fn field<T, P, R>(&mut self, name: &str, parser: P, render: R) -> Option<T>
+    where
+        P: FnOnce(&mut ByteReader<'a>) -> Result<T, ReadError>,
+        R: FnOnce(&T) -> String,
+    {
+        if self.failure.is_some() {
+            return None;
+        }
+
+        let mut reader = ByteReader::new(self.remaining());
+        match parser(&mut reader) {
+            Ok(value) => {
+                let consumed = reader.offset();
+                // Optional fields that were not present don't take up any bytes
+                if consumed > 0 {
+                    self.fields.push(Field {
+                        name: name.to_string(),
+                        range: self.offset..self.offset + consumed,
+                        value: render(&value),
+                    });
+                }
+                self.offset += consumed;
+
+                Some(value)
+            }
+            Err(e) => {
+                self.failure = Some(Failure {
+                    name: name.to_string(),
+                    offset: self.offset,
+                    kind: e.kind,
+                });
+
+                None
+            }
+        }
+    }
+
+    /// Anything left in the input is recorded as unparsed
+    fn finish(mut self) -> (Vec<Field>, Option<Failure>) {
+        if self.offset < self.input.len() {
+            self.fields.push(Field {
+                name: "unparsed".to_string(),
+                range: self.offset..self.input.len(),
+                value: format!("{} bytes", self.input.len() - self.offset),
+            });
+        }
+
+        (self.fields, self.failure)
+    }
+}
+
+// # Exposed functions
+/**
+Dissects a datagram into the fields it contains along with the byte range each field occupies.
+
+The datagram may include the `-1`/`-2` framing or start directly at the header byte.
+Split packets are dissected using the Source layout, only the first fragment carries a header.
+Parsing stops at the first field that can't be read, any bytes left over are returned as an `unparsed` field.
+*/
+pub fn dissect(input: &[u8]) -> Vec<Field> {
+    dissect_with_failure(input).0
+}
+
+/// Same as [`dissect`] but also returns the field the dissection stopped at, if any
+pub(crate) fn dissect_with_failure(input: &[u8]) -> (Vec<Field>, Option<Failure>) {
+    let mut dissector = Dissector::new(input);
+    framing(&mut dissector);
+
+    dissector.finish()
+}
+
+/**
+Traces how a datagram was parsed field by field, ending with the field the parse failed at if it did.
+
+Where an [`A2SError`](crate::error::A2SError) only reports the failing field, the trace shows every field read before it
+and the values they were read as, which is what is needed when adding support for a game that deviates from the wiki.
+The datagram may include the `-1`/`-2` framing or start directly at the header byte, same as [`dissect`].
+
+Only available with the `debug-errors` feature.
+
+# Examples
+```
+use a2s_parse::debug::{trace, TraceStep};
+
+// A2S_RULES response claiming one rule whose value is missing its terminator
+let payload = [0x45, 0x01, 0x00, 0x61, 0x00, 0x62];
+
+let steps = trace(&payload);
+
+for step in &steps {
+    println!("{:?}", step);
+}
+assert_eq!(
+    Some(&TraceStep::Failed {
+        name: "rule[0].value".to_string(),
+        offset: 5,
+        reason: "missing null terminator".to_string(),
+    }),
+    steps.last()
+);
+```
+*/
+#[cfg(feature = "debug-errors")]
+pub fn trace(input: &[u8]) -> Vec<TraceStep> {
+    let (fields, failure) = dissect_with_failure(input);
+    let mut steps: Vec<TraceStep> = fields.into_iter().map(TraceStep::Parsed).collect();
+
+    if let Some(failure) = failure {
+        // The bytes from the failed field on are recorded as unparsed, the failure already covers them
+        if let Some(TraceStep::Parsed(field)) = steps.last() {
+            if field.range.start == failure.offset && field.name == "unparsed" {
+                steps.pop();
+            }
+        }
+        steps.push(TraceStep::Failed {
+            reason: failure.reason(),
+            name: failure.name,
+            offset: failure.offset,
+        });
+    }
+
+    steps
+}
+
+/**
+Renders a datagram as a hexdump where each field starts on a new line annotated with its name and value.
+Intended for bug reports and protocol exploration.
+
+# Examples
+```
+use a2s_parse::debug::annotate;
+
+// Gold Source A2A_PING response
+let datagram = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+
+print!("{}", annotate(&datagram));
+// 0000  ff ff ff ff   framing: -1 (single packet)
+// 0004  6a            header: 'j' (PingResponse)
+// 0005  00            payload: ""
+```
+*/
+pub fn annotate(input: &[u8]) -> String {
+    let mut output = String::new();
+
+    for field in dissect(input) {
+        let bytes = &input[field.range.clone()];
+
+        for (line, chunk) in bytes.chunks(16).enumerate() {
+            let offset = field.range.start + line * 16;
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            if line == 0 {
+                output.push_str(&format!(
+                    "{:04x}  {:<47}  {}: {}\n",
+                    offset, hex, field.name, field.value
+                ));
+            } else {
+                output.push_str(&format!("{:04x}  {}\n", offset, hex));
+            }
+        }
+    }
+
+    output
+}
+
+/**
+Dissects both payloads and returns every field whose bytes differ, in the order they appear.
+
+Fields are matched by name so differences are still reported at the right offsets when an earlier field changed length.
+Fields are compared by their raw bytes, e.g. a server type of `'d'` and `'D'` differ even though both are [`Dedicated`](crate::parser_util::ServerType::Dedicated).
+Fields that were only found in one of the payloads are reported with the other side set to None.
+*/
+pub fn diff(left: &[u8], right: &[u8]) -> Vec<FieldDiff> {
+    let left_fields = dissect(left);
+    let right_fields = dissect(right);
+
+    let mut unmatched: HashMap<&str, &Field> = right_fields
+        .iter()
+        .map(|field| (field.name.as_str(), field))
+        .collect();
+    let mut diffs = Vec::new();
+
+    for l in &left_fields {
+        match unmatched.remove(l.name.as_str()) {
+            Some(r) if left[l.range.clone()] == right[r.range.clone()] => {}
+            r => diffs.push(FieldDiff {
+                name: l.name.clone(),
+                left: Some(l.clone()),
+                right: r.cloned(),
+            }),
+        }
+    }
+
+    // Keep the order fields appear in the right payload
+    for r in &right_fields {
+        if unmatched.contains_key(r.name.as_str()) {
+            diffs.push(FieldDiff {
+                name: r.name.clone(),
+                left: None,
+                right: Some(r.clone()),
+            });
+        }
+    }
+
+    diffs
+}
+
+// # Private dissection helper functions
+fn show<T: Debug>(value: &T) -> String {
+    format!("{:?}", value)
+}
+
+fn show_opt<T: Debug>(value: &Option<T>) -> String {
+    match value {
+        Some(v) => show(v),
+        None => "None".to_string(),
+    }
+}
+
+fn framing(d: &mut Dissector) -> Option<()> {
+    let prefix = ByteReader::new(d.remaining()).i32();
+
+    match prefix {
+        Ok(-1) => {
+            d.field("framing", ByteReader::i32, |_| {
+                "-1 (single packet)".to_string()
+            })?;
+            payload(d)
+        }
+        Ok(-2) => {
+            d.field("framing", ByteReader::i32, |_| {
+                "-2 (split packet)".to_string()
+            })?;
+            split(d)
+        }
+        // Assume the framing has already been removed
+        _ => payload(d),
+    }
+}
+
+fn split(d: &mut Dissector) -> Option<()> {
+    let id = d.field("id", ByteReader::i32, |v| format!("{} ({:#010x})", v, v))?;
+    d.field("total", ByteReader::u8, show)?;
+    let number = d.field("number", ByteReader::u8, show)?;
+    d.field("size", ByteReader::i16, show)?;
+
+    if number == 0 && id < 0 {
+        d.field("decompressed_size", ByteReader::i32, show)?;
+        d.field("crc32_checksum", ByteReader::i32, |v| {
+            format!("{:#010x}", v)
+        })?;
+    }
+
+    // Only the first fragment contains the start of the payload
+    if number == 0 {
+        framing(d)
+    } else {
+        d.field(
+            "fragment",
+            |r| Ok(r.rest()),
+            |v| format!("{} bytes", v.len()),
+        )
+        .map(|_| ())
+    }
+}
+
+fn payload(d: &mut Dissector) -> Option<()> {
+    let header = d.field("header", ByteReader::u8, |v| {
+        format!("{:?} ({:?})", *v as char, PayloadHeader::from(*v))
+    })?;
+
+    match PayloadHeader::from(header) {
+        PayloadHeader::InfoResponseSource => source_info(d),
+        PayloadHeader::InfoResponseGoldSource => goldsource_info(d),
+        PayloadHeader::PlayerResponse => players(d),
+        PayloadHeader::RulesResponse => rules(d),
+        PayloadHeader::PingResponse => d.field("payload", ByteReader::c_string, show).map(|_| ()),
+        PayloadHeader::InfoRequest => {
+            d.field("payload", ByteReader::c_string, show)?;
+            d.field("challenge", |r| Ok(r.i32().ok()), show_opt)
+                .map(|_| ())
+        }
+        PayloadHeader::PlayerRequest
+        | PayloadHeader::RulesRequest
+        | PayloadHeader::ChallengeRequest
+        | PayloadHeader::ChallengeResponse => {
+            d.field("challenge", ByteReader::i32, show).map(|_| ())
+        }
+        PayloadHeader::PingRequest => Some(()),
+        PayloadHeader::Other(_) => None,
+    }
+}
+
+fn source_info(d: &mut Dissector) -> Option<()> {
+    d.field("protocol", ByteReader::u8, show)?;
+    d.field("name", ByteReader::c_string, show)?;
+    d.field("map", ByteReader::c_string, show)?;
+    d.field("folder", ByteReader::c_string, show)?;
+    d.field("game", ByteReader::c_string, show)?;
+    let app_id = d.field("app_id", ByteReader::i16, show)?;
+    d.field("players", ByteReader::u8, show)?;
+    d.field("max_players", ByteReader::u8, show)?;
+    d.field("bots", ByteReader::u8, show)?;
+    d.field("server_type", server_type, show)?;
+    d.field("environment", environment, show)?;
+    d.field("visibility", ByteReader::bool, show)?;
+    d.field("vac", ByteReader::bool, show)?;
+
+    if app_id == 2400 {
+        d.field("the_ship.mode", |r| r.u8().map(TheShipGameMode::from), show)?;
+        d.field("the_ship.witnesses", ByteReader::u8, show)?;
+        d.field("the_ship.duration", ByteReader::u8, show)?;
+    }
+
+    d.field("version", ByteReader::c_string, show)?;
+    let flag = d
+        .field(
+            "extra_data_flag",
+            |r| Ok(r.opt_u8()),
+            |v| format!("{:#04x}", v.unwrap_or(0)),
+        )?
+        .unwrap_or(0);
+
+    d.field("port", |r| port(r, flag), show_opt)?;
+    d.field("steam_id", |r| steam_id(r, flag), show_opt)?;
+    d.field("source_tv_port", |r| source_tv_port(r, flag), show_opt)?;
+    d.field(
+        "source_tv_name",
+        |r| source_tv_name(r, flag, usize::MAX),
+        show_opt,
+    )?;
+    d.field("keywords", |r| keywords(r, flag, usize::MAX), show_opt)?;
+    d.field("game_id", |r| game_id(r, flag), show_opt)?;
+
+    Some(())
+}
+
+fn goldsource_info(d: &mut Dissector) -> Option<()> {
+    d.field("address", ByteReader::c_string, show)?;
+    d.field("name", ByteReader::c_string, show)?;
+    d.field("map", ByteReader::c_string, show)?;
+    d.field("folder", ByteReader::c_string, show)?;
+    d.field("game", ByteReader::c_string, show)?;
+    d.field("players", ByteReader::u8, show)?;
+    d.field("max_players", ByteReader::u8, show)?;
+    d.field("protocol", ByteReader::u8, show)?;
+    d.field("server_type", server_type, show)?;
+    d.field("environment", environment, show)?;
+    d.field("visibility", ByteReader::bool, show)?;
+    let is_mod = d.field("mod_half_life", ByteReader::bool, show)?;
+
+    if is_mod {
+        d.field("mod.link", ByteReader::c_string, show)?;
+        d.field("mod.download_link", ByteReader::c_string, show)?;
+        d.field("mod.null", ByteReader::null, |_| "0x00".to_string())?;
+        d.field("mod.version", ByteReader::i32, show)?;
+        d.field("mod.size", ByteReader::i32, show)?;
+        d.field("mod.mod_type", mod_type, show)?;
+        d.field("mod.dll", dll, show)?;
+    }
+
+    d.field("vac", ByteReader::bool, show)?;
+    d.field("bots", ByteReader::u8, show)?;
+
+    Some(())
+}
+
+fn players(d: &mut Dissector) -> Option<()> {
+    let count = d.field("players", ByteReader::u8, show)?;
+
+    // Connecting players are counted but have no data
+    let mut parsed = 0;
+    while parsed < count && !d.remaining().is_empty() {
+        d.field(&format!("player[{}].index", parsed), ByteReader::u8, show)?;
+        d.field(
+            &format!("player[{}].name", parsed),
+            ByteReader::c_string,
+            show,
+        )?;
+        d.field(&format!("player[{}].score", parsed), ByteReader::i32, show)?;
+        d.field(
+            &format!("player[{}].duration", parsed),
+            ByteReader::f32,
+            show,
+        )?;
+        parsed += 1;
+    }
+
+    // The Ship appends data for each player after the regular player data
+    for i in 0..parsed {
+        if d.remaining().is_empty() {
+            break;
+        }
+        d.field(&format!("player[{}].deaths", i), ByteReader::i32, show)?;
+        d.field(&format!("player[{}].money", i), ByteReader::i32, show)?;
+    }
+
+    Some(())
+}
+
+fn rules(d: &mut Dissector) -> Option<()> {
+    let count = d.field("rules", ByteReader::i16, show)?;
+
+    let mut parsed = 0;
+    while parsed < count && !d.remaining().is_empty() {
+        d.field(
+            &format!("rule[{}].name", parsed),
+            ByteReader::c_string,
+            show,
+        )?;
+        d.field(
+            &format!("rule[{}].value", parsed),
+            ByteReader::c_string,
+            show,
+        )?;
+        parsed += 1;
+    }
+
+    Some(())
+}
+
+// # Tests
+#[test]
+fn annotate_ping() {
+    let datagram: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+
+    let expected = format!(
+        "0000  {:<47}  framing: -1 (single packet)\n\
+         0004  {:<47}  header: 'j' (PingResponse)\n\
+         0005  {:<47}  payload: \"\"\n",
+        "ff ff ff ff", "6a", "00"
+    );
+
+    assert_eq!(expected, annotate(&datagram));
+}
+
+#[test]
+fn annotate_long_field() {
+    // Rule value longer than one line of the hexdump
+    let mut datagram = vec![0x45, 0x01, 0x00, 0x61, 0x00];
+    datagram.extend_from_slice(&[0x62; 20]);
+    datagram.push(0x00);
+
+    let annotated = annotate(&datagram);
+    let lines: Vec<&str> = annotated.lines().collect();
+
+    assert_eq!(5, lines.len());
+    assert!(lines[3].starts_with("0005  62 62"));
+    assert!(lines[3].ends_with(&format!("rule[0].value: {:?}", "b".repeat(20))));
+    assert_eq!("0015  62 62 62 62 00", lines[4]);
+}
+
+#[test]
+fn dissect_player_with_trailing_data() {
+    // Player response without the framing, one player followed by two bytes that don't belong
+    let payload: [u8; 15] = [
+        0x44, 0x01, 0x00, 0x41, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0xAB, 0xCD,
+    ];
+
+    let fields = dissect(&payload);
+    let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+
+    assert_eq!(
+        vec![
+            "header",
+            "players",
+            "player[0].index",
+            "player[0].name",
+            "player[0].score",
+            "player[0].duration",
+            "unparsed"
+        ],
+        names
+    );
+    assert_eq!(3..5, fields[3].range);
+    assert_eq!("\"A\"", fields[3].value);
+    assert_eq!(13..15, fields[6].range);
+}
+
+#[test]
+fn diff_shifted_fields() {
+    // Payloads only differ in the map name length and the case of the server type
+    let left: [u8; 22] = [
+        0x49, 0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0x0A, 0x00, 0x01, 0x02, 0x00,
+        0x64, 0x6C, 0x00, 0x01, 0x31, 0x00, 0x00,
+    ];
+    let right: [u8; 23] = [
+        0x49, 0x11, 0x61, 0x00, 0x62, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0x0A, 0x00, 0x01, 0x02,
+        0x00, 0x44, 0x6C, 0x00, 0x01, 0x31, 0x00, 0x00,
+    ];
+
+    let diffs = diff(&left, &right);
+    let names: Vec<&str> = diffs.iter().map(|d| d.name.as_str()).collect();
+
+    assert_eq!(vec!["map", "server_type"], names);
+    assert_eq!(4..6, diffs[0].left.as_ref().unwrap().range);
+    assert_eq!(4..7, diffs[0].right.as_ref().unwrap().range);
+    assert_eq!(15..16, diffs[1].left.as_ref().unwrap().range);
+    assert_eq!(16..17, diffs[1].right.as_ref().unwrap().range);
+}
+
+#[cfg(feature = "debug-errors")]
+#[test]
+fn trace_truncated_player() {
+    // Player response cut off within the score of the only player
+    let payload: [u8; 7] = [0x44, 0x01, 0x00, 0x41, 0x00, 0x05, 0x00];
+
+    let steps = trace(&payload);
+    let names: Vec<&str> = steps
+        .iter()
+        .map(|step| match step {
+            TraceStep::Parsed(field) => field.name.as_str(),
+            TraceStep::Failed { name, .. } => name.as_str(),
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            "header",
+            "players",
+            "player[0].index",
+            "player[0].name",
+            "player[0].score"
+        ],
+        names
+    );
+    assert_eq!(
+        TraceStep::Failed {
+            name: "player[0].score".to_string(),
+            offset: 5,
+            reason: "input ends inside the field".to_string(),
+        },
+        steps[4]
+    );
+}
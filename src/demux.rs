@@ -0,0 +1,209 @@
+use crate::clock::{Clock, SystemClock};
+use crate::packet::{AssembleError, AssemblyLimits, PacketAssembler, SplitPacket};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// # Structs
+/**
+Reassembles interleaved split responses from many servers, keyed by the address they came from and their id.
+
+Every response gets its own [`PacketAssembler`] when its first packet arrives, whichever packet that is.
+Responses that aren't complete within the timeout of their first packet are dropped, the lost packets won't be resent.
+The [`AssemblyLimits`] cap the number of responses and the bytes buffered across all of them.
+
+# Examples
+```
+use a2s_parse::demux::PacketDemux;
+use a2s_parse::packet::split_source_payload;
+use std::time::Duration;
+
+let mut demux = PacketDemux::new(Duration::from_secs(3));
+let first = "127.0.0.1:27015".parse().unwrap();
+let second = "127.0.0.1:27016".parse().unwrap();
+let response = vec![0xAB; 2000];
+let packets = split_source_payload(1, &response, 1248).unwrap();
+
+assert_eq!(None, demux.insert(first, &packets[0]).unwrap());
+assert_eq!(None, demux.insert(second, &packets[1]).unwrap());
+assert_eq!(Some(response.clone()), demux.insert(first, &packets[1]).unwrap());
+assert_eq!(1, demux.len());
+```
+*/
+#[derive(Debug)]
+pub struct PacketDemux {
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+    limits: AssemblyLimits,
+    assemblies: HashMap<(SocketAddr, i32), (Instant, PacketAssembler<'static>)>,
+}
+
+impl PacketDemux {
+    /// Drops responses that aren't complete `timeout` after their first packet
+    pub fn new(timeout: Duration) -> Self {
+        PacketDemux::with_clock(timeout, Arc::new(SystemClock))
+    }
+
+    /// Same as [`PacketDemux::new`] measuring time with the clock
+    pub fn with_clock(timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        PacketDemux {
+            timeout,
+            clock,
+            limits: AssemblyLimits::default(),
+            assemblies: HashMap::new(),
+        }
+    }
+
+    /// Replaces the default [`AssemblyLimits`]
+    pub fn with_limits(mut self, limits: AssemblyLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number of payload bytes buffered across all responses
+    pub fn buffered(&self) -> usize {
+        self.assemblies
+            .values()
+            .map(|(_, assembler)| assembler.buffered())
+            .sum()
+    }
+
+    /// Number of responses in progress, including expired ones that haven't been removed yet
+    pub fn len(&self) -> usize {
+        self.assemblies.len()
+    }
+
+    /// True if no responses are in progress
+    pub fn is_empty(&self) -> bool {
+        self.assemblies.is_empty()
+    }
+
+    /// Numbers of the packets of the response from `source` not received yet, None if no packet of it is buffered.
+    /// Lost packets aren't resent on their own, a client can resend the query if the response has been missing some for long
+    pub fn missing(&self, source: SocketAddr, id: i32) -> Option<Vec<u8>> {
+        self.assemblies
+            .get(&(source, id))
+            .map(|(_, assembler)| assembler.missing())
+    }
+
+    /// Time since the first packet of the response from `source` arrived, None if no packet of it is buffered
+    pub fn age(&self, source: SocketAddr, id: i32) -> Option<Duration> {
+        let now = self.clock.now();
+        self.assemblies
+            .get(&(source, id))
+            .map(|(started, _)| now.saturating_duration_since(*started))
+    }
+
+    /// Adds a packet received from `source` and returns the assembled response once it is complete.
+    /// Expired responses are removed first. A response that fails to assemble is removed and its error returned
+    pub fn insert(
+        &mut self,
+        source: SocketAddr,
+        packet: &impl SplitPacket,
+    ) -> Result<Option<Vec<u8>>, AssembleError> {
+        self.expire();
+
+        let key = (source, packet.id());
+        if !self.assemblies.contains_key(&key) && self.assemblies.len() >= self.limits.max_responses
+        {
+            return Err(AssembleError::TooManyResponses(self.limits.max_responses));
+        }
+        if self.buffered() + packet.payload().len() > self.limits.max_bytes {
+            return Err(AssembleError::TooLarge(self.limits.max_bytes));
+        }
+
+        let complete = match self.assemblies.get_mut(&key) {
+            Some((_, assembler)) => {
+                assembler.insert(packet)?;
+                assembler.is_complete()
+            }
+            None => {
+                let assembler = PacketAssembler::with_limits(packet, self.limits)?;
+                let complete = assembler.is_complete();
+                self.assemblies.insert(key, (self.clock.now(), assembler));
+                complete
+            }
+        };
+
+        match complete {
+            true => self
+                .assemblies
+                .remove(&key)
+                .map(|(_, assembler)| assembler.assemble())
+                .transpose(),
+            false => Ok(None),
+        }
+    }
+
+    /// Removes the responses that weren't completed in time, returns how many were removed
+    pub fn expire(&mut self) -> usize {
+        let now = self.clock.now();
+        let timeout = self.timeout;
+        let before = self.assemblies.len();
+
+        self.assemblies
+            .retain(|_, (started, _)| now.saturating_duration_since(*started) < timeout);
+        before - self.assemblies.len()
+    }
+}
+
+// # Tests
+#[test]
+fn interleaved_responses_expire() {
+    use crate::clock::ManualClock;
+    use crate::packet::split_source_payload;
+
+    let clock = ManualClock::new();
+    let mut demux = PacketDemux::with_clock(Duration::from_secs(3), Arc::new(clock.clone()));
+    let first = "127.0.0.1:27015".parse().unwrap();
+    let second = "127.0.0.1:27016".parse().unwrap();
+    let a: Vec<u8> = (0..=255).collect();
+    let b = vec![0x42; 300];
+    let a_packets = split_source_payload(5, &a, 112).unwrap();
+    let b_packets = split_source_payload(5, &b, 112).unwrap();
+    let c_packets = split_source_payload(6, &b, 112).unwrap();
+
+    assert_eq!(None, demux.insert(first, &a_packets[2]).unwrap());
+    assert_eq!(None, demux.insert(second, &b_packets[0]).unwrap());
+    assert_eq!(None, demux.insert(first, &c_packets[0]).unwrap());
+    assert_eq!(None, demux.insert(first, &a_packets[0]).unwrap());
+    assert_eq!(Some(a.clone()), demux.insert(first, &a_packets[1]).unwrap());
+    assert_eq!(2, demux.len());
+    assert_eq!(Some(vec![1, 2]), demux.missing(second, 5));
+    assert_eq!(None, demux.missing(second, 6));
+
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(Some(Duration::from_secs(2)), demux.age(first, 6));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(2, demux.expire());
+    assert_eq!(None, demux.insert(second, &b_packets[1]).unwrap());
+    assert_eq!(1, demux.len());
+}
+
+#[test]
+fn limits_responses_and_bytes() {
+    use crate::packet::split_source_payload;
+
+    let response = vec![0xAB; 1000];
+    let first = split_source_payload(1, &response, 112).unwrap();
+    let second = split_source_payload(2, &response, 112).unwrap();
+    let source = "127.0.0.1:27015".parse().unwrap();
+    let mut demux = PacketDemux::new(Duration::from_secs(3)).with_limits(AssemblyLimits {
+        max_responses: 1,
+        max_bytes: 150,
+        ..AssemblyLimits::default()
+    });
+
+    assert_eq!(None, demux.insert(source, &first[0]).unwrap());
+    assert_eq!(
+        Err(AssembleError::TooManyResponses(1)),
+        demux.insert(source, &second[0])
+    );
+    assert_eq!(
+        Err(AssembleError::TooLarge(150)),
+        demux.insert(source, &first[1])
+    );
+    assert_eq!(100, demux.buffered());
+}
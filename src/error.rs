@@ -0,0 +1,336 @@
+use crate::debug::dissect_with_failure;
+use crate::parser_util::{ReadError, ReadErrorKind};
+
+use std::{fmt, io};
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/**
+Reasons a request, response or packet can fail to parse, returned by every parser of the crate.
+
+Offsets count bytes from the start of the input given to the parser, so they point into the datagram
+for parsers taking complete datagrams and into the payload for parsers taking payloads.
+Errors within a message name the field they occurred in, using the names of [`dissect`](crate::debug::dissect).
+
+# Examples
+```
+use a2s_parse::error::A2SError;
+use a2s_parse::info_source::{parse_source_info, ServerInfoBuilder};
+use a2s_parse::message::A2sMessage;
+use a2s_parse::ping::parse_ping;
+
+assert_eq!(
+    Err(A2SError::TrailingData { offset: 2, len: 1 }),
+    parse_ping(&[0x30, 0x00, 0x30])
+);
+
+// Cut off within the 2 byte app id following the four strings
+let payload = ServerInfoBuilder::new().build().to_framed_bytes();
+let error = parse_source_info(&payload[5..10]).unwrap_err();
+
+assert_eq!(Some("app_id"), error.field());
+println!("{}", error);
+```
+
+Implements [`std::error::Error`] so it can be returned with `?` from functions returning `Box<dyn Error>` or similar
+```
+use a2s_parse::ping::parse_ping;
+
+fn ping(payload: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(parse_ping(payload)?)
+}
+
+assert_eq!("input ends inside payload at byte 1", ping(&[0x30]).unwrap_err().to_string());
+```
+*/
+pub enum A2SError {
+    /// The datagram doesn't start with the `-1` or `-2` prefix
+    InvalidPrefix,
+    /// The header byte doesn't belong to the message that was parsed, or to any message
+    UnexpectedHeader(u8),
+    /// The input ended before the field at the offset was complete
+    TruncatedField {
+        /// Offset of the field that was cut off, or the end of the input for strings missing their terminator
+        offset: usize,
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect), e.g. `source_tv_name` or `player[2].score`
+        field: Option<String>,
+    },
+    /// A field holds a value it can't have, e.g. a packet number that isn't below the number of packets
+    InvalidValue {
+        /// Offset of the field
+        offset: usize,
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+        field: Option<String>,
+    },
+    /// A string or count is larger than the [`ParseLimits`](crate::parser_util::ParseLimits) the parser was given
+    LimitExceeded {
+        /// Offset of the field
+        offset: usize,
+        /// Name of the field as reported by [`dissect`](crate::debug::dissect)
+        field: Option<String>,
+    },
+    /// Data is left after the last field of the message
+    TrailingData {
+        /// Offset of the first byte after the message
+        offset: usize,
+        /// Number of bytes left
+        len: usize,
+    },
+    /// The datagram is one packet of a response split across several, which have to be reassembled first
+    SplitResponse,
+}
+
+impl A2SError {
+    /**
+    True if the input ended before the message was complete, so feeding more bytes could still make it parse.
+    Every other error is a protocol violation no amount of further input fixes.
+
+    # Examples
+    ```
+    use a2s_parse::ping::parse_ping;
+
+    // The string terminator is missing, it may still arrive
+    assert!(parse_ping(&[0x30, 0x30]).unwrap_err().is_incomplete());
+    // Data after the message can't be fixed by reading more
+    assert!(!parse_ping(&[0x30, 0x00, 0x30]).unwrap_err().is_incomplete());
+    ```
+    */
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, A2SError::TruncatedField { .. })
+    }
+
+    /// Name of the field the error occurred in, if known
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            A2SError::TruncatedField { field, .. }
+            | A2SError::InvalidValue { field, .. }
+            | A2SError::LimitExceeded { field, .. } => field.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Names the field the error occurred in by dissecting the input again behind the bytes that were stripped
+    /// before parsing it, e.g. the header byte. Errors that already name their field are returned as they are
+    pub(crate) fn with_field(self, stripped: &[u8], input: &[u8]) -> Self {
+        if self.field().is_some() {
+            return self;
+        }
+        let (fields, failure) = dissect_with_failure(&[stripped, input].concat());
+        let name = |offset: usize| {
+            let offset = offset + stripped.len();
+            match failure {
+                Some(failure) => Some(failure.name),
+                None => fields
+                    .into_iter()
+                    .find(|field| field.range.contains(&offset))
+                    .map(|field| field.name),
+            }
+        };
+
+        match self {
+            A2SError::TruncatedField { offset, .. } => A2SError::TruncatedField {
+                offset,
+                field: name(offset),
+            },
+            A2SError::InvalidValue { offset, .. } => A2SError::InvalidValue {
+                offset,
+                field: name(offset),
+            },
+            A2SError::LimitExceeded { offset, .. } => A2SError::LimitExceeded {
+                offset,
+                field: name(offset),
+            },
+            error => error,
+        }
+    }
+
+    /// Moves the offset by the bytes in front of the input that was parsed, e.g. the prefix and header byte
+    pub(crate) fn offset_by(self, bytes: usize) -> Self {
+        match self {
+            A2SError::TruncatedField { offset, field } => A2SError::TruncatedField {
+                offset: offset + bytes,
+                field,
+            },
+            A2SError::InvalidValue { offset, field } => A2SError::InvalidValue {
+                offset: offset + bytes,
+                field,
+            },
+            A2SError::LimitExceeded { offset, field } => A2SError::LimitExceeded {
+                offset: offset + bytes,
+                field,
+            },
+            A2SError::TrailingData { offset, len } => A2SError::TrailingData {
+                offset: offset + bytes,
+                len,
+            },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for A2SError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            A2SError::InvalidPrefix => write!(f, "datagram doesn't start with the -1 or -2 prefix"),
+            A2SError::UnexpectedHeader(header) => {
+                write!(f, "unexpected header byte 0x{:02X}", header)
+            }
+            A2SError::TruncatedField {
+                offset,
+                field: Some(field),
+            } => write!(f, "input ends inside {} at byte {}", field, offset),
+            A2SError::TruncatedField { offset, .. } => {
+                write!(f, "input ends inside the field at byte {}", offset)
+            }
+            A2SError::InvalidValue {
+                offset,
+                field: Some(field),
+            } => write!(f, "invalid value of {} at byte {}", field, offset),
+            A2SError::InvalidValue { offset, .. } => write!(f, "invalid value at byte {}", offset),
+            A2SError::LimitExceeded {
+                offset,
+                field: Some(field),
+            } => write!(f, "{} at byte {} exceeds the parse limits", field, offset),
+            A2SError::LimitExceeded { offset, .. } => {
+                write!(f, "value at byte {} exceeds the parse limits", offset)
+            }
+            A2SError::TrailingData { offset, len } => {
+                write!(f, "{} bytes left after the message at byte {}", len, offset)
+            }
+            A2SError::SplitResponse => write!(f, "response is split across several packets"),
+        }
+    }
+}
+
+impl std::error::Error for A2SError {}
+
+/**
+Lets parsers be called with `?` in functions returning [`io::Result`], e.g. loops reading datagrams from a socket.
+Incomplete input is reported as [`io::ErrorKind::UnexpectedEof`], every other error as [`io::ErrorKind::InvalidData`].
+The A2SError is kept as the inner error
+
+# Examples
+```
+use a2s_parse::ping::parse_ping;
+use std::io;
+
+fn ping(payload: &[u8]) -> io::Result<String> {
+    Ok(parse_ping(payload)?)
+}
+
+assert_eq!(io::ErrorKind::UnexpectedEof, ping(&[0x30]).unwrap_err().kind());
+assert_eq!(io::ErrorKind::InvalidData, ping(&[0x30, 0x00, 0x30]).unwrap_err().kind());
+```
+*/
+impl From<ReadError> for A2SError {
+    fn from(error: ReadError) -> Self {
+        let offset = error.offset;
+        match error.kind {
+            ReadErrorKind::Eof | ReadErrorKind::MissingNull => A2SError::TruncatedField {
+                offset,
+                field: None,
+            },
+            ReadErrorKind::NotNull => A2SError::InvalidValue {
+                offset,
+                field: None,
+            },
+            ReadErrorKind::TooLarge => A2SError::LimitExceeded {
+                offset,
+                field: None,
+            },
+            ReadErrorKind::Trailing(len) => A2SError::TrailingData { offset, len },
+        }
+    }
+}
+
+impl From<A2SError> for io::Error {
+    fn from(input: A2SError) -> Self {
+        let kind = match input.is_incomplete() {
+            true => io::ErrorKind::UnexpectedEof,
+            false => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, input)
+    }
+}
+
+// # Tests
+#[test]
+fn convert_read_errors() {
+    use crate::parser_util::ByteReader;
+
+    let input = [0x01, 0x02, 0x03];
+    let mut reader = ByteReader::new(&input);
+    reader.u8().unwrap();
+
+    assert_eq!(
+        A2SError::TruncatedField {
+            offset: 1,
+            field: None
+        },
+        A2SError::from(reader.clone().i32().unwrap_err())
+    );
+    assert_eq!(
+        A2SError::TruncatedField {
+            offset: 3,
+            field: None
+        },
+        A2SError::from(reader.clone().c_string().unwrap_err())
+    );
+    assert_eq!(
+        A2SError::TrailingData { offset: 1, len: 2 },
+        A2SError::from(reader.finish().unwrap_err())
+    );
+    assert_eq!(
+        A2SError::InvalidValue {
+            offset: 1,
+            field: None
+        },
+        A2SError::from(reader.clone().null().unwrap_err())
+    );
+    assert_eq!(
+        A2SError::LimitExceeded {
+            offset: 1,
+            field: None
+        },
+        A2SError::from(reader.limited_c_string(1).unwrap_err())
+    );
+    assert_eq!(
+        A2SError::TruncatedField {
+            offset: 8,
+            field: None
+        },
+        A2SError::TruncatedField {
+            offset: 3,
+            field: None
+        }
+        .offset_by(5)
+    );
+}
+
+#[test]
+fn incomplete_input() {
+    use crate::info_source::{parse_source_info, ServerInfoBuilder};
+    use crate::message::A2sMessage;
+
+    let payload = ServerInfoBuilder::new().build().to_framed_bytes();
+
+    // Every cut through the payload could be completed by the bytes that follow
+    for end in 5..payload.len() {
+        assert!(parse_source_info(&payload[5..end])
+            .unwrap_err()
+            .is_incomplete());
+    }
+    assert!(!A2SError::InvalidPrefix.is_incomplete());
+    assert!(!A2SError::TrailingData { offset: 0, len: 1 }.is_incomplete());
+}
+
+#[test]
+fn convert_to_io_error() {
+    let error = io::Error::from(A2SError::UnexpectedHeader(0x41));
+
+    assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    assert_eq!(
+        Some(&A2SError::UnexpectedHeader(0x41)),
+        error.into_inner().unwrap().downcast_ref::<A2SError>()
+    );
+}
@@ -0,0 +1,356 @@
+use crate::async_client::{AsyncDatagramTransport, TransportFuture};
+use crate::backoff::{next_random, seed_random};
+use crate::client::{is_timeout, BUFFER_SIZE};
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// # Structs
+#[derive(Clone, Debug, PartialEq)]
+/**
+How often a [`FaultyTransport`] injects each fault, probabilities from 0 (never) to 1 (every datagram).
+
+Every datagram is rolled for every fault on its own, so a datagram can e.g. be both duplicated and corrupted.
+The default injects nothing.
+*/
+pub struct FaultPolicy {
+    /// Probability of a datagram being lost
+    pub drop: f64,
+    /// Probability of a datagram arriving twice
+    pub duplicate: f64,
+    /// Probability of a received datagram being held back until the next one arrived, it is lost if none follows
+    pub reorder: f64,
+    /// Probability of a received datagram arriving [`FaultPolicy::latency`] late
+    pub delay: f64,
+    /// Time delayed datagrams arrive late, e.g. after the client gave up waiting for them
+    pub latency: Duration,
+    /// Probability of a datagram being cut off at a random length
+    pub truncate: f64,
+    /// Probability of a random byte of the datagram being flipped
+    pub corrupt: f64,
+    /// Seed of the random numbers, the same seed and traffic inject the same faults
+    pub seed: u64,
+}
+
+impl Default for FaultPolicy {
+    /// No faults, seeded with 0
+    fn default() -> Self {
+        FaultPolicy {
+            drop: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            delay: 0.0,
+            latency: Duration::from_secs(0),
+            truncate: 0.0,
+            corrupt: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/**
+[`AsyncDatagramTransport`] decorator injecting the faults of UDP into the datagrams of another transport, to test how
+clients cope with lost, duplicated, reordered, late, truncated and corrupted datagrams.
+
+Sent datagrams can be dropped, duplicated, truncated or corrupted. Received datagrams can additionally be reordered and
+delayed, they are held back and handed out once their time has come. The faults are picked by a seeded random number
+generator, so tests fail the same way every run.
+
+# Examples
+```no_run
+# #[cfg(feature = "tokio")]
+# async fn run() -> Result<(), Box<dyn std::error::Error>> {
+use a2s_parse::async_client::AsyncA2SClient;
+use a2s_parse::fault::{FaultPolicy, FaultyTransport};
+use tokio::net::UdpSocket;
+
+let socket = UdpSocket::bind("0.0.0.0:0").await?;
+socket.connect("127.0.0.1:27015").await?;
+
+let policy = FaultPolicy {
+    drop: 0.2,
+    duplicate: 0.1,
+    reorder: 0.1,
+    seed: 7,
+    ..FaultPolicy::default()
+};
+let client = AsyncA2SClient::new(FaultyTransport::new(socket, policy));
+println!("{:?}", client.info().await);
+# Ok(())
+# }
+```
+*/
+#[derive(Debug)]
+pub struct FaultyTransport<T> {
+    inner: T,
+    policy: FaultPolicy,
+    state: Mutex<State>,
+}
+
+/// Random number state and received datagrams held back
+#[derive(Debug)]
+struct State {
+    random: u64,
+    /// Datagrams to hand out with the time they arrive at, in the order they were received
+    arrived: VecDeque<(Instant, Vec<u8>)>,
+    /// Datagram waiting for the next one to arrive before it
+    reordered: Option<Vec<u8>>,
+}
+
+impl<T: AsyncDatagramTransport> FaultyTransport<T> {
+    /// Injects the faults of the policy into the datagrams of the transport
+    pub fn new(inner: T, policy: FaultPolicy) -> Self {
+        let state = State {
+            random: seed_random(policy.seed),
+            arrived: VecDeque::new(),
+            reordered: None,
+        };
+
+        FaultyTransport {
+            inner,
+            policy,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// The transport the faults are injected into
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// The transport the faults are injected into, received datagrams that are held back are lost
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Rolls the faults that apply to either direction, returns the datagrams that are left
+    fn mangle(&self, state: &mut State, datagram: &[u8]) -> Vec<Vec<u8>> {
+        if roll(state, self.policy.drop) {
+            return Vec::new();
+        }
+
+        let mut datagram = datagram.to_vec();
+        if roll(state, self.policy.truncate) && !datagram.is_empty() {
+            let len = next_random(&mut state.random) as usize % datagram.len();
+            datagram.truncate(len);
+        }
+        if roll(state, self.policy.corrupt) && !datagram.is_empty() {
+            let index = next_random(&mut state.random) as usize % datagram.len();
+            // Any byte but 0 changes the datagram
+            datagram[index] ^= (next_random(&mut state.random) % 255 + 1) as u8;
+        }
+
+        match roll(state, self.policy.duplicate) {
+            true => vec![datagram.clone(), datagram],
+            false => vec![datagram],
+        }
+    }
+
+    /// Queues a received datagram to be handed out, delayed or behind the next one if the policy says so
+    fn arrive(&self, datagram: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        for datagram in self.mangle(&mut state, datagram) {
+            let at = match roll(&mut state, self.policy.delay) {
+                true => now + self.policy.latency,
+                false => now,
+            };
+            match state.reordered.take() {
+                Some(reordered) => {
+                    state.arrived.push_back((at, datagram));
+                    state.arrived.push_back((at, reordered));
+                }
+                None if roll(&mut state, self.policy.reorder) => state.reordered = Some(datagram),
+                None => state.arrived.push_back((at, datagram)),
+            }
+        }
+    }
+
+    /// Takes the first datagram that has arrived by now, and otherwise the time the next one arrives at
+    fn take_arrived(&self) -> Result<Vec<u8>, Option<Instant>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        match state.arrived.iter().position(|(at, _)| *at <= now) {
+            Some(index) => Ok(state
+                .arrived
+                .remove(index)
+                .map(|(_, datagram)| datagram)
+                .unwrap_or_default()),
+            None => Err(state.arrived.iter().map(|(at, _)| *at).min()),
+        }
+    }
+}
+
+impl<T: AsyncDatagramTransport> AsyncDatagramTransport for FaultyTransport<T> {
+    fn send<'a>(&'a self, datagram: &'a [u8]) -> TransportFuture<'a, ()> {
+        let datagrams = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.mangle(&mut state, datagram)
+        };
+
+        Box::pin(async move {
+            for datagram in datagrams {
+                self.inner.send(&datagram).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn recv_until<'a>(
+        &'a self,
+        buffer: &'a mut [u8],
+        deadline: Instant,
+    ) -> TransportFuture<'a, usize> {
+        Box::pin(async move {
+            let mut received = vec![0; BUFFER_SIZE];
+            loop {
+                let next = match self.take_arrived() {
+                    Ok(datagram) => {
+                        let len = datagram.len().min(buffer.len());
+                        buffer[..len].copy_from_slice(&datagram[..len]);
+                        return Ok(len);
+                    }
+                    Err(next) => next,
+                };
+
+                // Wakes up for the next delayed datagram if it arrives before the deadline
+                let wake = next.map_or(deadline, |next| next.min(deadline));
+                match self.inner.recv_until(&mut received, wake).await {
+                    Ok(len) => self.arrive(&received[..len]),
+                    Err(e) if is_timeout(&e) => match next {
+                        Some(next) if next <= deadline => {}
+                        _ => return Err(io::ErrorKind::TimedOut.into()),
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> TransportFuture<'_, ()> {
+        self.inner.sleep_until(deadline)
+    }
+}
+
+// # Private helper functions
+/// True with the probability
+fn roll(state: &mut State, probability: f64) -> bool {
+    // The upper 53 bits fill the mantissa of a number in [0, 1)
+    let sample = (next_random(&mut state.random) >> 11) as f64 / (1u64 << 53) as f64;
+    sample < probability
+}
+
+// # Tests
+#[cfg(test)]
+fn faulty_client(
+    responses: Vec<Vec<Vec<u8>>>,
+    policy: FaultPolicy,
+) -> crate::async_client::AsyncA2SClient<FaultyTransport<crate::async_client::ScriptedTransport>> {
+    use crate::async_client::{AsyncA2SClient, ScriptedTransport};
+    use crate::client::QueryOptions;
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(100),
+        retries: 0,
+        ..QueryOptions::default()
+    };
+    let transport = FaultyTransport::new(ScriptedTransport::new(responses), policy);
+    AsyncA2SClient::with_options(transport, options)
+}
+
+#[test]
+fn survive_faults() {
+    use crate::info_source::ServerInfoBuilder;
+    use crate::message::A2sMessage;
+    use crate::packet::split_source_payload;
+    use futures::executor::block_on;
+
+    let info = ServerInfoBuilder::new().map("ctf_2fort").build();
+    let packets: Vec<Vec<u8>> = split_source_payload(4, &info.to_framed_bytes(), 20)
+        .unwrap()
+        .iter()
+        .map(|packet| packet.to_bytes())
+        .collect();
+
+    let duplicate = FaultPolicy {
+        duplicate: 1.0,
+        ..FaultPolicy::default()
+    };
+    let client = faulty_client(vec![vec![info.to_framed_bytes()]], duplicate);
+    assert_eq!(info, block_on(client.info()).unwrap());
+    assert_eq!(2, client.transport().inner().requests.lock().unwrap().len());
+
+    let reorder = FaultPolicy {
+        reorder: 1.0,
+        ..FaultPolicy::default()
+    };
+    let client = faulty_client(vec![packets], reorder);
+    assert_eq!(info, block_on(client.info()).unwrap());
+
+    let delay = FaultPolicy {
+        delay: 1.0,
+        latency: Duration::from_millis(20),
+        ..FaultPolicy::default()
+    };
+    let client = faulty_client(vec![vec![info.to_framed_bytes()]], delay);
+    assert_eq!(info, block_on(client.info()).unwrap());
+}
+
+#[test]
+fn report_faults() {
+    use crate::client::ClientError;
+    use crate::info_source::ServerInfoBuilder;
+    use crate::message::A2sMessage;
+    use futures::executor::block_on;
+
+    let info = ServerInfoBuilder::new().build().to_framed_bytes();
+
+    let drop = FaultPolicy {
+        drop: 1.0,
+        ..FaultPolicy::default()
+    };
+    let client = faulty_client(vec![vec![info.clone()]], drop);
+    assert!(matches!(block_on(client.info()), Err(ClientError::Io(e)) if is_timeout(&e)));
+    assert!(client
+        .transport()
+        .inner()
+        .requests
+        .lock()
+        .unwrap()
+        .is_empty());
+
+    // Arrives after the client gave up
+    let delay = FaultPolicy {
+        delay: 1.0,
+        latency: Duration::from_millis(300),
+        ..FaultPolicy::default()
+    };
+    let client = faulty_client(vec![vec![info.clone()]], delay);
+    assert!(matches!(block_on(client.info()), Err(ClientError::Io(e)) if is_timeout(&e)));
+
+    for seed in 0..8 {
+        let truncate = FaultPolicy {
+            truncate: 1.0,
+            seed,
+            ..FaultPolicy::default()
+        };
+        let client = faulty_client(vec![vec![info.clone()]], truncate);
+        assert!(!matches!(
+            block_on(client.info()),
+            Ok(_) | Err(ClientError::Io(_))
+        ));
+    }
+}
@@ -1,19 +1,20 @@
-use nom::{
-    combinator::all_consuming,
-    error::Error,
-    number::complete::{le_i32, le_u8},
-    Finish, IResult,
-};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
 
+use crate::conformance::{parse_with_warnings, player_count_violations, ParseWarning, Violation};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
 use crate::parser_util::{
-    c_string, environment, parse_bool, parse_null, server_type, Environment, ServerType,
+    all_consuming, environment, server_type, vac_status, with_remaining, write_bool,
+    write_c_string, ByteReader, Environment, ParseLimits, ReadError, ServerType, VacStatus,
 };
 
 // # Structs
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Data contained within an [A2S_INFO Response](https://developer.valvesoftware.com/wiki/Server_queries#Obsolete_GoldSource_Response) for Goldsource
 pub struct GoldSourceResponseInfo {
-    /// Server IP address IPV4:PORT
+    /// Server IP address and port, IPv6 addresses are enclosed in brackets `[IPV6]:PORT`
     pub address: String,
     /// Name of the Server
     pub name: String,
@@ -39,13 +40,14 @@ pub struct GoldSourceResponseInfo {
     pub mod_half_life: bool,
     /// If it is a mod, HalfLifeMod contains the mod data
     pub mod_fields: Option<HalfLifeMod>,
-    /// Is the server secured by VAC
-    pub vac: bool,
+    /// Whether the server is secured by VAC
+    pub vac: VacStatus,
     /// Number of bots currently connected to the server
     pub bots: u8,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Contains parsed Half-Life mod data
 pub struct HalfLifeMod {
     /// Website for the mod
@@ -63,6 +65,7 @@ pub struct HalfLifeMod {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Parsed Half-Life mod type
 pub enum ModType {
     /// Single and Multiplayer mod
@@ -83,7 +86,18 @@ impl From<u8> for ModType {
     }
 }
 
+impl From<&ModType> for u8 {
+    fn from(input: &ModType) -> Self {
+        match input {
+            ModType::SingleAndMultiplayer => 0,
+            ModType::MultiplayerOnly => 1,
+            ModType::Other(input) => *input,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Custom or standard Half-Life DLL for the mod
 pub enum ModDLL {
     /// Mod uses the base Half-Life DLL
@@ -104,96 +118,229 @@ impl From<u8> for ModDLL {
     }
 }
 
+impl From<&ModDLL> for u8 {
+    fn from(input: &ModDLL) -> Self {
+        match input {
+            ModDLL::HalfLife => 0,
+            ModDLL::Custom => 1,
+            ModDLL::Other(input) => *input,
+        }
+    }
+}
+
+impl GoldSourceResponseInfo {
+    /// True if the server reported being secured with VAC
+    pub fn vac_secured(&self) -> bool {
+        self.vac.is_secured()
+    }
+
+    /// Checks the response for more players than slots or more bots than players,
+    /// see [`SourceResponseInfo::validate`](crate::info_source::SourceResponseInfo::validate)
+    pub fn validate(&self) -> Vec<Violation> {
+        player_count_violations(self.players, self.max_players, self.bots)
+    }
+
+    /// True if the player count, bot count or map differ from a previous response from the same server,
+    /// see [`SourceResponseInfo::changed_since`](crate::info_source::SourceResponseInfo::changed_since)
+    pub fn changed_since(&self, previous: &GoldSourceResponseInfo) -> bool {
+        self.players != previous.players || self.bots != previous.bots || self.map != previous.map
+    }
+
+    /**
+    The [`address`](GoldSourceResponseInfo::address) field as a socket address, None if it isn't a valid IPv4 or IPv6 address and port.
+
+    # Examples
+    ```
+    use a2s_parse::info_goldsource::parse_goldsource_info;
+    # let mut payload = b"[2001:db8::1]:27015\0name\0map\0valve\0Half-Life\0".to_vec();
+    # payload.extend_from_slice(&[0x01, 0x20, 0x2F, 0x64, 0x6C, 0x00, 0x00, 0x01, 0x00]);
+    let info = parse_goldsource_info(&payload).unwrap();
+
+    assert!(info.socket_addr().unwrap().is_ipv6());
+    ```
+    */
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.address.parse().ok()
+    }
+}
+
 // # Exposed final parser
 // TODO: comment better
 // Returns the info or an error if the parsing failed or there was remaining data in the input
 // Remaining data in the input is not considered failure as old servers truncated data to one packet,
 
-pub fn parse_goldsource_info(input: &[u8]) -> Result<GoldSourceResponseInfo, Error<&[u8]>> {
-    match p_goldsource_info(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+/// Attempt to parse the provided payload into a Gold Source A2S_INFO response, an [`A2SError`] is returned on failure.
+pub fn parse_goldsource_info(input: &[u8]) -> Result<GoldSourceResponseInfo, A2SError> {
+    parse_goldsource_info_with_limits(input, &ParseLimits::default())
+}
+
+/// Same as [`parse_goldsource_info`] enforcing the given limits instead of the default ones
+pub fn parse_goldsource_info_with_limits(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<GoldSourceResponseInfo, A2SError> {
+    match all_consuming(input, |reader| goldsource_info(reader, limits)) {
+        Ok(info) => Ok(info),
+        Err(e) => Err(A2SError::from(e).with_field(&[GoldSourceResponseInfo::HEADER], input)),
     }
 }
 
-// # Private parsing helper functions
-// Make sure the parser ate all the data
-// TODO: move into main parsing function
-fn p_goldsource_info(input: &[u8]) -> IResult<&[u8], GoldSourceResponseInfo> {
-    all_consuming(goldsource_info)(input)
+/// Same as [`parse_goldsource_info`] for a complete datagram starting with the `-1` prefix and 'm' header
+pub fn parse_framed_goldsource_info(input: &[u8]) -> Result<GoldSourceResponseInfo, A2SError> {
+    GoldSourceResponseInfo::parse_framed(input)
+}
+
+/// Same as [`parse_goldsource_info`] but any data after the last field is returned instead of causing an error
+pub fn parse_goldsource_info_with_remaining(
+    input: &[u8],
+) -> Result<(GoldSourceResponseInfo, Vec<u8>), A2SError> {
+    match with_remaining(input, |reader| {
+        goldsource_info(reader, &ParseLimits::default())
+    }) {
+        Ok((info, remaining)) => Ok((info, remaining.to_vec())),
+        Err(e) => Err(A2SError::from(e).with_field(&[GoldSourceResponseInfo::HEADER], input)),
+    }
+}
+
+/// Same as [`parse_goldsource_info_with_limits`] but works around what it can instead of failing, see
+/// [`parse_lenient`](crate::conformance::parse_lenient). Strings over the limit are clamped
+pub fn parse_goldsource_info_lenient(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<(GoldSourceResponseInfo, Vec<ParseWarning>), A2SError> {
+    parse_with_warnings(GoldSourceResponseInfo::HEADER, input, |reader| {
+        goldsource_info(reader, limits)
+    })
 }
 
+// # Private parsing helper functions
 // Does the bulk of the parsing
-fn goldsource_info(input: &[u8]) -> IResult<&[u8], GoldSourceResponseInfo> {
-    let (input, address) = c_string(input)?;
-    let (input, name) = c_string(input)?;
-    let (input, map) = c_string(input)?;
-    let (input, folder) = c_string(input)?;
-    let (input, game) = c_string(input)?;
-    let (input, players) = le_u8(input)?;
-    let (input, max_players) = le_u8(input)?;
-    let (input, protocol) = le_u8(input)?;
-    let (input, server_type) = server_type(input)?;
-    let (input, environment) = environment(input)?;
-    let (input, visibility) = parse_bool(input)?;
-    let (input, mod_half_life) = parse_bool(input)?;
-    let (input, mod_fields) = mod_fields(input, mod_half_life)?;
-    let (input, vac) = parse_bool(input)?;
-    let (input, bots) = le_u8(input)?;
-
-    Ok((
-        input,
-        GoldSourceResponseInfo {
-            address,
-            name,
-            map,
-            folder,
-            game,
-            players,
-            max_players,
-            protocol,
-            server_type,
-            environment,
-            visibility,
-            mod_half_life,
-            mod_fields,
-            vac,
-            bots,
-        },
-    ))
+fn goldsource_info(
+    reader: &mut ByteReader,
+    limits: &ParseLimits,
+) -> Result<GoldSourceResponseInfo, ReadError> {
+    let max_len = limits.max_string_len;
+
+    let address = reader.limited_c_string(max_len)?;
+    let name = reader.limited_c_string(max_len)?;
+    let map = reader.limited_c_string(max_len)?;
+    let folder = reader.limited_c_string(max_len)?;
+    let game = reader.limited_c_string(max_len)?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let protocol = reader.u8()?;
+    let server_type = server_type(reader)?;
+    let environment = environment(reader)?;
+    let visibility = reader.bool()?;
+    let mod_half_life = reader.bool()?;
+    let mod_fields = mod_fields(reader, mod_half_life, limits)?;
+    let vac = vac_status(reader)?;
+    let bots = reader.u8()?;
+
+    Ok(GoldSourceResponseInfo {
+        address,
+        name,
+        map,
+        folder,
+        game,
+        players,
+        max_players,
+        protocol,
+        server_type,
+        environment,
+        visibility,
+        mod_half_life,
+        mod_fields,
+        vac,
+        bots,
+    })
 }
 
-fn mod_type(input: &[u8]) -> IResult<&[u8], ModType> {
-    le_u8(input).map(|(next, res)| (next, res.into()))
+pub(crate) fn mod_type(reader: &mut ByteReader) -> Result<ModType, ReadError> {
+    reader.u8().map(ModType::from)
 }
 
-fn dll(input: &[u8]) -> IResult<&[u8], ModDLL> {
-    le_u8(input).map(|(next, res)| (next, res.into()))
+pub(crate) fn dll(reader: &mut ByteReader) -> Result<ModDLL, ReadError> {
+    reader.u8().map(ModDLL::from)
 }
 
-fn mod_fields(input: &[u8], is_mod: bool) -> IResult<&[u8], Option<HalfLifeMod>> {
+fn mod_fields(
+    reader: &mut ByteReader,
+    is_mod: bool,
+    limits: &ParseLimits,
+) -> Result<Option<HalfLifeMod>, ReadError> {
     if is_mod {
-        let (input, link) = c_string(input)?;
-        let (input, download_link) = c_string(input)?;
-        let (input, _) = parse_null(input)?;
-        let (input, version) = le_i32(input)?;
-        let (input, size) = le_i32(input)?;
-        let (input, mod_type) = mod_type(input)?;
-        let (input, dll) = dll(input)?;
-
-        Ok((
-            input,
-            Some(HalfLifeMod {
-                link,
-                download_link,
-                version,
-                size,
-                mod_type,
-                dll,
-            }),
-        ))
+        let max_len = limits.max_string_len;
+
+        let link = reader.limited_c_string(max_len)?;
+        let download_link = reader.limited_c_string(max_len)?;
+        reader.null()?;
+        let version = reader.i32()?;
+        let size = reader.i32()?;
+        let mod_type = mod_type(reader)?;
+        let dll = dll(reader)?;
+
+        Ok(Some(HalfLifeMod {
+            link,
+            download_link,
+            version,
+            size,
+            mod_type,
+            dll,
+        }))
     } else {
-        Ok((input, None))
+        Ok(None)
+    }
+}
+
+// # Message implementation
+impl A2sMessage for GoldSourceResponseInfo {
+    const HEADER: u8 = 0x6D;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_goldsource_info(input)
+    }
+
+    fn parse_lenient(input: &[u8]) -> Result<(Self, Vec<ParseWarning>), A2SError> {
+        parse_goldsource_info_lenient(input, &ParseLimits::default())
+    }
+
+    /// The mod fields are written if present, `mod_half_life` is written as is
+    fn write(&self, output: &mut Vec<u8>) {
+        write_c_string(output, &self.address);
+        write_c_string(output, &self.name);
+        write_c_string(output, &self.map);
+        write_c_string(output, &self.folder);
+        write_c_string(output, &self.game);
+        output.push(self.players);
+        output.push(self.max_players);
+        output.push(self.protocol);
+        output.push(self.server_type.to_byte(true));
+        output.push(self.environment.to_byte(true));
+        write_bool(output, self.visibility);
+        write_bool(output, self.mod_half_life);
+
+        if let Some(mod_fields) = &self.mod_fields {
+            write_c_string(output, &mod_fields.link);
+            write_c_string(output, &mod_fields.download_link);
+            output.push(0x00);
+            output.extend_from_slice(&mod_fields.version.to_le_bytes());
+            output.extend_from_slice(&mod_fields.size.to_le_bytes());
+            output.push((&mod_fields.mod_type).into());
+            output.push((&mod_fields.dll).into());
+        }
+
+        output.push(self.vac.into());
+        output.push(self.bots);
+    }
+}
+
+/// Parses the payload following the header byte, same as [`parse_goldsource_info`]
+impl<'a> TryFrom<&'a [u8]> for GoldSourceResponseInfo {
+    type Error = A2SError;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_goldsource_info(input)
     }
 }
 
@@ -239,9 +386,27 @@ fn info_cs() {
                 mod_type: ModType::SingleAndMultiplayer,
                 dll: ModDLL::Custom,
             }),
-            vac: true,
+            vac: VacStatus::Secured,
             bots: 0,
         },
         response
     );
 }
+
+#[test]
+fn info_round_trip() {
+    // Half-Life mod on a dedicated linux server
+    let payload: [u8; 41] = [
+        0x31, 0x2E, 0x32, 0x2E, 0x33, 0x2E, 0x34, 0x3A, 0x35, 0x00, 0x61, 0x00, 0x62, 0x00, 0x63,
+        0x00, 0x64, 0x00, 0x01, 0x02, 0x2F, 0x44, 0x4C, 0x00, 0x01, 0x65, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00,
+    ];
+
+    let response = GoldSourceResponseInfo::parse(&payload).unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output);
+
+    assert_eq!("1.2.3.4:5", response.address);
+    assert_eq!(ModDLL::Custom, response.mod_fields.as_ref().unwrap().dll);
+    assert_eq!(&payload[..], &output[..]);
+}
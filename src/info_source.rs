@@ -1,16 +1,18 @@
+use crate::conformance::{parse_with_warnings, player_count_violations, ParseWarning, Violation};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
 use crate::parser_util::{
-    c_string, environment, opt_le_u8, parse_bool, server_type, Environment, ServerType,
+    all_consuming, environment, fnv1a, server_type, vac_status, with_remaining, write_bool,
+    write_c_string, ByteReader, Environment, ParseLimits, ReadError, ServerType, VacStatus,
 };
 
-use nom::{
-    combinator::all_consuming,
-    error::Error,
-    number::complete::{le_i16, le_u64, le_u8},
-    Finish, IResult,
-};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
 
 // # Structs
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Data contained within an [A2S_INFO Response](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format) for Source
 pub struct SourceResponseInfo {
     /// Procool version used by the server
     pub protocol: u8,
@@ -36,8 +38,8 @@ pub struct SourceResponseInfo {
     pub environment: Environment,
     /// Is the server private
     pub visibility: bool,
-    /// Is the server secured with VAC
-    pub vac: bool,
+    /// Whether the server is secured with VAC
+    pub vac: VacStatus,
     /// Optional data transmitted by [The Ship](https://developer.valvesoftware.com/wiki/The_Ship)
     pub the_ship: Option<TheShipFields>,
     /// Version of the game installed on the server
@@ -49,12 +51,13 @@ pub struct SourceResponseInfo {
     /// if `EDF & 0x10` then servers steam ID is transmitted
     /// if `EDF & 0x40` then the spectator port number and name of the spectator server for SourceTV are contained
     /// if `EDF & 0x20` then tags that describe the game are transmitted
-    /// if `EDF & 0x01` then the full game ID and untruncated App ID are contained. 
+    /// if `EDF & 0x01` then the full game ID and untruncated App ID are contained.
     pub extra_data_fields: ExtraDataFields,
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Possible gamemodes for The Ship
 pub enum TheShipGameMode {
     /// 0 -> Hunt Gamemode
@@ -86,7 +89,22 @@ impl From<u8> for TheShipGameMode {
         }
     }
 }
+
+impl From<&TheShipGameMode> for u8 {
+    fn from(input: &TheShipGameMode) -> Self {
+        match input {
+            TheShipGameMode::Hunt => 0,
+            TheShipGameMode::Elimination => 1,
+            TheShipGameMode::Duel => 2,
+            TheShipGameMode::Deathmatch => 3,
+            TheShipGameMode::VIP_Team => 4,
+            TheShipGameMode::Team_Elimination => 5,
+            TheShipGameMode::Other(input) => *input,
+        }
+    }
+}
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Optionally transmitted data about the configuration of The Ship (only used by one game)
 pub struct TheShipFields {
     /// Gamemode
@@ -97,6 +115,7 @@ pub struct TheShipFields {
     pub duration: u8,
 }
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Optional Extra Data Fields
 /// if `EDF & 0x80` then the servers port is also transmitted
 /// if `EDF & 0x10` then servers steam ID is transmitted
@@ -118,177 +137,704 @@ pub struct ExtraDataFields {
     pub game_id: Option<u64>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Summary of how a server is protected, for filtering in server browsers
+pub struct SecurityInfo {
+    /// Whether the server is secured with VAC
+    pub vac: VacStatus,
+    /// True if a password is required to join
+    pub password_protected: bool,
+    /// Tags in the keywords naming an anti-cheat other than VAC, see [`ANTI_CHEAT_KEYWORDS`]
+    pub anti_cheat: Vec<String>,
+}
+
+/// Keywords recognized as naming an anti-cheat in [`SecurityInfo`], compared case insensitively
+pub const ANTI_CHEAT_KEYWORDS: [&str; 3] = ["battleye", "eac", "easyanticheat"];
+
+impl SourceResponseInfo {
+    /**
+    Link that opens Steam and connects to the server, `steam://connect/ip:port`.
+
+    The address is the one the server was queried on, if the server sent its game port in the extra data fields
+    that port is used instead as it may differ from the query port.
+
+    # Examples
+    ```
+    # use a2s_parse::info_source::parse_source_info;
+    # let payload = [
+    #     0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0xF0, 0x00, 0x01, 0x02, 0x00, 0x64,
+    #     0x6C, 0x00, 0x01, 0x31, 0x00, 0x80, 0x88, 0x69,
+    # ];
+    // Server queried on 27015 that reports its game port as 27016
+    let info = parse_source_info(&payload).unwrap();
+
+    assert_eq!(
+        "steam://connect/192.0.2.1:27016",
+        info.connect_url("192.0.2.1:27015".parse().unwrap())
+    );
+    ```
+    */
+    pub fn connect_url(&self, address: SocketAddr) -> String {
+        let mut address = address;
+        if let Some(port) = self.extra_data_fields.port {
+            // The port is sent as a signed value but is really unsigned
+            address.set_port(port as u16);
+        }
+
+        format!("steam://connect/{}", address)
+    }
+
+    /// Same as [`connect_url`](SourceResponseInfo::connect_url) with the password appended, `steam://connect/ip:port/password`.
+    /// Useful with a placeholder for private servers whose password the user has to fill in.
+    pub fn connect_url_with_password(&self, address: SocketAddr, password: &str) -> String {
+        format!("{}/{}", self.connect_url(address), password)
+    }
+
+    /**
+    Serializes the response into the A2S_INFO payload following the 'I' header, the counterpart to [`parse_source_info`].
+
+    Extra data fields are written when their bit is set in [`extra_data_flag`](SourceResponseInfo::extra_data_flag),
+    a field whose bit is set but holds `None` is written as 0 or an empty string. Parsing the output gives back an equal response.
+
+    # Examples
+    ```
+    use a2s_parse::info_source::parse_source_info;
+
+    let payload = [
+        0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0xF0, 0x00, 0x01, 0x02, 0x00, 0x64,
+        0x6C, 0x00, 0x01, 0x31, 0x00, 0x80, 0x88, 0x69,
+    ];
+
+    let info = parse_source_info(&payload).unwrap();
+
+    assert_eq!(&payload[..], &info.to_bytes()[..]);
+    ```
+    */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write(&mut output);
+
+        output
+    }
+
+    /// True if the server reported being secured with VAC
+    pub fn vac_secured(&self) -> bool {
+        self.vac.is_secured()
+    }
+
+    /// Combines VAC, visibility and anti-cheat tags in the keywords into a [`SecurityInfo`]
+    pub fn security(&self) -> SecurityInfo {
+        let anti_cheat = self
+            .extra_data_fields
+            .keywords
+            .iter()
+            .flat_map(|keywords| keywords.split(','))
+            .map(str::trim)
+            .filter(|tag| {
+                ANTI_CHEAT_KEYWORDS
+                    .iter()
+                    .any(|known| tag.eq_ignore_ascii_case(known))
+            })
+            .map(str::to_string)
+            .collect();
+
+        SecurityInfo {
+            vac: self.vac,
+            password_protected: self.visibility,
+            anti_cheat,
+        }
+    }
+
+    /**
+    Checks the response for values that contradict each other: more players than slots, more bots than players,
+    and extra data fields present or missing against their bit in the [`extra_data_flag`](SourceResponseInfo::extra_data_flag).
+    Parsing never produces the last kind, responses built by hand or deserialized from elsewhere may.
+
+    Useful for telling spoofed or broken servers apart, an empty Vec means the response is consistent.
+
+    # Examples
+    ```
+    use a2s_parse::conformance::Violation;
+    use a2s_parse::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().players(3).max_players(2).build();
+
+    assert_eq!(
+        vec![Violation::PlayersOverMax { players: 3, max_players: 2 }],
+        info.validate()
+    );
+    ```
+    */
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = player_count_violations(self.players, self.max_players, self.bots);
+        let fields = &self.extra_data_fields;
+
+        for (field, present, bit) in [
+            ("port", fields.port.is_some(), 0x80),
+            ("steam_id", fields.steam_id.is_some(), 0x10),
+            ("source_tv_port", fields.source_tv_port.is_some(), 0x40),
+            ("source_tv_name", fields.source_tv_name.is_some(), 0x40),
+            ("keywords", fields.keywords.is_some(), 0x20),
+            ("game_id", fields.game_id.is_some(), 0x01),
+        ]
+        .iter()
+        {
+            let flag_set = self.extra_data_flag & bit != 0;
+            if *present != flag_set {
+                violations.push(Violation::ExtraDataFlag {
+                    field: field.to_string(),
+                    flag_set,
+                });
+            }
+        }
+
+        violations
+    }
+
+    /**
+    True if the player count, bot count, map or version differ from a previous response from the same server.
+
+    Querying A2S_INFO is cheap, pollers can use this to only re-fetch A2S_PLAYER and A2S_RULES when something changed.
+    Players swapping out without the count changing are not detected.
+    */
+    pub fn changed_since(&self, previous: &SourceResponseInfo) -> bool {
+        self.players != previous.players
+            || self.bots != previous.bots
+            || self.map != previous.map
+            || self.version != previous.version
+    }
+
+    /**
+    Deterministic hash identifying the server independent of the address it was queried on.
+
+    Only the name, folder, version, Steam ID and game ID are hashed, values like the map or player count change
+    between polls. Crawlers can use it to detect the same server announced on several IPs or ports.
+    The hash is stable across runs and versions of this crate but is not cryptographic.
+    */
+    pub fn fingerprint(&self) -> u64 {
+        let steam_id = self.extra_data_fields.steam_id.map(u64::to_le_bytes);
+        let game_id = self.extra_data_fields.game_id.map(u64::to_le_bytes);
+
+        // Strings are hashed null terminated and ids with a presence byte so fields can't run into each other
+        fnv1a(&[
+            self.name.as_bytes(),
+            &[0],
+            self.folder.as_bytes(),
+            &[0],
+            self.version.as_bytes(),
+            &[0],
+            &[steam_id.is_some() as u8],
+            &steam_id.unwrap_or_default(),
+            &[game_id.is_some() as u8],
+            &game_id.unwrap_or_default(),
+        ])
+    }
+}
+
+/**
+Builder for [`SourceResponseInfo`] that sets the extra data flag from the optional fields that were given.
+
+Fields that aren't set default to 0, empty strings, a dedicated Linux server and VAC insecure.
+The protocol defaults to 17, the version Source servers currently report.
+
+# Examples
+```
+use a2s_parse::info_source::ServerInfoBuilder;
+
+let info = ServerInfoBuilder::new()
+    .name("My Server")
+    .map("de_dust2")
+    .players(12)
+    .max_players(24)
+    .port(27015)
+    .keywords("casual,competitive")
+    .build();
+
+assert_eq!(0xA0, info.extra_data_flag);
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerInfoBuilder {
+    info: SourceResponseInfo,
+}
+
+impl ServerInfoBuilder {
+    /// Creates a builder with every field at its default
+    pub fn new() -> Self {
+        ServerInfoBuilder {
+            info: SourceResponseInfo {
+                protocol: 17,
+                name: String::new(),
+                map: String::new(),
+                folder: String::new(),
+                game: String::new(),
+                app_id: 0,
+                players: 0,
+                max_players: 0,
+                bots: 0,
+                server_type: ServerType::Dedicated,
+                environment: Environment::Linux,
+                visibility: false,
+                vac: VacStatus::Insecure,
+                the_ship: None,
+                version: String::new(),
+                extra_data_flag: 0,
+                extra_data_fields: ExtraDataFields {
+                    port: None,
+                    steam_id: None,
+                    source_tv_port: None,
+                    source_tv_name: None,
+                    keywords: None,
+                    game_id: None,
+                },
+            },
+        }
+    }
+
+    /// Name of the server
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.info.name = name.into();
+        self
+    }
+
+    /// Map the server has loaded
+    pub fn map(mut self, map: impl Into<String>) -> Self {
+        self.info.map = map.into();
+        self
+    }
+
+    /// Name of the folder containing the game files
+    pub fn folder(mut self, folder: impl Into<String>) -> Self {
+        self.info.folder = folder.into();
+        self
+    }
+
+    /// Full name of the game
+    pub fn game(mut self, game: impl Into<String>) -> Self {
+        self.info.game = game.into();
+        self
+    }
+
+    /// Version of the game installed on the server
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.info.version = version.into();
+        self
+    }
+
+    /// Protocol version used by the server
+    pub fn protocol(mut self, protocol: u8) -> Self {
+        self.info.protocol = protocol;
+        self
+    }
+
+    /// Number of players on the server
+    pub fn players(mut self, players: u8) -> Self {
+        self.info.players = players;
+        self
+    }
+
+    /// Maximum number of players the server can hold
+    pub fn max_players(mut self, max_players: u8) -> Self {
+        self.info.max_players = max_players;
+        self
+    }
+
+    /// Number of bots on the server
+    pub fn bots(mut self, bots: u8) -> Self {
+        self.info.bots = bots;
+        self
+    }
+
+    /// Steam Application ID of the game
+    pub fn app_id(mut self, app_id: i16) -> Self {
+        self.info.app_id = app_id;
+        self
+    }
+
+    /// Whether a password is required to join
+    pub fn visibility(mut self, visibility: bool) -> Self {
+        self.info.visibility = visibility;
+        self
+    }
+
+    /// Type of server
+    pub fn server_type(mut self, server_type: ServerType) -> Self {
+        self.info.server_type = server_type;
+        self
+    }
+
+    /// Operating system the server is running on
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.info.environment = environment;
+        self
+    }
+
+    /// Whether the server is secured with VAC
+    pub fn vac(mut self, vac: VacStatus) -> Self {
+        self.info.vac = vac;
+        self
+    }
+
+    /// Fields sent by The Ship, the app id also has to be one of The Ship's for parsers to read them
+    pub fn the_ship(mut self, the_ship: TheShipFields) -> Self {
+        self.info.the_ship = Some(the_ship);
+        self
+    }
+
+    /// Game port of the server, sets `EDF & 0x80`
+    pub fn port(mut self, port: u16) -> Self {
+        self.info.extra_data_fields.port = Some(port as i16);
+        self
+    }
+
+    /// Steam ID of the server, sets `EDF & 0x10`
+    pub fn steam_id(mut self, steam_id: u64) -> Self {
+        self.info.extra_data_fields.steam_id = Some(steam_id);
+        self
+    }
+
+    /// Port and name of the SourceTV relay, sets `EDF & 0x40`
+    pub fn source_tv(mut self, port: u16, name: impl Into<String>) -> Self {
+        self.info.extra_data_fields.source_tv_port = Some(port as i16);
+        self.info.extra_data_fields.source_tv_name = Some(name.into());
+        self
+    }
+
+    /// Tags describing the game, sets `EDF & 0x20`
+    pub fn keywords(mut self, keywords: impl Into<String>) -> Self {
+        self.info.extra_data_fields.keywords = Some(keywords.into());
+        self
+    }
+
+    /// 64 bit game ID, sets `EDF & 0x01`
+    pub fn game_id(mut self, game_id: u64) -> Self {
+        self.info.extra_data_fields.game_id = Some(game_id);
+        self
+    }
+
+    /// Creates the response with the extra data flag matching the optional fields that were set
+    pub fn build(self) -> SourceResponseInfo {
+        let mut info = self.info;
+        let fields = &info.extra_data_fields;
+
+        info.extra_data_flag = [
+            (fields.port.is_some(), 0x80),
+            (fields.steam_id.is_some(), 0x10),
+            (fields.source_tv_port.is_some(), 0x40),
+            (fields.keywords.is_some(), 0x20),
+            (fields.game_id.is_some(), 0x01),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flag, (_, bit)| flag | bit);
+
+        info
+    }
+}
+
+impl Default for ServerInfoBuilder {
+    fn default() -> Self {
+        ServerInfoBuilder::new()
+    }
+}
+
 // # Exposed final parser
 // Makes sure that all of the input data was consumed, if not to much data was fed or something
 // TODO: comment better
 // Returns the info or an error if the parsing failed or there was remaining data in the input
 // Remaining data in the input is not considered failure as old servers truncated data to one packet,
 
-pub fn parse_source_info(input: &[u8]) -> Result<SourceResponseInfo, Error<&[u8]>> {
-    match p_source_info(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+/// Attempt to parse the provided payload into a Source A2S_INFO response, an [`A2SError`] is returned on failure.
+pub fn parse_source_info(input: &[u8]) -> Result<SourceResponseInfo, A2SError> {
+    parse_source_info_with_limits(input, &ParseLimits::default())
+}
+
+/// Same as [`parse_source_info`] enforcing the given limits instead of the default ones
+pub fn parse_source_info_with_limits(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<SourceResponseInfo, A2SError> {
+    match all_consuming(input, |reader| source_info(reader, limits)) {
+        Ok(info) => Ok(info),
+        Err(e) => Err(A2SError::from(e).with_field(&[SourceResponseInfo::HEADER], input)),
     }
 }
 
-// # Private parsing helper functions
-// Makes sure that all of the data was consumed by the previous parser
-fn p_source_info(input: &[u8]) -> IResult<&[u8], SourceResponseInfo> {
-    all_consuming(source_info)(input)
+/// Same as [`parse_source_info`] for a complete datagram starting with the `-1` prefix and 'I' header
+pub fn parse_framed_source_info(input: &[u8]) -> Result<SourceResponseInfo, A2SError> {
+    SourceResponseInfo::parse_framed(input)
+}
+
+/// Same as [`parse_source_info`] but any data after the last field is returned instead of causing an error,
+/// for inspecting servers that append data the wiki doesn't describe
+pub fn parse_source_info_with_remaining(
+    input: &[u8],
+) -> Result<(SourceResponseInfo, Vec<u8>), A2SError> {
+    match with_remaining(input, |reader| source_info(reader, &ParseLimits::default())) {
+        Ok((info, remaining)) => Ok((info, remaining.to_vec())),
+        Err(e) => Err(A2SError::from(e).with_field(&[SourceResponseInfo::HEADER], input)),
+    }
+}
+
+/// Same as [`parse_source_info_with_limits`] but works around what it can instead of failing, see
+/// [`parse_lenient`](crate::conformance::parse_lenient). Counts and strings over the limits are clamped and extra data fields that are cut off are left out
+pub fn parse_source_info_lenient(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<(SourceResponseInfo, Vec<ParseWarning>), A2SError> {
+    parse_with_warnings(SourceResponseInfo::HEADER, input, |reader| {
+        source_info(reader, limits)
+    })
 }
+
+// # Private parsing helper functions
 // Does the bulk of the parsing
-fn source_info(input: &[u8]) -> IResult<&[u8], SourceResponseInfo> {
-    let (input, protocol) = le_u8(input)?;
-    let (input, name) = c_string(input)?;
-    let (input, map) = c_string(input)?;
-    let (input, folder) = c_string(input)?;
-    let (input, game) = c_string(input)?;
-    let (input, app_id) = le_i16(input)?;
-    let (input, players) = le_u8(input)?;
-    let (input, max_players) = le_u8(input)?;
-    let (input, bots) = le_u8(input)?;
-    let (input, server_type) = server_type(input)?;
-    let (input, environment) = environment(input)?;
-    let (input, visibility) = parse_bool(input)?;
-    let (input, vac) = parse_bool(input)?;
-    let (input, the_ship) = the_ship(input, app_id == 2400)?;
+fn source_info(
+    reader: &mut ByteReader,
+    limits: &ParseLimits,
+) -> Result<SourceResponseInfo, ReadError> {
+    let max_len = limits.max_string_len;
+
+    let protocol = reader.u8()?;
+    let name = reader.limited_c_string(max_len)?;
+    let map = reader.limited_c_string(max_len)?;
+    let folder = reader.limited_c_string(max_len)?;
+    let game = reader.limited_c_string(max_len)?;
+    let app_id = reader.i16()?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let bots = reader.u8()?;
+    let server_type = server_type(reader)?;
+    let environment = environment(reader)?;
+    let visibility = reader.bool()?;
+    let vac = vac_status(reader)?;
+    let the_ship = the_ship(reader, app_id == 2400)?;
 
     // The version is either the last data in the input, or there is the extra data flag
-    let (input, version) = c_string(input)?;
+    let version = reader.limited_c_string(max_len)?;
 
     // Doesn't always exist, need to make optional
-    let (input, extra_data_flag) = opt_le_u8(input)?;
+    let extra_data_flag = reader.opt_u8();
     // Unwrap, 0 means no data flags
     let extra_data_flag: u8 = extra_data_flag.unwrap_or(0);
 
     // TODO: This is not optimal, should skip trying to parse all of the values if the flag is 0
-    let (input, extra_data_fields) = extra_data_fields(input, extra_data_flag)?;
+    let extra_data_fields = extra_data_fields(reader, extra_data_flag, limits)?;
 
-    Ok((
-        input,
-        SourceResponseInfo {
-            protocol,
-            name,
-            map,
-            folder,
-            game,
-            app_id,
-            players,
-            max_players,
-            bots,
-            server_type,
-            environment,
-            visibility,
-            vac,
-            the_ship,
-            version,
-            extra_data_flag,
-            extra_data_fields,
-        },
-    ))
+    Ok(SourceResponseInfo {
+        protocol,
+        name,
+        map,
+        folder,
+        game,
+        app_id,
+        players,
+        max_players,
+        bots,
+        server_type,
+        environment,
+        visibility,
+        vac,
+        the_ship,
+        version,
+        extra_data_flag,
+        extra_data_fields,
+    })
 }
 
-fn the_ship(input: &[u8], is_ship: bool) -> IResult<&[u8], Option<TheShipFields>> {
+fn the_ship(reader: &mut ByteReader, is_ship: bool) -> Result<Option<TheShipFields>, ReadError> {
     if is_ship {
-        let (input, mode) = le_u8(input).map(|(next, res)| (next, res.into()))?;
-        let (input, witnesses) = le_u8(input)?;
-        let (input, duration) = le_u8(input)?;
-
-        Ok((
-            input,
-            Some(TheShipFields {
-                mode,
-                witnesses,
-                duration,
-            }),
-        ))
+        let mode = reader.u8()?.into();
+        let witnesses = reader.u8()?;
+        let duration = reader.u8()?;
+
+        Ok(Some(TheShipFields {
+            mode,
+            witnesses,
+            duration,
+        }))
     } else {
-        Ok((input, None))
-    }
-}
-
-fn extra_data_fields(input: &[u8], extra_data_flag: u8) -> IResult<&[u8], ExtraDataFields> {
-    let (input, port) = port(input, extra_data_flag)?;
-    let (input, steam_id) = steam_id(input, extra_data_flag)?;
-    let (input, source_tv_port) = source_tv_port(input, extra_data_flag)?;
-    let (input, source_tv_name) = source_tv_name(input, extra_data_flag)?;
-    let (input, keywords) = keywords(input, extra_data_flag)?;
-    let (input, game_id) = game_id(input, extra_data_flag)?;
-
-    Ok((
-        input,
-        ExtraDataFields {
-            port,
-            steam_id,
-            source_tv_port,
-            source_tv_name,
-            keywords,
-            game_id,
-        },
-    ))
+        Ok(None)
+    }
 }
 
-fn port(input: &[u8], flag: u8) -> IResult<&[u8], Option<i16>> {
-    if flag & 0x80 != 0 {
-        let (input, port) = le_i16(input)?;
+fn extra_data_fields(
+    reader: &mut ByteReader,
+    extra_data_flag: u8,
+    limits: &ParseLimits,
+) -> Result<ExtraDataFields, ReadError> {
+    let mut fields = ExtraDataFields {
+        port: None,
+        steam_id: None,
+        source_tv_port: None,
+        source_tv_name: None,
+        keywords: None,
+        game_id: None,
+    };
+    // Lenient parses keep the fields read before one that is cut off
+    let read = read_extra_data_fields(reader, extra_data_flag, limits, &mut fields);
+    reader.optional(read)?;
+
+    Ok(fields)
+}
+
+fn read_extra_data_fields(
+    reader: &mut ByteReader,
+    extra_data_flag: u8,
+    limits: &ParseLimits,
+    fields: &mut ExtraDataFields,
+) -> Result<(), ReadError> {
+    let max_len = limits.max_string_len;
+
+    fields.port = port(reader, extra_data_flag)?;
+    fields.steam_id = steam_id(reader, extra_data_flag)?;
+    fields.source_tv_port = source_tv_port(reader, extra_data_flag)?;
+    fields.source_tv_name = source_tv_name(reader, extra_data_flag, max_len)?;
+    fields.keywords = keywords(reader, extra_data_flag, max_len)?;
+    fields.game_id = game_id(reader, extra_data_flag)?;
 
-        Ok((input, Some(port)))
+    Ok(())
+}
+
+pub(crate) fn port(reader: &mut ByteReader, flag: u8) -> Result<Option<i16>, ReadError> {
+    if flag & 0x80 != 0 {
+        reader.i16().map(Some)
     } else {
-        Ok((input, None))
+        Ok(None)
     }
 }
 
-fn steam_id(input: &[u8], flag: u8) -> IResult<&[u8], Option<u64>> {
+pub(crate) fn steam_id(reader: &mut ByteReader, flag: u8) -> Result<Option<u64>, ReadError> {
     if flag & 0x10 != 0 {
-        let (input, steam_id) = le_u64(input)?;
-
-        Ok((input, Some(steam_id)))
+        reader.u64().map(Some)
     } else {
-        Ok((input, None))
+        Ok(None)
     }
 }
 
-fn source_tv_port(input: &[u8], flag: u8) -> IResult<&[u8], Option<i16>> {
+pub(crate) fn source_tv_port(reader: &mut ByteReader, flag: u8) -> Result<Option<i16>, ReadError> {
     if flag & 0x40 != 0 {
-        let (input, port) = le_i16(input)?;
-
-        Ok((input, Some(port)))
+        reader.i16().map(Some)
     } else {
-        Ok((input, None))
+        Ok(None)
     }
 }
 
-fn source_tv_name(input: &[u8], flag: u8) -> IResult<&[u8], Option<String>> {
+pub(crate) fn source_tv_name(
+    reader: &mut ByteReader,
+    flag: u8,
+    max_len: usize,
+) -> Result<Option<String>, ReadError> {
     if flag & 0x40 != 0 {
-        let (input, name) = c_string(input)?;
-
-        Ok((input, Some(name)))
+        reader.limited_c_string(max_len).map(Some)
     } else {
-        Ok((input, None))
+        Ok(None)
     }
 }
 
-fn keywords(input: &[u8], flag: u8) -> IResult<&[u8], Option<String>> {
+pub(crate) fn keywords(
+    reader: &mut ByteReader,
+    flag: u8,
+    max_len: usize,
+) -> Result<Option<String>, ReadError> {
     if flag & 0x20 != 0 {
-        let (input, keywords) = c_string(input)?;
+        reader.limited_c_string(max_len).map(Some)
+    } else {
+        Ok(None)
+    }
+}
 
-        Ok((input, Some(keywords)))
+pub(crate) fn game_id(reader: &mut ByteReader, flag: u8) -> Result<Option<u64>, ReadError> {
+    if flag & 0x01 != 0 {
+        reader.u64().map(Some)
     } else {
-        Ok((input, None))
+        Ok(None)
     }
 }
 
-fn game_id(input: &[u8], flag: u8) -> IResult<&[u8], Option<u64>> {
-    if flag & 0x20 != 0 {
-        let (input, game_id) = le_u64(input)?;
+// # Message implementation
+impl A2sMessage for SourceResponseInfo {
+    const HEADER: u8 = 0x49;
 
-        Ok((input, Some(game_id)))
-    } else {
-        Ok((input, None))
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_source_info(input)
+    }
+
+    fn parse_lenient(input: &[u8]) -> Result<(Self, Vec<ParseWarning>), A2SError> {
+        parse_source_info_lenient(input, &ParseLimits::default())
+    }
+
+    /// Optional extra data fields are written when their bit is set in the extra data flag, missing values are written as 0 or ""
+    fn write(&self, output: &mut Vec<u8>) {
+        output.push(self.protocol);
+        write_c_string(output, &self.name);
+        write_c_string(output, &self.map);
+        write_c_string(output, &self.folder);
+        write_c_string(output, &self.game);
+        output.extend_from_slice(&self.app_id.to_le_bytes());
+        output.push(self.players);
+        output.push(self.max_players);
+        output.push(self.bots);
+        output.push(self.server_type.to_byte(false));
+        output.push(self.environment.to_byte(false));
+        write_bool(output, self.visibility);
+        output.push(self.vac.into());
+
+        if let Some(ship) = &self.the_ship {
+            output.push((&ship.mode).into());
+            output.push(ship.witnesses);
+            output.push(ship.duration);
+        }
+
+        write_c_string(output, &self.version);
+
+        // The flag is optional, no flag is the same as a flag of 0
+        let flag = self.extra_data_flag;
+        if flag == 0 {
+            return;
+        }
+        output.push(flag);
+
+        let fields = &self.extra_data_fields;
+        if flag & 0x80 != 0 {
+            output.extend_from_slice(&fields.port.unwrap_or(0).to_le_bytes());
+        }
+        if flag & 0x10 != 0 {
+            output.extend_from_slice(&fields.steam_id.unwrap_or(0).to_le_bytes());
+        }
+        if flag & 0x40 != 0 {
+            output.extend_from_slice(&fields.source_tv_port.unwrap_or(0).to_le_bytes());
+            write_c_string(output, fields.source_tv_name.as_deref().unwrap_or(""));
+        }
+        if flag & 0x20 != 0 {
+            write_c_string(output, fields.keywords.as_deref().unwrap_or(""));
+        }
+        if flag & 0x01 != 0 {
+            output.extend_from_slice(&fields.game_id.unwrap_or(0).to_le_bytes());
+        }
+    }
+}
+
+/// Parses the payload following the header byte, same as [`parse_source_info`]
+impl<'a> TryFrom<&'a [u8]> for SourceResponseInfo {
+    type Error = A2SError;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_source_info(input)
     }
 }
 
 // # Tests
+/// Info payload of a dedicated linux server without extra data fields, named "a" on map "b"
+#[cfg(test)]
+pub(crate) fn info_payload() -> [u8; 20] {
+    [
+        0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0xF0, 0x00, 0x01, 0x02, 0x00, 0x64,
+        0x6C, 0x00, 0x01, 0x31, 0x00,
+    ]
+}
+
 #[test]
 fn info_css() {
     // Packet from souce wiki
@@ -319,7 +865,7 @@ fn info_css() {
             server_type: ServerType::Dedicated,
             environment: Environment::Linux,
             visibility: false,
-            vac: false,
+            vac: VacStatus::Insecure,
             the_ship: None,
             version: "1.0.0.22".to_string(),
             extra_data_flag: 0,
@@ -362,7 +908,7 @@ fn info_the_ship() {
             server_type: ServerType::NonDedicated,
             environment: Environment::Windows,
             visibility: false,
-            vac: false,
+            vac: VacStatus::Insecure,
             the_ship: Some(TheShipFields {
                 mode: TheShipGameMode::Elimination,
                 witnesses: 3,
@@ -382,3 +928,144 @@ fn info_the_ship() {
         response
     );
 }
+
+#[test]
+fn info_round_trip() {
+    // Dedicated linux server with every extra data field
+    let payload: [u8; 48] = [
+        0x11, 0x61, 0x00, 0x62, 0x00, 0x63, 0x00, 0x64, 0x00, 0xF0, 0x00, 0x01, 0x02, 0x00, 0x64,
+        0x6C, 0x00, 0x01, 0x31, 0x00, 0xF1, 0x87, 0x69, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x88, 0x69, 0x74, 0x76, 0x00, 0x6B, 0x65, 0x79, 0x00, 0xF0, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00,
+    ];
+
+    let response = SourceResponseInfo::parse(&payload).unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output);
+
+    assert_eq!(Some(27015), response.extra_data_fields.port);
+    assert_eq!(Some("key".to_string()), response.extra_data_fields.keywords);
+    assert_eq!(Some(0xF0), response.extra_data_fields.game_id);
+    assert_eq!(&payload[..], &output[..]);
+    assert_eq!(output, response.to_bytes());
+}
+
+#[test]
+fn connect_url_without_port() {
+    // Server that doesn't send any extra data fields
+    let payload = info_payload();
+
+    let info = parse_source_info(&payload).unwrap();
+
+    assert_eq!(
+        "steam://connect/[2001:db8::1]:27015/PASSWORD",
+        info.connect_url_with_password("[2001:db8::1]:27015".parse().unwrap(), "PASSWORD")
+    );
+}
+
+#[test]
+fn fingerprint_ignores_volatile_fields() {
+    let payload = info_payload();
+
+    let info = parse_source_info(&payload).unwrap();
+    let mut other = info.clone();
+    other.map = "other".to_string();
+    other.players = 2;
+    let mut renamed = info.clone();
+    renamed.name = "renamed".to_string();
+
+    assert_eq!(info.fingerprint(), other.fingerprint());
+    assert_ne!(info.fingerprint(), renamed.fingerprint());
+}
+
+#[test]
+fn changed_since_previous_poll() {
+    let payload = info_payload();
+
+    let previous = parse_source_info(&payload).unwrap();
+    let mut current = previous.clone();
+    current.max_players = 32;
+    assert!(!current.changed_since(&previous));
+
+    current.map = "de_dust2".to_string();
+    assert!(current.changed_since(&previous));
+}
+
+#[test]
+fn framed_info() {
+    let datagram = [&[0xFF, 0xFF, 0xFF, 0xFF, 0x49][..], &info_payload()].concat();
+
+    assert_eq!(
+        parse_source_info(&datagram[5..]).unwrap(),
+        parse_framed_source_info(&datagram).unwrap()
+    );
+    // Split prefix
+    assert!(parse_framed_source_info(&[&[0xFE][..], &datagram[1..]].concat()).is_err());
+    // Gold Source header
+    assert!(parse_framed_source_info(&[&datagram[..4], &[0x6D], &datagram[5..]].concat()).is_err());
+}
+
+#[test]
+fn security_summary() {
+    let payload = info_payload();
+
+    let mut info = parse_source_info(&payload).unwrap();
+    info.extra_data_fields.keywords = Some("pvp,BattlEye,hardcore".to_string());
+
+    assert!(info.vac_secured());
+    assert_eq!(
+        SecurityInfo {
+            vac: VacStatus::Secured,
+            password_protected: false,
+            anti_cheat: vec!["BattlEye".to_string()],
+        },
+        info.security()
+    );
+}
+
+#[test]
+fn builder_round_trip() {
+    let info = ServerInfoBuilder::new()
+        .name("a")
+        .steam_id(76561197960287930)
+        .source_tv(27020, "tv")
+        .game_id(240)
+        .build();
+
+    assert_eq!(0x51, info.extra_data_flag);
+    assert_eq!(info, parse_source_info(&info.to_bytes()).unwrap());
+}
+
+#[test]
+fn lenient_truncated_extra_data_fields() {
+    use crate::conformance::{Deviation, DeviationKind};
+
+    let payload = ServerInfoBuilder::new()
+        .keywords("secure")
+        .game_id(240)
+        .build()
+        .to_bytes();
+    // Cut off inside the game id
+    let truncated = &payload[..payload.len() - 4];
+    let offset = payload.len() - 8;
+
+    assert!(parse_source_info(truncated).is_err());
+    let (info, warnings) = parse_source_info_lenient(truncated, &ParseLimits::default()).unwrap();
+
+    assert_eq!(Some("secure"), info.extra_data_fields.keywords.as_deref());
+    assert_eq!(None, info.extra_data_fields.game_id);
+    assert_eq!(
+        vec![
+            ParseWarning::Dropped {
+                field: Some("game_id".to_string()),
+                offset
+            },
+            ParseWarning::Deviation(Deviation {
+                field: "game_id".to_string(),
+                offset,
+                kind: DeviationKind::Truncated,
+            }),
+        ],
+        warnings
+    );
+}
@@ -0,0 +1,540 @@
+use crate::info_source::{ExtraDataFields, SourceResponseInfo, TheShipFields};
+use crate::rules::{ResponseRule, RuleData};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq)]
+/**
+A key with a string or a block of nested key values, in Valve's [KeyValues](https://developer.valvesoftware.com/wiki/KeyValues) text format.
+
+Rules and Source info responses convert to and from it so they can be exchanged with tooling that reads the format.
+
+# Examples
+```
+use a2s_parse::keyvalues::KeyValues;
+use a2s_parse::rules::{ResponseRule, RuleData};
+use std::convert::TryFrom;
+
+let rules = ResponseRule {
+    rules: 1,
+    rule_data: vec![RuleData {
+        name: "mp_friendlyfire".to_string(),
+        value: "0".to_string(),
+    }],
+    remaining_data: String::new(),
+};
+
+let text = KeyValues::from(&rules).to_string();
+assert_eq!("\"rules\"\n{\n\t\"mp_friendlyfire\"\t\"0\"\n}\n", text);
+
+let parsed: KeyValues = text.parse().unwrap();
+assert_eq!(rules, ResponseRule::try_from(&parsed).unwrap());
+```
+*/
+pub struct KeyValues {
+    /// Name of the key
+    pub key: String,
+    /// Value of the key
+    pub value: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Value of a [`KeyValues`] entry
+pub enum Value {
+    /// A single string
+    String(String),
+    /// Nested key values between braces, keys may repeat
+    Block(Vec<KeyValues>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Reasons KeyValues text or its conversion to a response can fail
+pub enum KeyValuesError {
+    /// The text ended inside a string or block
+    UnexpectedEnd,
+    /// A brace was found where a key or value was expected
+    UnexpectedToken(char),
+    /// Text was found after the root key value
+    TrailingData,
+    /// The root key doesn't name the expected response
+    WrongRoot(String),
+    /// A required key is missing
+    MissingKey(String),
+    /// A value couldn't be converted to the field's type
+    InvalidValue {
+        /// Key of the value
+        key: String,
+        /// The value that failed to convert
+        value: String,
+    },
+}
+
+impl fmt::Display for KeyValuesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyValuesError::UnexpectedEnd => write!(f, "unexpected end of text"),
+            KeyValuesError::UnexpectedToken(token) => write!(f, "unexpected '{}'", token),
+            KeyValuesError::TrailingData => write!(f, "data after the root key"),
+            KeyValuesError::WrongRoot(key) => write!(f, "unexpected root key \"{}\"", key),
+            KeyValuesError::MissingKey(key) => write!(f, "missing key \"{}\"", key),
+            KeyValuesError::InvalidValue { key, value } => {
+                write!(f, "invalid value \"{}\" for key \"{}\"", value, key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyValuesError {}
+
+impl KeyValues {
+    /// Creates a key with a string value
+    pub fn string(key: impl Into<String>, value: impl Into<String>) -> Self {
+        KeyValues {
+            key: key.into(),
+            value: Value::String(value.into()),
+        }
+    }
+
+    /// Creates a key with a block of key values
+    pub fn block(key: impl Into<String>, children: Vec<KeyValues>) -> Self {
+        KeyValues {
+            key: key.into(),
+            value: Value::Block(children),
+        }
+    }
+
+    /// First child with the key, keys are compared case insensitively like the engine does
+    pub fn get(&self, key: &str) -> Option<&KeyValues> {
+        match &self.value {
+            Value::Block(children) => children
+                .iter()
+                .find(|child| child.key.eq_ignore_ascii_case(key)),
+            Value::String(_) => None,
+        }
+    }
+
+    /// String value of the first child with the key
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key).map(|child| &child.value) {
+            Some(Value::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "\t".repeat(depth);
+
+        match &self.value {
+            Value::String(value) => {
+                writeln!(
+                    f,
+                    "{}\"{}\"\t\"{}\"",
+                    indent,
+                    escape(&self.key),
+                    escape(value)
+                )
+            }
+            Value::Block(children) => {
+                writeln!(f, "{}\"{}\"", indent, escape(&self.key))?;
+                writeln!(f, "{}{{", indent)?;
+                for child in children {
+                    child.write(f, depth + 1)?;
+                }
+                writeln!(f, "{}}}", indent)
+            }
+        }
+    }
+}
+
+impl fmt::Display for KeyValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl FromStr for KeyValues {
+    type Err = KeyValuesError;
+
+    /// Parses a single root key, `//` comments and unquoted tokens are accepted
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Tokens {
+            chars: input.chars().peekable(),
+        };
+
+        let root = key_values(&mut tokens)?;
+        match tokens.next()? {
+            None => Ok(root),
+            Some(_) => Err(KeyValuesError::TrailingData),
+        }
+    }
+}
+
+// # Response conversions
+impl From<&ResponseRule> for KeyValues {
+    /// Each rule becomes a key in a `"rules"` block, truncated remaining data is left out
+    fn from(input: &ResponseRule) -> Self {
+        let children = input
+            .rule_data
+            .iter()
+            .map(|rule| KeyValues::string(&rule.name, &rule.value))
+            .collect();
+
+        KeyValues::block("rules", children)
+    }
+}
+
+impl TryFrom<&KeyValues> for ResponseRule {
+    type Error = KeyValuesError;
+
+    fn try_from(input: &KeyValues) -> Result<Self, Self::Error> {
+        let children = match (&input.value, input.key.eq_ignore_ascii_case("rules")) {
+            (Value::Block(children), true) => children,
+            _ => return Err(KeyValuesError::WrongRoot(input.key.clone())),
+        };
+
+        let rule_data = children
+            .iter()
+            .map(|child| match &child.value {
+                Value::String(value) => Ok(RuleData {
+                    name: child.key.clone(),
+                    value: value.clone(),
+                }),
+                Value::Block(_) => Err(KeyValuesError::InvalidValue {
+                    key: child.key.clone(),
+                    value: "{".to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ResponseRule {
+            rules: rule_data.len() as i16,
+            rule_data,
+            remaining_data: String::new(),
+        })
+    }
+}
+
+impl From<&SourceResponseInfo> for KeyValues {
+    /// Fields become keys in an `"info"` block, server type and environment are written as their letters, bytes
+    /// above ASCII as the Latin-1 character of the same code, and optional fields are only written if present
+    fn from(input: &SourceResponseInfo) -> Self {
+        let fields = &input.extra_data_fields;
+        let mut children = vec![
+            KeyValues::string("protocol", input.protocol.to_string()),
+            KeyValues::string("name", &input.name),
+            KeyValues::string("map", &input.map),
+            KeyValues::string("folder", &input.folder),
+            KeyValues::string("game", &input.game),
+            KeyValues::string("app_id", input.app_id.to_string()),
+            KeyValues::string("players", input.players.to_string()),
+            KeyValues::string("max_players", input.max_players.to_string()),
+            KeyValues::string("bots", input.bots.to_string()),
+            KeyValues::string(
+                "server_type",
+                char::from(input.server_type.to_byte(false)).to_string(),
+            ),
+            KeyValues::string(
+                "environment",
+                char::from(input.environment.to_byte(false)).to_string(),
+            ),
+            KeyValues::string("visibility", (input.visibility as u8).to_string()),
+            KeyValues::string("vac", u8::from(input.vac).to_string()),
+        ];
+        if let Some(ship) = &input.the_ship {
+            children.push(KeyValues::block(
+                "the_ship",
+                vec![
+                    KeyValues::string("mode", u8::from(&ship.mode).to_string()),
+                    KeyValues::string("witnesses", ship.witnesses.to_string()),
+                    KeyValues::string("duration", ship.duration.to_string()),
+                ],
+            ));
+        }
+        children.push(KeyValues::string("version", &input.version));
+        children.push(KeyValues::string(
+            "extra_data_flag",
+            input.extra_data_flag.to_string(),
+        ));
+
+        let optional = [
+            ("port", fields.port.map(|v| v.to_string())),
+            ("steam_id", fields.steam_id.map(|v| v.to_string())),
+            (
+                "source_tv_port",
+                fields.source_tv_port.map(|v| v.to_string()),
+            ),
+            ("source_tv_name", fields.source_tv_name.clone()),
+            ("keywords", fields.keywords.clone()),
+            ("game_id", fields.game_id.map(|v| v.to_string())),
+        ];
+        for (key, value) in optional.iter() {
+            if let Some(value) = value {
+                children.push(KeyValues::string(*key, value));
+            }
+        }
+
+        KeyValues::block("info", children)
+    }
+}
+
+impl TryFrom<&KeyValues> for SourceResponseInfo {
+    type Error = KeyValuesError;
+
+    fn try_from(input: &KeyValues) -> Result<Self, Self::Error> {
+        match (&input.value, input.key.eq_ignore_ascii_case("info")) {
+            (Value::Block(_), true) => (),
+            _ => return Err(KeyValuesError::WrongRoot(input.key.clone())),
+        }
+
+        let the_ship = match input.get("the_ship") {
+            Some(ship) => Some(TheShipFields {
+                mode: required::<u8>(ship, "mode")?.into(),
+                witnesses: required(ship, "witnesses")?,
+                duration: required(ship, "duration")?,
+            }),
+            None => None,
+        };
+
+        Ok(SourceResponseInfo {
+            protocol: required(input, "protocol")?,
+            name: required(input, "name")?,
+            map: required(input, "map")?,
+            folder: required(input, "folder")?,
+            game: required(input, "game")?,
+            app_id: required(input, "app_id")?,
+            players: required(input, "players")?,
+            max_players: required(input, "max_players")?,
+            bots: required(input, "bots")?,
+            server_type: letter(input, "server_type")?.into(),
+            environment: letter(input, "environment")?.into(),
+            visibility: required::<u8>(input, "visibility")? != 0,
+            vac: required::<u8>(input, "vac")?.into(),
+            the_ship,
+            version: required(input, "version")?,
+            extra_data_flag: required(input, "extra_data_flag")?,
+            extra_data_fields: ExtraDataFields {
+                port: optional(input, "port")?,
+                steam_id: optional(input, "steam_id")?,
+                source_tv_port: optional(input, "source_tv_port")?,
+                source_tv_name: optional(input, "source_tv_name")?,
+                keywords: optional(input, "keywords")?,
+                game_id: optional(input, "game_id")?,
+            },
+        })
+    }
+}
+
+// # Private helper functions
+/// Reads the optional string value of a key and converts it
+fn optional<T: FromStr>(input: &KeyValues, key: &str) -> Result<Option<T>, KeyValuesError> {
+    match input.get_str(key) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| KeyValuesError::InvalidValue {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Reads the string value of a key and converts it, erroring if it is missing
+fn required<T: FromStr>(input: &KeyValues, key: &str) -> Result<T, KeyValuesError> {
+    optional(input, key)?.ok_or_else(|| KeyValuesError::MissingKey(key.to_string()))
+}
+
+/// Reads a byte written as a single character, the inverse of `char::from(u8)`
+fn letter(input: &KeyValues, key: &str) -> Result<u8, KeyValuesError> {
+    let value: String = required(input, key)?;
+    let mut chars = value.chars();
+
+    match (chars.next().map(u32::from), chars.next()) {
+        (Some(code), None) if code <= 0xFF => Ok(code as u8),
+        _ => Err(KeyValuesError::InvalidValue {
+            key: key.to_string(),
+            value,
+        }),
+    }
+}
+
+fn escape(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+struct Tokens<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Tokens<'_> {
+    /// Next token skipping whitespace and comments, None at the end of the text
+    fn next(&mut self) -> Result<Option<Token>, KeyValuesError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    let mut lookahead = self.chars.clone();
+                    lookahead.next();
+                    if lookahead.peek() != Some(&'/') {
+                        break;
+                    }
+                    self.chars
+                        .by_ref()
+                        .take_while(|c| *c != '\n')
+                        .for_each(drop);
+                }
+                _ => break,
+            }
+        }
+
+        match self.chars.next() {
+            None => Ok(None),
+            Some('{') => Ok(Some(Token::Open)),
+            Some('}') => Ok(Some(Token::Close)),
+            Some('"') => {
+                let mut text = String::new();
+                loop {
+                    match self.chars.next() {
+                        None => return Err(KeyValuesError::UnexpectedEnd),
+                        Some('"') => return Ok(Some(Token::Text(text))),
+                        Some('\\') => match self.chars.next() {
+                            None => return Err(KeyValuesError::UnexpectedEnd),
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(c) => text.push(c),
+                        },
+                        Some(c) => text.push(c),
+                    }
+                }
+            }
+            Some(first) => {
+                let mut text = first.to_string();
+                while let Some(c) = self.chars.peek() {
+                    if c.is_whitespace() || matches!(c, '"' | '{' | '}') {
+                        break;
+                    }
+                    text.push(*c);
+                    self.chars.next();
+                }
+                Ok(Some(Token::Text(text)))
+            }
+        }
+    }
+}
+
+fn key_values(tokens: &mut Tokens<'_>) -> Result<KeyValues, KeyValuesError> {
+    let key = match tokens.next()? {
+        Some(Token::Text(key)) => key,
+        Some(Token::Open) => return Err(KeyValuesError::UnexpectedToken('{')),
+        Some(Token::Close) => return Err(KeyValuesError::UnexpectedToken('}')),
+        None => return Err(KeyValuesError::UnexpectedEnd),
+    };
+
+    match tokens.next()? {
+        Some(Token::Text(value)) => Ok(KeyValues::string(key, value)),
+        Some(Token::Open) => Ok(KeyValues::block(key, block(tokens)?)),
+        Some(Token::Close) => Err(KeyValuesError::UnexpectedToken('}')),
+        None => Err(KeyValuesError::UnexpectedEnd),
+    }
+}
+
+/// Reads key values until the closing brace of the block
+fn block(tokens: &mut Tokens<'_>) -> Result<Vec<KeyValues>, KeyValuesError> {
+    let mut children = Vec::new();
+
+    loop {
+        let key = match tokens.next()? {
+            Some(Token::Text(key)) => key,
+            Some(Token::Close) => return Ok(children),
+            Some(Token::Open) => return Err(KeyValuesError::UnexpectedToken('{')),
+            None => return Err(KeyValuesError::UnexpectedEnd),
+        };
+
+        match tokens.next()? {
+            Some(Token::Text(value)) => children.push(KeyValues::string(key, value)),
+            Some(Token::Open) => children.push(KeyValues::block(key, block(tokens)?)),
+            Some(Token::Close) => return Err(KeyValuesError::UnexpectedToken('}')),
+            None => return Err(KeyValuesError::UnexpectedEnd),
+        }
+    }
+}
+
+// # Tests
+#[test]
+fn source_info_round_trip() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new()
+        .name("quoted \"name\"\twith tab")
+        .map("b")
+        .port(27016)
+        .build();
+
+    let text = KeyValues::from(&info).to_string();
+    let parsed: KeyValues = text.parse().unwrap();
+
+    assert_eq!(Some("27016"), parsed.get_str("port"));
+    assert_eq!(None, parsed.get("keywords"));
+    assert_eq!(info, SourceResponseInfo::try_from(&parsed).unwrap());
+}
+
+#[test]
+fn other_letters_round_trip() {
+    use crate::info_source::ServerInfoBuilder;
+    use crate::parser_util::{Environment, ServerType};
+
+    let info = ServerInfoBuilder::new()
+        .server_type(ServerType::Other(0xE9))
+        .environment(Environment::Other(0x80))
+        .build();
+
+    let text = KeyValues::from(&info).to_string();
+    let parsed: KeyValues = text.parse().unwrap();
+
+    assert_eq!(Some("\u{E9}"), parsed.get_str("server_type"));
+    assert_eq!(info, SourceResponseInfo::try_from(&parsed).unwrap());
+
+    // Characters beyond Latin-1 don't stand for a byte
+    let parsed: KeyValues = text.replace('\u{E9}', "\u{100}").parse().unwrap();
+    assert_eq!(
+        Err(KeyValuesError::InvalidValue {
+            key: "server_type".to_string(),
+            value: "\u{100}".to_string(),
+        }),
+        SourceResponseInfo::try_from(&parsed)
+    );
+}
+
+#[test]
+fn parse_valve_syntax() {
+    let text = "// comment\n\"rules\" {\n  sv_cheats 0 // trailing\n  \"hostname\" \"a { b\"\n}\n";
+
+    let parsed: KeyValues = text.parse().unwrap();
+
+    assert_eq!(Some("0"), parsed.get_str("SV_CHEATS"));
+    assert_eq!(Some("a { b"), parsed.get_str("hostname"));
+    assert_eq!(
+        Err(KeyValuesError::UnexpectedEnd),
+        "\"rules\" {".parse::<KeyValues>()
+    );
+    assert_eq!(
+        Err(KeyValuesError::WrongRoot("info".to_string())),
+        ResponseRule::try_from(&KeyValues::block("info", vec![]))
+    );
+}
@@ -12,26 +12,79 @@ All requests are parsed in [`requests`]
 
 // This is gonna hurt (at first)
 #![deny(missing_docs)]
-// TODO: Add better errors for parsing failures
 
+/// [`ServerAddress`](address::ServerAddress) parsing host strings and `steam://connect` URLs
+pub mod address;
+/// Scrubbing player names and Steam IDs from responses before they are stored
+pub mod anonymize;
+/// [`AsyncA2SClient`](async_client::AsyncA2SClient) querying servers over any async runtime
+pub mod async_client;
+/// [`Backoff`](backoff::Backoff) strategies deciding how long to wait between retries
+pub mod backoff;
+/// [`ParseCache`](cache::ParseCache) skipping parsing of byte identical payloads and [`ResponseCache`](cache::ResponseCache) reusing recent responses
+pub mod cache;
+/// [`Challenge`](challenge::Challenge) numbers used to prevent spoofed requests
+pub mod challenge;
+/// Blocking [`A2SClient`](client::A2SClient) querying servers over UDP
+pub mod client;
+/// [`Clock`](clock::Clock) abstracting time so timing logic can be tested deterministically
+pub mod clock;
+/// Checking payloads against the format documented on the [wiki](https://developer.valvesoftware.com/wiki/Server_queries)
+pub mod conformance;
+/// [`Correlator`](correlator::Correlator) matching responses to the requests a proxy forwarded
+pub mod correlator;
+/// Annotated dissection of datagrams for bug reports and protocol exploration
+pub mod debug;
+/// [`PacketDemux`](demux::PacketDemux) reassembling interleaved split responses of many servers
+pub mod demux;
+/// [`A2SError`](error::A2SError) returned by every parser when the input is malformed
+pub mod error;
+/// [`FaultyTransport`](fault::FaultyTransport) injecting packet loss and other faults of UDP into a transport
+pub mod fault;
 ///Parsing complete responses to [A2S_INFO](https://developer.valvesoftware.com/wiki/Server_queries#A2S_INFO) requests for [Gold Source](https://developer.valvesoftware.com/wiki/Goldsource)
 pub mod info_goldsource;
+/// Converting responses to and from Valve's [KeyValues](https://developer.valvesoftware.com/wiki/KeyValues) text format
+pub mod keyvalues;
+/// [`A2sMessage`](message::A2sMessage) trait implemented by every request and response
+pub mod message;
+/// Exporting [`ServerSnapshot`](client::ServerSnapshot)s in the [Prometheus](https://prometheus.io) text format
+#[cfg(feature = "metrics-export")]
+pub mod metrics;
+/// [`MioQuery`](mio_driver::MioQuery) driving a query from a [mio](https://docs.rs/mio) event loop
+#[cfg(feature = "mio")]
+pub mod mio_driver;
+/// [`ServerMonitor`](monitor::ServerMonitor) polling servers in the background and reporting changes
+pub mod monitor;
+/// [`QueryObserver`](observer::QueryObserver) notified of the datagrams, retries and parse errors of queries
+pub mod observer;
 /// Parsing [A2S Packets](https://developer.valvesoftware.com/wiki/Server_queries#Protocol)
 pub mod packet;
 // TODO: links?
 /// Parsing complete responses to [A2S_INFO](https://developer.valvesoftware.com/wiki/Server_queries#A2S_INFO) requests for [Source](https://developer.valvesoftware.com/wiki/Source)
 pub mod info_source;
-/// Enums used across [`info_goldsource`], [`info_source`], and [`packet`]
+/// Enums used across [`info_goldsource`], [`info_source`], and [`packet`], and the [`ParseLimits`](parser_util::ParseLimits) every parser enforces
 pub mod parser_util;
+/// Recording datagrams to [pcap](https://wiki.wireshark.org/Development/LibpcapFileFormat) captures
+pub mod pcap;
 /// Parsing complete responses to [A2S_PING](https://developer.valvesoftware.com/wiki/Server_queries#A2A_PING) requests for [Gold Source](https://developer.valvesoftware.com/wiki/Goldsource) and [Source](https://developer.valvesoftware.com/wiki/Source)
 pub mod ping;
 /// Parsing complete responses to [A2S_PLAYER](https://developer.valvesoftware.com/wiki/Server_queries#A2A_PLAYER) requests for [Gold Source](https://developer.valvesoftware.com/wiki/Goldsource) and [Source](https://developer.valvesoftware.com/wiki/Source)
 pub mod player;
+/// [`QueryPool`](pool::QueryPool) querying many servers over a few shared sockets
+pub mod pool;
+/// [`RateLimiter`](rate_limit::RateLimiter) limiting how fast requests are sent
+pub mod rate_limit;
 /// Parsing all complete [A2S](https://developer.valvesoftware.com/wiki/Server_queries#Requests) requests
 pub mod requests;
+/// [`parse_any`](response::parse_any) parsing any datagram a server sends into a [`Response`](response::Response)
+pub mod response;
 /// Parsing complete responses to [A2S_RULES](https://developer.valvesoftware.com/wiki/Server_queries#A2A_RULES) requests for [Gold Source](https://developer.valvesoftware.com/wiki/Goldsource) and [Source](https://developer.valvesoftware.com/wiki/Source)
 pub mod rules;
-
-// TODO: Parse any slice provided and attempt to make a packet out of it
-// Need to figure out how to return different packet types from one function call and how to determine
-// split gold source from split source
+/// [`Schedule`](schedule::Schedule) ordering the requests of batch queries so hosting ranges aren't flooded
+pub mod schedule;
+/// Sans-IO [`QuerySession`](session::QuerySession) handling challenges and split responses without touching sockets
+pub mod session;
+/// Snapshots of responses sharing their strings through a [`StringPool`](snapshot::StringPool) to reduce memory
+pub mod snapshot;
+/// Tracking players across consecutive [A2S_PLAYER](https://developer.valvesoftware.com/wiki/Server_queries#A2A_PLAYER) responses
+pub mod tracker;
@@ -0,0 +1,158 @@
+use crate::conformance::{payload_deviations, ParseWarning};
+use crate::error::A2SError;
+use crate::packet::{parse_framing, parse_multi_packet_auto, Framing, MultiPacket};
+
+// # Traits
+/**
+Common interface of every [A2S](https://developer.valvesoftware.com/wiki/Server_queries) request and response,
+allowing messages to be handled generically e.g. in dispatch tables.
+
+Both [`parse`](A2sMessage::parse) and [`write`](A2sMessage::write) work on the payload following the header byte,
+the same as the `parse_*` functions in each module.
+
+# Examples
+```
+use a2s_parse::message::A2sMessage;
+use a2s_parse::requests::PlayerRequest;
+
+let request = PlayerRequest { challenge: -1 };
+
+let mut payload = vec![PlayerRequest::HEADER];
+request.write(&mut payload);
+
+assert_eq!(vec![0x55, 0xFF, 0xFF, 0xFF, 0xFF], payload);
+assert_eq!(request, PlayerRequest::parse(&payload[1..]).unwrap());
+```
+*/
+pub trait A2sMessage: Sized {
+    /// Header byte identifying the message, see [`PayloadHeader`](crate::packet::PayloadHeader)
+    const HEADER: u8;
+
+    /// Parses the payload following the header byte
+    fn parse(input: &[u8]) -> Result<Self, A2SError>;
+
+    /// Appends the payload following the header byte to the output
+    fn write(&self, output: &mut Vec<u8>);
+
+    /// Same as [`parse`](A2sMessage::parse) but works around what it can, see
+    /// [`parse_lenient`](crate::conformance::parse_lenient). Skips data after the last field unless overridden
+    fn parse_lenient(input: &[u8]) -> Result<(Self, Vec<ParseWarning>), A2SError> {
+        let message = match Self::parse(input) {
+            Err(A2SError::TrailingData { offset, .. }) => Self::parse(&input[..offset])?,
+            result => result?,
+        };
+
+        Ok((message, payload_deviations(Self::HEADER, input)))
+    }
+
+    /// Complete single packet datagram holding the message, the `-1` prefix and header byte followed by the payload
+    fn to_framed_bytes(&self) -> Vec<u8> {
+        let mut output = vec![0xFF, 0xFF, 0xFF, 0xFF, Self::HEADER];
+        self.write(&mut output);
+
+        output
+    }
+
+    /**
+    Same as [`parse`](A2sMessage::parse) but also accepts a payload that still starts with the header byte.
+
+    If the first byte is the header and the rest parses, that result is returned. Otherwise the whole input is parsed,
+    so a payload that legitimately starts with the same value as the header (e.g. 68 players and 'D') still parses.
+
+    # Examples
+    ```
+    use a2s_parse::message::A2sMessage;
+    use a2s_parse::ping::PingResponse;
+
+    let with_header = PingResponse::parse_skipping_header(&[0x6A, 0x00]).unwrap();
+    let without_header = PingResponse::parse_skipping_header(&[0x00]).unwrap();
+
+    assert_eq!(with_header, without_header);
+    ```
+    */
+    fn parse_skipping_header(input: &[u8]) -> Result<Self, A2SError> {
+        match input.split_first() {
+            Some((&header, payload)) if header == Self::HEADER => {
+                Self::parse(payload).or_else(|_| Self::parse(input))
+            }
+            _ => Self::parse(input),
+        }
+    }
+
+    /**
+    Parses a complete single packet datagram, checking the `-1` prefix and that the header byte matches the message
+    before parsing the payload. Split responses have to be reassembled first and fail with [`A2SError::SplitResponse`].
+    Offsets in errors count from the start of the datagram.
+
+    # Examples
+    ```
+    use a2s_parse::message::A2sMessage;
+    use a2s_parse::ping::PingResponse;
+
+    let datagram = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+
+    assert_eq!("", PingResponse::parse_framed(&datagram).unwrap().payload);
+    assert!(PingResponse::parse_framed(&datagram[4..]).is_err());
+    ```
+    */
+    fn parse_framed(input: &[u8]) -> Result<Self, A2SError> {
+        match parse_framing(input)? {
+            (Framing::Single, payload) => match payload.split_first() {
+                Some((&header, payload)) if header == Self::HEADER => {
+                    Self::parse(payload).map_err(|e| e.offset_by(5))
+                }
+                Some((&header, _)) => Err(A2SError::UnexpectedHeader(header)),
+                None => Err(A2SError::TruncatedField {
+                    offset: 4,
+                    field: Some("header".to_string()),
+                }),
+            },
+            (Framing::Split, _) => Err(A2SError::SplitResponse),
+            (Framing::Unframed, _) => Err(A2SError::InvalidPrefix),
+        }
+    }
+
+    /**
+    Parses a datagram exactly as it was received. A `-1` datagram is parsed as by [`parse_framed`](A2sMessage::parse_framed),
+    a `-2` datagram is accepted if it is the only packet of an uncompressed split response, which some servers send
+    instead of a single packet. Responses split across several packets fail with [`A2SError::SplitResponse`]
+    and have to be reassembled, e.g. with a [`DatagramAssembler`](crate::packet::DatagramAssembler).
+
+    # Examples
+    ```
+    use a2s_parse::message::A2sMessage;
+    use a2s_parse::packet::split_source_payload;
+    use a2s_parse::ping::PingResponse;
+
+    let framed = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+    let split = split_source_payload(1, &framed, 1248).unwrap()[0].to_bytes();
+
+    assert_eq!("", PingResponse::parse_datagram(&framed).unwrap().payload);
+    assert_eq!("", PingResponse::parse_datagram(&split).unwrap().payload);
+    assert!(PingResponse::parse_datagram(&split_source_payload(1, &framed, 15).unwrap()[0].to_bytes()).is_err());
+    ```
+    */
+    fn parse_datagram(input: &[u8]) -> Result<Self, A2SError> {
+        match parse_framing(input)? {
+            (Framing::Single, _) => Self::parse_framed(input),
+            (Framing::Split, _) => {
+                let (total, compressed, payload) = match parse_multi_packet_auto(input)? {
+                    MultiPacket::Source(packet) => (
+                        packet.total,
+                        packet.compression_data.is_some(),
+                        packet.payload,
+                    ),
+                    MultiPacket::GoldSource(packet) => {
+                        (packet.total_packets, false, packet.payload)
+                    }
+                };
+                match total == 1 && !compressed {
+                    true => Self::parse_framed(payload)
+                        .map_err(|e| e.offset_by(input.len() - payload.len())),
+                    false => Err(A2SError::SplitResponse),
+                }
+            }
+            (Framing::Unframed, _) => Err(A2SError::InvalidPrefix),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::client::ServerSnapshot;
+
+use std::fmt::Write;
+
+// # Exposed functions
+/**
+Renders a snapshot in the Prometheus [text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+ready to be served from the `/metrics` endpoint of an exporter.
+
+Exports the player, bot and slot counts and the VAC status as gauges, and every rule whose value parses as a finite
+number as an `a2s_rule` gauge labelled with the rule name. Snapshots without rules only export the gauges. The labels are added to every sample, usually to tell
+servers apart. Requires the `metrics-export` feature.
+
+# Examples
+```
+use a2s_parse::client::ServerSnapshot;
+use a2s_parse::info_source::ServerInfoBuilder;
+use a2s_parse::metrics::to_prometheus;
+use a2s_parse::player::ResponsePlayer;
+use a2s_parse::rules::{ResponseRule, RuleData};
+
+let snapshot = ServerSnapshot {
+    info: ServerInfoBuilder::new().players(3).max_players(24).build(),
+    players: Some(ResponsePlayer { players: 0, player_data: Vec::new() }),
+    rules: Some(ResponseRule {
+        rules: 1,
+        rule_data: vec![RuleData { name: "mp_timelimit".to_string(), value: "30".to_string() }],
+        remaining_data: String::new(),
+    }),
+    unanswered: Vec::new(),
+};
+
+let text = to_prometheus(&snapshot, &[("server", "192.0.2.1:27015")]);
+
+assert!(text.contains("a2s_players{server=\"192.0.2.1:27015\"} 3\n"));
+assert!(text.contains("a2s_rule{server=\"192.0.2.1:27015\",rule=\"mp_timelimit\"} 30\n"));
+```
+*/
+pub fn to_prometheus(snapshot: &ServerSnapshot, labels: &[(&str, &str)]) -> String {
+    let info = &snapshot.info;
+    let mut output = String::new();
+
+    for (name, help, value) in [
+        (
+            "a2s_players",
+            "Players on the server, including bots",
+            info.players,
+        ),
+        ("a2s_bots", "Bots on the server", info.bots),
+        (
+            "a2s_max_players",
+            "Player slots of the server",
+            info.max_players,
+        ),
+        (
+            "a2s_vac",
+            "1 if the server is secured with VAC",
+            info.vac_secured() as u8,
+        ),
+    ]
+    .iter()
+    {
+        header(&mut output, name, help);
+        sample(&mut output, name, labels, None, f64::from(*value));
+    }
+
+    let rules: Vec<(&str, f64)> = snapshot
+        .rules
+        .iter()
+        .flat_map(|rules| &rules.rule_data)
+        .filter_map(|rule| match rule.value.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Some((rule.name.as_str(), value)),
+            _ => None,
+        })
+        .collect();
+
+    if !rules.is_empty() {
+        header(&mut output, "a2s_rule", "Value of a rule holding a number");
+        for (rule, value) in rules {
+            sample(&mut output, "a2s_rule", labels, Some(rule), value);
+        }
+    }
+
+    output
+}
+
+// # Private helper functions
+/// Writes the HELP and TYPE lines of a gauge
+fn header(output: &mut String, name: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} gauge", name);
+}
+
+/// Writes one sample line, the rule label is added after the given labels
+fn sample(
+    output: &mut String,
+    name: &str,
+    labels: &[(&str, &str)],
+    rule: Option<&str>,
+    value: f64,
+) {
+    let labels: Vec<String> = labels
+        .iter()
+        .copied()
+        .chain(rule.map(|rule| ("rule", rule)))
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+        .collect();
+
+    match labels.is_empty() {
+        true => {
+            let _ = writeln!(output, "{} {}", name, value);
+        }
+        false => {
+            let _ = writeln!(output, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+/// Escapes a label value, backslashes, double quotes and line feeds have to be escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// # Tests
+#[test]
+fn export_snapshot() {
+    use crate::info_source::ServerInfoBuilder;
+    use crate::parser_util::VacStatus;
+    use crate::player::ResponsePlayer;
+    use crate::rules::{ResponseRule, RuleData};
+
+    let rule = |name: &str, value: &str| RuleData {
+        name: name.to_string(),
+        value: value.to_string(),
+    };
+    let snapshot = ServerSnapshot {
+        info: ServerInfoBuilder::new()
+            .players(5)
+            .bots(2)
+            .max_players(16)
+            .vac(VacStatus::Secured)
+            .build(),
+        players: Some(ResponsePlayer {
+            players: 0,
+            player_data: Vec::new(),
+        }),
+        rules: Some(ResponseRule {
+            rules: 4,
+            rule_data: vec![
+                rule("sv_gravity", "800"),
+                rule("sv_tags", "secure"),
+                rule("mp_friendlyfire", " 0.5 "),
+                rule("odd\"name", "nan"),
+            ],
+            remaining_data: String::new(),
+        }),
+        unanswered: Vec::new(),
+    };
+
+    let expected = "\
+# HELP a2s_players Players on the server, including bots
+# TYPE a2s_players gauge
+a2s_players 5
+# HELP a2s_bots Bots on the server
+# TYPE a2s_bots gauge
+a2s_bots 2
+# HELP a2s_max_players Player slots of the server
+# TYPE a2s_max_players gauge
+a2s_max_players 16
+# HELP a2s_vac 1 if the server is secured with VAC
+# TYPE a2s_vac gauge
+a2s_vac 1
+# HELP a2s_rule Value of a rule holding a number
+# TYPE a2s_rule gauge
+a2s_rule{rule=\"sv_gravity\"} 800
+a2s_rule{rule=\"mp_friendlyfire\"} 0.5
+";
+
+    assert_eq!(expected, to_prometheus(&snapshot, &[]));
+    let without_rules = ServerSnapshot {
+        rules: None,
+        ..snapshot.clone()
+    };
+    assert!(!to_prometheus(&without_rules, &[]).contains("a2s_rule"));
+    assert!(
+        to_prometheus(&snapshot, &[("server", "a\"b")]).contains("a2s_bots{server=\"a\\\"b\"} 2\n")
+    );
+}
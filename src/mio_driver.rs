@@ -0,0 +1,164 @@
+use crate::client::{ClientError, BUFFER_SIZE};
+use crate::message::A2sMessage;
+use crate::session::{Event, QuerySession};
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use mio::event::Source;
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+// # Structs
+/**
+Query of one server over a non-blocking [`mio`] socket, advancing a [`QuerySession`] on readiness events.
+
+Register it with a [`mio::Poll`] for [`Interest::READABLE`], call [`start`](MioQuery::start) and then
+[`on_readable`](MioQuery::on_readable) whenever its token is readable until it returns the response.
+Timeouts are up to the event loop, call [`resend`](MioQuery::resend) to send the request again. Requires the `mio` feature.
+
+# Examples
+```no_run
+use a2s_parse::mio_driver::MioQuery;
+use a2s_parse::session::QuerySession;
+use mio::{Events, Interest, Poll, Token};
+
+let mut poll = Poll::new()?;
+let mut events = Events::with_capacity(16);
+let mut query = MioQuery::connect("127.0.0.1:27015".parse()?, QuerySession::info())?;
+poll.registry().register(&mut query, Token(0), Interest::READABLE)?;
+query.start()?;
+
+let info = loop {
+    poll.poll(&mut events, None)?;
+    if let Some(info) = query.on_readable()? {
+        break info;
+    }
+};
+println!("{}", info.name);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug)]
+pub struct MioQuery<T> {
+    socket: UdpSocket,
+    session: QuerySession<T>,
+}
+
+impl<T: A2sMessage> MioQuery<T> {
+    /// Binds a socket of the same IP version as the server and connects it, no data is sent until the query is started
+    pub fn connect(server: SocketAddr, session: QuerySession<T>) -> io::Result<Self> {
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(server)?;
+
+        Ok(MioQuery { socket, session })
+    }
+
+    /// The session driven by the query
+    pub fn session(&self) -> &QuerySession<T> {
+        &self.session
+    }
+
+    /// Sends the request
+    pub fn start(&mut self) -> io::Result<()> {
+        self.socket.send(&self.session.transmit().data).map(|_| ())
+    }
+
+    /// Sends the request again after a timeout
+    pub fn resend(&mut self) -> io::Result<()> {
+        self.start()
+    }
+
+    /// Receives every datagram waiting on the socket and hands it to the session, answering challenges.
+    /// Returns the response once it is complete and None while the query is still waiting
+    pub fn on_readable(&mut self) -> Result<Option<T>, ClientError> {
+        let mut buffer = [0; BUFFER_SIZE];
+
+        loop {
+            let received = match self.socket.recv(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            match self.session.handle_datagram(&buffer[..received]) {
+                Event::Transmit(datagram) => {
+                    self.socket.send(&datagram.data)?;
+                }
+                Event::Pending => {}
+                Event::Response(response) => return Ok(Some(response)),
+                Event::Error(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<T> Source for MioQuery<T> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.socket.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.socket.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.socket.deregister(registry)
+    }
+}
+
+// # Tests
+#[test]
+fn query_on_readiness() {
+    use crate::challenge::build_challenge_response;
+    use crate::requests::build_player_request;
+    use mio::{Events, Poll};
+
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut buffer = [0; BUFFER_SIZE];
+        let (_, peer) = server.recv_from(&mut buffer).unwrap();
+        server.send_to(&build_challenge_response(4), peer).unwrap();
+        let (received, peer) = server.recv_from(&mut buffer).unwrap();
+        server
+            .send_to(&[0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00], peer)
+            .unwrap();
+        buffer[..received].to_vec()
+    });
+
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(4);
+    let mut query = MioQuery::connect(address, QuerySession::players()).unwrap();
+    poll.registry()
+        .register(&mut query, Token(7), Interest::READABLE)
+        .unwrap();
+    query.start().unwrap();
+
+    let players = loop {
+        poll.poll(&mut events, Some(std::time::Duration::from_secs(3)))
+            .unwrap();
+        assert!(events.iter().any(|event| event.token() == Token(7)));
+        if let Some(players) = query.on_readable().unwrap() {
+            break players;
+        }
+    };
+
+    assert!(players.player_data.is_empty());
+    assert_eq!(Some(4), query.session().challenge());
+    assert_eq!(build_player_request(4), handle.join().unwrap());
+}
@@ -0,0 +1,386 @@
+use crate::client::QueryOptions;
+use crate::info_source::SourceResponseInfo;
+use crate::pool::QueryPool;
+use crate::rules::ResponseRule;
+use crate::tracker::{PlayerEvent, PlayerTracker, TrackedPlayer};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Callback invoked with the server and what changed
+type Callback = Box<dyn FnMut(SocketAddr, &ServerChange) + Send>;
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq)]
+/// Change noticed by a [`ServerMonitor`] between two polls of a server
+pub enum ServerChange {
+    /// The server changed maps
+    Map {
+        /// Map during the previous poll
+        previous: String,
+        /// Map now
+        current: String,
+    },
+    /// Players joined or left
+    PlayerCount {
+        /// Number of players during the previous poll
+        previous: u8,
+        /// Number of players now
+        current: u8,
+    },
+    /// A player joined or left, matched across A2S_PLAYER responses by a [`PlayerTracker`]
+    Player(PlayerEvent),
+    /// Any rule was added, removed or changed its value
+    Rules {
+        /// Rules during the previous poll
+        previous: ResponseRule,
+        /// Rules now
+        current: ResponseRule,
+    },
+}
+
+/// Latest responses of a server
+#[derive(Debug, Default)]
+struct Known {
+    info: Option<SourceResponseInfo>,
+    players: Option<PlayerTracker>,
+    rules: Option<ResponseRule>,
+}
+
+/**
+Polls a list of servers on an interval and invokes callbacks when their map, player count, players or rules change.
+
+Every poll queries A2S_INFO of all servers through a [`QueryPool`] and compares the responses with those of the previous
+successful poll. A2S_PLAYER and A2S_RULES are only queried for servers whose info
+[`changed_since`](SourceResponseInfo::changed_since) the previous poll, and the first time a server answers, which
+saves two queries per server and poll. Players swapping out without the count changing and rules changing on their own
+are therefore noticed at the next change of the info, as are players and rules whose query failed.
+The first responses of a server are only remembered, failed queries are skipped and compared against the next
+successful one. Call [`poll`](ServerMonitor::poll) from your own loop or [`spawn`](ServerMonitor::spawn) a thread
+polling in the background.
+
+# Examples
+```no_run
+use a2s_parse::monitor::ServerMonitor;
+use std::time::Duration;
+
+let servers = vec!["127.0.0.1:27015".parse()?];
+let monitor = ServerMonitor::new(servers, Duration::from_secs(30))?
+    .on_change(|server, change| println!("{}: {:?}", server, change));
+
+let handle = monitor.spawn();
+// ...
+let monitor = handle.stop();
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub struct ServerMonitor {
+    servers: Vec<SocketAddr>,
+    interval: Duration,
+    pool: QueryPool,
+    callbacks: Vec<Callback>,
+    known: HashMap<SocketAddr, Known>,
+}
+
+/// Monitor polling in a background thread, returned by [`ServerMonitor::spawn`]. Dropping it stops the thread
+#[derive(Debug)]
+pub struct MonitorHandle {
+    stop: Sender<()>,
+    thread: JoinHandle<ServerMonitor>,
+}
+
+impl ServerMonitor {
+    /// Same as [`ServerMonitor::with_options`] with the default [`QueryOptions`]
+    pub fn new(servers: Vec<SocketAddr>, interval: Duration) -> io::Result<Self> {
+        ServerMonitor::with_options(servers, interval, QueryOptions::default())
+    }
+
+    /// Polls the servers every `interval` with a [`QueryPool`] created from the options
+    pub fn with_options(
+        servers: Vec<SocketAddr>,
+        interval: Duration,
+        options: QueryOptions,
+    ) -> io::Result<Self> {
+        Ok(ServerMonitor {
+            servers,
+            interval,
+            pool: QueryPool::with_options(1, options)?,
+            callbacks: Vec::new(),
+            known: HashMap::new(),
+        })
+    }
+
+    /// Adds a callback invoked for every change, callbacks are called in the order they were added
+    pub fn on_change(
+        mut self,
+        callback: impl FnMut(SocketAddr, &ServerChange) + Send + 'static,
+    ) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Servers being monitored
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// Latest A2S_INFO response of the server, None if it never answered
+    pub fn info(&self, server: SocketAddr) -> Option<&SourceResponseInfo> {
+        self.known.get(&server)?.info.as_ref()
+    }
+
+    /// Players connected to the server as of the latest A2S_PLAYER response, None if it never answered
+    pub fn players(&self, server: SocketAddr) -> Option<&[TrackedPlayer]> {
+        self.known
+            .get(&server)?
+            .players
+            .as_ref()
+            .map(PlayerTracker::players)
+    }
+
+    /// Latest A2S_RULES response of the server, None if it never answered
+    pub fn rules(&self, server: SocketAddr) -> Option<&ResponseRule> {
+        self.known.get(&server)?.rules.as_ref()
+    }
+
+    /// Queries every server once, invokes the callbacks for every change and returns the changes
+    pub fn poll(&mut self) -> Vec<(SocketAddr, ServerChange)> {
+        let infos = self.pool.info(&self.servers);
+
+        let mut changes = Vec::new();
+        let mut changed = Vec::new();
+        for (&server, info) in self.servers.iter().zip(infos) {
+            let info = match info {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            let known = self.known.entry(server).or_default();
+
+            match known.info.take() {
+                Some(previous) => {
+                    if previous.map != info.map {
+                        changes.push((
+                            server,
+                            ServerChange::Map {
+                                previous: previous.map.clone(),
+                                current: info.map.clone(),
+                            },
+                        ));
+                    }
+                    if previous.players != info.players {
+                        changes.push((
+                            server,
+                            ServerChange::PlayerCount {
+                                previous: previous.players,
+                                current: info.players,
+                            },
+                        ));
+                    }
+                    if info.changed_since(&previous) {
+                        changed.push(server);
+                    }
+                }
+                None => changed.push(server),
+            }
+            known.info = Some(info);
+        }
+
+        let players = self.pool.players(&changed);
+        let rules = self.pool.rules(&changed);
+        for ((&server, players), rules) in changed.iter().zip(players).zip(rules) {
+            let known = self.known.entry(server).or_default();
+
+            if let Ok(players) = players {
+                match &mut known.players {
+                    Some(tracker) => changes.extend(
+                        tracker
+                            .update(&players)
+                            .into_iter()
+                            .map(|event| (server, ServerChange::Player(event))),
+                    ),
+                    None => {
+                        let mut tracker = PlayerTracker::new();
+                        tracker.update(&players);
+                        known.players = Some(tracker);
+                    }
+                }
+            }
+
+            if let Ok(rules) = rules {
+                match known.rules.replace(rules.clone()) {
+                    Some(previous) if previous != rules => changes.push((
+                        server,
+                        ServerChange::Rules {
+                            previous,
+                            current: rules,
+                        },
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        for (server, change) in &changes {
+            for callback in &mut self.callbacks {
+                callback(*server, change);
+            }
+        }
+        changes
+    }
+
+    /// Polls the servers in a background thread every interval until the handle is stopped or dropped
+    pub fn spawn(mut self) -> MonitorHandle {
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            loop {
+                self.poll();
+                match stopped.recv_timeout(self.interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+            self
+        });
+
+        MonitorHandle { stop, thread }
+    }
+}
+
+impl fmt::Debug for ServerMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerMonitor")
+            .field("servers", &self.servers)
+            .field("interval", &self.interval)
+            .field("pool", &self.pool)
+            .field("callbacks", &self.callbacks.len())
+            .field("known", &self.known)
+            .finish()
+    }
+}
+
+impl MonitorHandle {
+    /// Stops polling and returns the monitor once the current poll finished
+    pub fn stop(self) -> ServerMonitor {
+        // The thread only stops on its own if it panicked, which join reports
+        let _ = self.stop.send(());
+        match self.thread.join() {
+            Ok(monitor) => monitor,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+// # Tests
+#[test]
+fn reports_changes() {
+    use crate::client::serve;
+    use crate::info_source::ServerInfoBuilder;
+    use crate::message::A2sMessage;
+    use crate::player::{PlayerData, ResponsePlayer};
+    use crate::rules::RuleData;
+    use std::sync::{Arc, Mutex};
+
+    let rules = |value: &str| ResponseRule {
+        rules: 1,
+        rule_data: vec![RuleData {
+            name: "mp_timelimit".to_string(),
+            value: value.to_string(),
+        }],
+        remaining_data: String::new(),
+    };
+    let players = |players: &[(&str, f32)]| ResponsePlayer {
+        players: players.len() as u8,
+        player_data: players
+            .iter()
+            .map(|(name, duration)| PlayerData {
+                index: 0,
+                name: name.to_string(),
+                score: 0,
+                duration: *duration,
+                ship_data: None,
+            })
+            .collect(),
+    };
+    let before = ServerInfoBuilder::new().map("de_dust2").players(2).build();
+    let after = ServerInfoBuilder::new().map("de_nuke").players(3).build();
+    let (address, server) = serve(vec![
+        before.to_framed_bytes(),
+        players(&[("Alice", 10.0), ("Bob", 5.0)]).to_framed_bytes(),
+        rules("30").to_framed_bytes(),
+        // Unchanged, players and rules aren't queried
+        before.to_framed_bytes(),
+        after.to_framed_bytes(),
+        players(&[("Alice", 20.0), ("Carol", 1.0)]).to_framed_bytes(),
+        rules("45").to_framed_bytes(),
+    ]);
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let callback_seen = Arc::clone(&seen);
+    let mut monitor = ServerMonitor::new(vec![address], Duration::from_secs(1))
+        .unwrap()
+        .on_change(move |server, change| {
+            callback_seen.lock().unwrap().push((server, change.clone()))
+        });
+
+    assert!(monitor.poll().is_empty());
+    assert!(monitor.poll().is_empty());
+    let changes = monitor.poll();
+
+    assert_eq!(
+        vec![
+            (
+                address,
+                ServerChange::Map {
+                    previous: "de_dust2".to_string(),
+                    current: "de_nuke".to_string(),
+                }
+            ),
+            (
+                address,
+                ServerChange::PlayerCount {
+                    previous: 2,
+                    current: 3,
+                }
+            ),
+            (
+                address,
+                ServerChange::Player(PlayerEvent::Left(TrackedPlayer {
+                    id: 1,
+                    name: "Bob".to_string(),
+                    duration: 5.0,
+                }))
+            ),
+            (
+                address,
+                ServerChange::Player(PlayerEvent::Joined(TrackedPlayer {
+                    id: 2,
+                    name: "Carol".to_string(),
+                    duration: 1.0,
+                }))
+            ),
+            (
+                address,
+                ServerChange::Rules {
+                    previous: rules("30"),
+                    current: rules("45"),
+                }
+            ),
+        ],
+        changes
+    );
+    assert_eq!(changes, *seen.lock().unwrap());
+    assert_eq!(Some(&after), monitor.info(address));
+    assert_eq!(2, monitor.players(address).unwrap().len());
+    let headers: Vec<u8> = server
+        .join()
+        .unwrap()
+        .iter()
+        .map(|request| request[4])
+        .collect();
+    assert_eq!(b"TUVTTUV".to_vec(), headers);
+}
@@ -0,0 +1,132 @@
+use crate::error::A2SError;
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// # Traits
+/**
+Notified of what clients do while querying, set it as [`QueryOptions::observer`](crate::client::QueryOptions::observer)
+to count requests, responses, retries and broken responses in whichever metrics system the application uses.
+
+Called by [`A2SClient`](crate::client::A2SClient), [`AsyncA2SClient`](crate::async_client::AsyncA2SClient) and
+[`QueryPool`](crate::pool::QueryPool). Every method does nothing by default so only the interesting ones have to be
+implemented. The server is None for async transports that don't know their peer.
+
+# Examples
+```no_run
+use a2s_parse::client::{A2SClient, QueryOptions};
+use a2s_parse::error::A2SError;
+use a2s_parse::observer::QueryObserver;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    parse_errors: AtomicU64,
+}
+
+impl QueryObserver for Counters {
+    fn on_send(&self, _server: Option<SocketAddr>, _datagram: &[u8]) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_parse_error(&self, _server: Option<SocketAddr>, _error: &A2SError) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+let counters = Arc::new(Counters::default());
+let options = QueryOptions {
+    observer: Some(counters.clone()),
+    ..QueryOptions::default()
+};
+
+A2SClient::with_options("127.0.0.1:27015", options)?.info()?;
+println!("{} requests sent", counters.sent.load(Ordering::Relaxed));
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub trait QueryObserver: fmt::Debug + Send + Sync {
+    /// Called with every datagram sent to the server, resent requests and requests answering challenges included
+    fn on_send(&self, _server: Option<SocketAddr>, _datagram: &[u8]) {}
+
+    /// Called with every datagram received from the server, whether the query used it or not
+    fn on_receive(&self, _server: Option<SocketAddr>, _datagram: &[u8]) {}
+
+    /// Called when a request timed out and is resent after the delay
+    fn on_retry(&self, _server: Option<SocketAddr>, _delay: Duration) {}
+
+    /// Called when a query failed as the response couldn't be parsed
+    fn on_parse_error(&self, _server: Option<SocketAddr>, _error: &A2SError) {}
+}
+
+// # Tests
+/// Observer listing the events it was notified of
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct Recorder(std::sync::Mutex<Vec<String>>);
+
+#[cfg(test)]
+impl QueryObserver for Recorder {
+    fn on_send(&self, server: Option<SocketAddr>, datagram: &[u8]) {
+        let event = format!("send {:?} {}", server, datagram.len());
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_receive(&self, server: Option<SocketAddr>, datagram: &[u8]) {
+        let event = format!("receive {:?} {}", server, datagram.len());
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_retry(&self, server: Option<SocketAddr>, delay: Duration) {
+        let event = format!("retry {:?} {:?}", server, delay);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_parse_error(&self, server: Option<SocketAddr>, _error: &A2SError) {
+        let event = format!("parse error {:?}", server);
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+#[test]
+fn observe_queries() {
+    use crate::async_client::{AsyncA2SClient, ScriptedTransport};
+    use crate::backoff::Fixed;
+    use crate::client::{ClientError, QueryOptions};
+    use crate::requests::build_player_request;
+    use futures::executor::block_on;
+    use std::sync::Arc;
+
+    let recorder = Arc::new(Recorder::default());
+    // Times out once, then answers with a player whose name is cut off
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x01, 0x00, 0x61];
+    let transport = ScriptedTransport::new(vec![vec![], vec![response]]);
+    let options = QueryOptions {
+        backoff: Box::new(Fixed(Duration::from_millis(10))),
+        observer: Some(recorder.clone()),
+        ..QueryOptions::default()
+    };
+    let client = AsyncA2SClient::with_options(transport, options);
+
+    assert!(matches!(
+        block_on(client.players()),
+        Err(ClientError::Parse(_))
+    ));
+
+    let server = Some(SocketAddr::from(([127, 0, 0, 1], 27015)));
+    let request = build_player_request(-1).len();
+    assert_eq!(
+        vec![
+            format!("send {:?} {}", server, request),
+            format!("retry {:?} {:?}", server, Duration::from_millis(10)),
+            format!("send {:?} {}", server, request),
+            format!("receive {:?} 8", server),
+            format!("parse error {:?}", server),
+        ],
+        *recorder.0.lock().unwrap()
+    );
+}
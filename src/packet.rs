@@ -1,9 +1,17 @@
-use nom::{
-    combinator::rest,
-    error::Error,
-    number::complete::{le_i16, le_i32, le_u8},
-    Finish, IResult,
-};
+use crate::error::A2SError;
+use crate::parser_util::{ByteReader, ReadError};
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// Bytes preceding the payload of every Source split packet, the `-2` prefix, id, total, number and size
+pub const SOURCE_SPLIT_HEADER_SIZE: usize = 12;
+
+/// App ids of games whose servers send Source split packets without the size field, see [`has_split_size_field`]
+pub const NO_SPLIT_SIZE_APP_IDS: [i16; 3] = [215, 17550, 17700];
+
+/// Largest size field accepted by [`parse_source_multi_packet_lenient`], servers use values around 1248
+const MAX_SPLIT_SIZE: i16 = 4096;
 
 // # Structs / Enums
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -39,6 +47,15 @@ pub struct SourceMultiPacket<'a> {
     pub payload: &'a [u8],
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Split packet in the format it was detected as, returned by [`parse_multi_packet_auto`]
+pub enum MultiPacket<'a> {
+    /// Packet in the Source format
+    Source(SourceMultiPacket<'a>),
+    /// Packet in the Gold Source format
+    GoldSource(GoldsourceMultiPacket<'a>),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// Optional data contained within the first packet of a Source Multi Packet response
 pub struct CompressionData {
@@ -48,6 +65,97 @@ pub struct CompressionData {
     pub crc32_checksum: i32,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Reasons a packet can't be added to a [`PacketAssembler`] or the response can't be assembled
+pub enum AssembleError {
+    /// The packet belongs to another response, holds its id
+    WrongId(i32),
+    /// The packet's total number of packets differs from the packets received before
+    TotalMismatch {
+        /// Total of the packets received before
+        expected: u8,
+        /// Total of the packet
+        found: u8,
+    },
+    /// The packet number is not below the total number of packets
+    NumberOutOfRange {
+        /// Number of the packet
+        number: u8,
+        /// Total number of packets
+        total: u8,
+    },
+    /// Not every packet of the response has been received
+    Incomplete,
+    /// The response is compressed but no packet carried the compression data
+    MissingCompressionData,
+    /// The response is compressed but the `compression` feature is disabled
+    Compressed,
+    /// The compressed response is not valid bzip2 data, holds the error message
+    Decompress(String),
+    /// The decompressed response doesn't match the size and checksum sent with the first packet
+    ChecksumMismatch,
+    /// The response has more packets than [`AssemblyLimits::max_packets`] allows
+    TooManyPackets {
+        /// Total number of packets of the response
+        total: u8,
+        /// Largest number of packets allowed
+        limit: u8,
+    },
+    /// Buffering the packet or decompressing the response would exceed [`AssemblyLimits::max_bytes`], holds the limit
+    TooLarge(usize),
+    /// Starting another response would exceed [`AssemblyLimits::max_responses`], holds the limit
+    TooManyResponses(usize),
+    /// The datagram is neither a single nor a split packet, holds the parse error
+    Malformed(A2SError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Outcome of feeding a datagram to a [`DatagramAssembler`]
+pub enum FeedResult {
+    /// The complete response starting with the `-1` prefix
+    Complete(Vec<u8>),
+    /// More packets of a split response are needed
+    NeedMore,
+    /// The datagram or the response is invalid, the packets received so far are dropped
+    Error(AssembleError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Caps on the memory a [`PacketAssembler`] or [`PacketDemux`](crate::demux::PacketDemux) uses, so a malicious server
+/// can't make it buffer unbounded amounts of data
+pub struct AssemblyLimits {
+    /// Largest number of packets a response may be split into
+    pub max_packets: u8,
+    /// Largest number of bytes buffered, per response for an assembler and across all responses for a demux.
+    /// Also caps the size of decompressed responses
+    pub max_bytes: usize,
+    /// Largest number of responses a demux assembles at once
+    pub max_responses: usize,
+}
+
+impl Default for AssemblyLimits {
+    /// 64 packets and 1 MiB per response, 1024 responses at once
+    fn default() -> Self {
+        AssemblyLimits {
+            max_packets: 64,
+            max_bytes: 1 << 20,
+            max_responses: 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Prefix of a datagram indicating whether it holds the whole response
+pub enum Framing {
+    /// `-1`, the datagram holds the complete payload
+    Single,
+    /// `-2`, the datagram is one packet of a split response
+    Split,
+    /// No prefix, the datagram starts directly at the header byte. Sent by a few very old or buggy Gold Source servers
+    Unframed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 /// Indicates the type of payload contained within the packet  
 /// Used in [`packet`](crate::packet)
 pub enum PayloadHeader {
@@ -109,111 +217,1330 @@ impl From<u8> for PayloadHeader {
 }
 
 // # Exposed final parsers
-/// Attempt to parse the provided slice into a valid Goldsource Response, nom errors are returned on failure.
-pub fn parse_goldsource_multi_packet(input: &[u8]) -> Result<GoldsourceMultiPacket, Error<&[u8]>> {
-    match p_goldsource_multi_packet(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+/// Attempt to parse the provided slice into a valid Goldsource Response, an [`A2SError`] is returned on failure.
+pub fn parse_goldsource_multi_packet(input: &[u8]) -> Result<GoldsourceMultiPacket<'_>, A2SError> {
+    p_goldsource_multi_packet(&mut ByteReader::new(input)).map_err(A2SError::from)
+}
+/// Attempt to parse the provided slice into a valid Source Response, an [`A2SError`] is returned on failure.
+pub fn parse_source_multi_packet(input: &[u8]) -> Result<SourceMultiPacket<'_>, A2SError> {
+    match p_source_multi_packet(&mut ByteReader::new(input), true) {
+        Ok(packet) => Ok(packet),
+        Err(e) => Err(A2SError::from(e).with_field(&[0xFE, 0xFF, 0xFF, 0xFF], input)),
+    }
+}
+
+/// Same as [`parse_source_multi_packet`] for games that leave out the size field, see [`has_split_size_field`]
+pub fn parse_source_multi_packet_without_size(
+    input: &[u8],
+) -> Result<SourceMultiPacket<'_>, A2SError> {
+    p_source_multi_packet(&mut ByteReader::new(input), false).map_err(A2SError::from)
+}
+
+/**
+Parses a complete split datagram including its `-2` prefix without knowing whether the server sends Source or Gold Source packets.
+
+Both interpretations are checked for consistency: the packet number has to be below the total and the first packet of an
+uncompressed response has to start with the `-1` prefix of the response, Source packets also need a believable size field
+as described for [`parse_source_multi_packet_lenient`]. If both are consistent the Source format is preferred, which only
+happens for packets after the first. Fails with [`A2SError::InvalidValue`] if neither is consistent.
+
+# Examples
+```
+use a2s_parse::packet::{parse_multi_packet_auto, MultiPacket};
+
+// Gold Source packet 0 of 2, followed by the start of the response
+let datagram = [0xFE, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x00, 0x00, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x45];
+
+assert!(matches!(parse_multi_packet_auto(&datagram), Ok(MultiPacket::GoldSource(_))));
+```
+*/
+pub fn parse_multi_packet_auto(datagram: &[u8]) -> Result<MultiPacket<'_>, A2SError> {
+    let packet = match parse_framing(datagram)? {
+        (Framing::Split, packet) => packet,
+        _ => return Err(A2SError::InvalidPrefix),
+    };
+
+    if let Ok(source) = parse_source_multi_packet_lenient(packet) {
+        if source.number < source.total && plausible_split_size(&source) {
+            return Ok(MultiPacket::Source(source));
+        }
+    }
+    let goldsource = parse_goldsource_multi_packet(packet).map_err(|e| e.offset_by(4))?;
+    let framed = goldsource.current_packet != 0 || goldsource.payload.starts_with(&[0xFF; 4]);
+    match goldsource.current_packet < goldsource.total_packets && framed {
+        true => Ok(MultiPacket::GoldSource(goldsource)),
+        // Offset of the packet number
+        false => Err(A2SError::InvalidValue {
+            offset: 8,
+            field: Some("number".to_string()),
+        }),
     }
 }
-/// Attempt to parse the provided slice into a valid Source Response, nom errors are returned on failure.
-pub fn parse_source_multi_packet(input: &[u8]) -> Result<SourceMultiPacket, Error<&[u8]>> {
-    match p_source_multi_packet(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+
+/// Parses the packet with or without the size field depending on the app id and protocol from the server's A2S_INFO response
+pub fn parse_source_multi_packet_for(
+    input: &[u8],
+    app_id: i16,
+    protocol: u8,
+) -> Result<SourceMultiPacket<'_>, A2SError> {
+    match has_split_size_field(app_id, protocol) {
+        true => parse_source_multi_packet(input),
+        false => parse_source_multi_packet_without_size(input),
+    }
+}
+
+/**
+Same as [`parse_source_multi_packet`] but falls back to parsing without the size field if the packet doesn't look right with it,
+for when the game of the server isn't known.
+
+A packet parsed with the size field is accepted if the size is large enough for the packet and below 4096, and for the
+first packet of an uncompressed response if the payload starts with the `-1` prefix. Without the size field the first
+two bytes of the payload are read as the size, which rarely passes for real payloads.
+
+# Examples
+```
+use a2s_parse::packet::parse_source_multi_packet_lenient;
+
+// Id, total, number and the start of the response, no size field
+let packet = [0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x45];
+
+let parsed = parse_source_multi_packet_lenient(&packet).unwrap();
+
+assert_eq!(None, parsed.size);
+assert_eq!(&[0xFF, 0xFF, 0xFF, 0xFF, 0x45], parsed.payload);
+```
+*/
+pub fn parse_source_multi_packet_lenient(input: &[u8]) -> Result<SourceMultiPacket<'_>, A2SError> {
+    match parse_source_multi_packet(input) {
+        Ok(packet) if plausible_split_size(&packet) => Ok(packet),
+        Ok(packet) => parse_source_multi_packet_without_size(input).or(Ok(packet)),
+        Err(e) => parse_source_multi_packet_without_size(input).map_err(|_| e),
     }
 }
 
+/// False for the games listed in [`NO_SPLIT_SIZE_APP_IDS`] and for Counter-Strike: Source (240) servers on protocol 7,
+/// whose split packets lack the size field
+pub fn has_split_size_field(app_id: i16, protocol: u8) -> bool {
+    !(NO_SPLIT_SIZE_APP_IDS.contains(&app_id) || (app_id == 240 && protocol == 7))
+}
+
 // # Additional minor parsers for determining single/multi packet and the payload type
 /// The first byte of the payload indicates the message type contained within according to the [`PayloadHeader`](crate::parser_util::PayloadHeader)
-pub fn parse_payload_header(input: &[u8]) -> Result<PayloadHeader, Error<&[u8]>> {
-    match p_payload_header(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
-    }
+pub fn parse_payload_header(input: &[u8]) -> Result<PayloadHeader, A2SError> {
+    p_payload_header(&mut ByteReader::new(input)).map_err(A2SError::from)
 }
 
 /// Returns true if the first byte of the response is -2, indicating the response is split over multiple packets [wiki](https://developer.valvesoftware.com/wiki/Server_queries#Simple_Response_Format)
-pub fn parse_is_split_payload(input: &[u8]) -> Result<bool, Error<&[u8]>> {
-    match p_is_split_payload(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+pub fn parse_is_split_payload(input: &[u8]) -> Result<bool, A2SError> {
+    p_is_split_payload(&mut ByteReader::new(input)).map_err(A2SError::from)
+}
+
+/**
+Reads the `-1` or `-2` prefix of a datagram and returns the data following it, any other prefix is an error.
+
+# Examples
+```
+use a2s_parse::packet::{parse_framing, Framing};
+
+let datagram = [0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00];
+
+assert_eq!((Framing::Single, &datagram[4..]), parse_framing(&datagram).unwrap());
+```
+*/
+pub fn parse_framing(input: &[u8]) -> Result<(Framing, &[u8]), A2SError> {
+    match input.get(..4) {
+        Some([0xFF, 0xFF, 0xFF, 0xFF]) => Ok((Framing::Single, &input[4..])),
+        Some([0xFE, 0xFF, 0xFF, 0xFF]) => Ok((Framing::Split, &input[4..])),
+        Some(_) => Err(A2SError::InvalidPrefix),
+        None => Err(A2SError::TruncatedField {
+            offset: 0,
+            field: Some("framing".to_string()),
+        }),
     }
 }
 
-// # Private parsing helper functions
-fn p_goldsource_multi_packet(input: &[u8]) -> IResult<&[u8], GoldsourceMultiPacket> {
-    let (input, id) = le_i32(input)?;
-    let (input, packet_number) = le_u8(input)?;
-    let current_packet = packet_number >> 4;
-    let total_packets = packet_number & 0x0F;
-    let (input, payload) = rest(input)?;
+/**
+Same as [`parse_framing`] but also accepts a datagram without a prefix if it starts with a known header byte,
+returning [`Framing::Unframed`] and the whole datagram so the deviation can be reported.
+*/
+pub fn parse_framing_lenient(input: &[u8]) -> Result<(Framing, &[u8]), A2SError> {
+    match parse_framing(input) {
+        Ok(v) => Ok(v),
+        Err(e) => match input.first().map(|byte| PayloadHeader::from(*byte)) {
+            Some(PayloadHeader::Other(_)) | None => Err(e),
+            Some(_) => Ok((Framing::Unframed, input)),
+        },
+    }
+}
+
+// # Writing split packets
+impl SourceMultiPacket<'_> {
+    /// Complete datagram for the packet, starting with the `-2` prefix
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = vec![0xFE, 0xFF, 0xFF, 0xFF];
+        output.extend_from_slice(&self.id.to_le_bytes());
+        output.push(self.total);
+        output.push(self.number);
+        if let Some(size) = self.size {
+            output.extend_from_slice(&size.to_le_bytes());
+        }
+        if let Some(data) = &self.compression_data {
+            output.extend_from_slice(&data.decompressed_size.to_le_bytes());
+            output.extend_from_slice(&data.crc32_checksum.to_le_bytes());
+        }
+        output.extend_from_slice(self.payload);
+
+        output
+    }
+}
+
+/**
+Splits a response into Source split packets whose datagrams are at most `max_packet_size` bytes, for servers answering requests.
+
+The response is the complete message as it would be sent in a single packet, starting with the `-1` prefix.
+The most significant bit of the id is cleared as it marks compressed responses. The size field of each packet is set to `max_packet_size`.
+None is returned if `max_packet_size` leaves no room for payload or the response would need more than 255 packets.
+
+# Examples
+```
+use a2s_parse::packet::split_source_payload;
+
+let response = vec![0xAB; 3000];
+
+let packets = split_source_payload(1, &response, 1248).unwrap();
+
+assert_eq!(3, packets.len());
+assert!(packets.iter().all(|p| p.to_bytes().len() <= 1248));
+assert_eq!(response, packets.iter().flat_map(|p| p.payload.to_vec()).collect::<Vec<u8>>());
+```
+*/
+pub fn split_source_payload(
+    id: i32,
+    response: &[u8],
+    max_packet_size: usize,
+) -> Option<Vec<SourceMultiPacket<'_>>> {
+    split_source(id & i32::MAX, response, max_packet_size, None)
+}
+
+/**
+Same as [`split_source_payload`] for a response compressed with bzip2, e.g. by `compress_payload` with the `compression` feature.
+
+The most significant bit of the id is set to mark the response as compressed and the compression data
+is written in the first packet, which leaves 8 bytes less room for its payload.
+*/
+pub fn split_compressed_source_payload(
+    id: i32,
+    compressed: &[u8],
+    compression_data: CompressionData,
+    max_packet_size: usize,
+) -> Option<Vec<SourceMultiPacket<'_>>> {
+    split_source(
+        id | i32::MIN,
+        compressed,
+        max_packet_size,
+        Some(compression_data),
+    )
+}
+
+/**
+Compresses a response with bzip2 the way Orange Box era servers do, returning the compressed data
+and the [`CompressionData`] describing the original response. Requires the `compression` feature.
+
+# Examples
+```
+use a2s_parse::packet::{compress_payload, split_compressed_source_payload};
+
+let response = vec![0xAB; 10_000];
+
+let (compressed, data) = compress_payload(&response).unwrap();
+let packets = split_compressed_source_payload(1, &compressed, data, 1248).unwrap();
+
+assert_eq!(1, packets.len());
+assert_eq!(10_000, packets[0].compression_data.as_ref().unwrap().decompressed_size);
+```
+*/
+#[cfg(feature = "compression")]
+pub fn compress_payload(response: &[u8]) -> std::io::Result<(Vec<u8>, CompressionData)> {
+    use std::io::Write;
+
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+    encoder.write_all(response)?;
+    let compressed = encoder.finish()?;
 
     Ok((
-        input,
-        GoldsourceMultiPacket {
-            id,
-            packet_number,
-            current_packet,
-            total_packets,
-            payload,
+        compressed,
+        CompressionData {
+            decompressed_size: response.len() as i32,
+            crc32_checksum: crc32fast::hash(response) as i32,
         },
     ))
 }
 
-fn p_source_multi_packet(input: &[u8]) -> IResult<&[u8], SourceMultiPacket> {
-    let (input, id) = le_i32(input)?;
-    let (input, total) = le_u8(input)?;
-    let (input, number) = le_u8(input)?;
-    // TODO: Size is an odd one as most games use it.. but certain ones dont.. have to chain parsers maybe?
+// # Reassembling split packets
+impl SplitPacket for MultiPacket<'_> {
+    fn id(&self) -> i32 {
+        match self {
+            MultiPacket::Source(packet) => packet.id(),
+            MultiPacket::GoldSource(packet) => packet.id(),
+        }
+    }
+
+    fn total(&self) -> u8 {
+        match self {
+            MultiPacket::Source(packet) => packet.total(),
+            MultiPacket::GoldSource(packet) => packet.total(),
+        }
+    }
+
+    fn number(&self) -> u8 {
+        match self {
+            MultiPacket::Source(packet) => packet.number(),
+            MultiPacket::GoldSource(packet) => packet.number(),
+        }
+    }
+
+    fn payload(&self) -> &[u8] {
+        match self {
+            MultiPacket::Source(packet) => packet.payload(),
+            MultiPacket::GoldSource(packet) => packet.payload(),
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        match self {
+            MultiPacket::Source(packet) => packet.is_compressed(),
+            MultiPacket::GoldSource(packet) => packet.is_compressed(),
+        }
+    }
+
+    fn compression_data(&self) -> Option<&CompressionData> {
+        match self {
+            MultiPacket::Source(packet) => packet.compression_data(),
+            MultiPacket::GoldSource(packet) => packet.compression_data(),
+        }
+    }
+}
+
+/// Packet of a split response in either the Source or the Gold Source format, accepted by [`PacketAssembler`]
+pub trait SplitPacket {
+    /// Id of the response the packet belongs to
+    fn id(&self) -> i32;
+    /// Number of packets in the response
+    fn total(&self) -> u8;
+    /// Number of the packet in the response, starting at 0
+    fn number(&self) -> u8;
+    /// Payload carried by the packet
+    fn payload(&self) -> &[u8];
+    /// True if the payload of the response is compressed
+    fn is_compressed(&self) -> bool {
+        false
+    }
+    /// Compression data carried by the packet, only the first packet of a compressed response has it
+    fn compression_data(&self) -> Option<&CompressionData> {
+        None
+    }
+}
+
+impl SplitPacket for SourceMultiPacket<'_> {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn total(&self) -> u8 {
+        self.total
+    }
+
+    fn number(&self) -> u8 {
+        self.number
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.payload
+    }
+
+    /// The most significant bit of the id is set for compressed responses
+    fn is_compressed(&self) -> bool {
+        self.id < 0
+    }
+
+    fn compression_data(&self) -> Option<&CompressionData> {
+        self.compression_data.as_ref()
+    }
+}
+
+/// Gold Source responses are never compressed, the total and number are only 4 bits each
+impl SplitPacket for GoldsourceMultiPacket<'_> {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn total(&self) -> u8 {
+        self.total_packets
+    }
+
+    fn number(&self) -> u8 {
+        self.current_packet
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.payload
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleError::WrongId(id) => write!(f, "packet belongs to response {}", id),
+            AssembleError::TotalMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected {} packets but the packet says {}",
+                    expected, found
+                )
+            }
+            AssembleError::NumberOutOfRange { number, total } => {
+                write!(
+                    f,
+                    "packet number {} is out of range of {} packets",
+                    number, total
+                )
+            }
+            AssembleError::Incomplete => write!(f, "not every packet has been received"),
+            AssembleError::MissingCompressionData => {
+                write!(f, "compressed response is missing the compression data")
+            }
+            AssembleError::Compressed => {
+                write!(f, "response is compressed, enable the compression feature")
+            }
+            AssembleError::Decompress(e) => write!(f, "failed to decompress response: {}", e),
+            AssembleError::ChecksumMismatch => {
+                write!(f, "decompressed response failed the checksum")
+            }
+            AssembleError::TooManyPackets { total, limit } => {
+                write!(
+                    f,
+                    "response has {} packets, at most {} are allowed",
+                    total, limit
+                )
+            }
+            AssembleError::TooLarge(limit) => {
+                write!(f, "response exceeds the limit of {} bytes", limit)
+            }
+            AssembleError::TooManyResponses(limit) => {
+                write!(f, "already assembling the limit of {} responses", limit)
+            }
+            AssembleError::Malformed(e) => write!(f, "failed to parse datagram: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssembleError::Malformed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/**
+Puts the packets of a Source or Gold Source split response back together.
+
+Packets are accepted in any order and placed by their number, a packet received twice replaces the earlier copy.
+Packets of other responses and packets disagreeing about the total number of packets are rejected.
+Compressed Source responses are decompressed with bzip2 if the `compression` feature is enabled and checked against
+the size and CRC32 checksum sent with the first packet, corrupt responses are never returned.
+Both formats carry the complete response including its `-1` prefix, so the assembled payload of an uncompressed response starts with it.
+Responses exceeding the [`AssemblyLimits`] are rejected, [`PacketAssembler::new`] uses the default limits.
+
+[`insert`](PacketAssembler::insert) copies the payload of the packet. Receive loops that keep every datagram in its own
+buffer until the response is complete can use [`with_borrowed`](PacketAssembler::with_borrowed) and
+[`insert_borrowed`](PacketAssembler::insert_borrowed) instead, which keep a reference to the payload. The fragments are
+then only copied once, when the response is assembled, and compressed responses are decompressed straight from them.
+
+# Examples
+```
+use a2s_parse::packet::{split_source_payload, PacketAssembler};
+
+let response = vec![0xAB; 3000];
+let packets = split_source_payload(1, &response, 1248).unwrap();
+
+let mut assembler = PacketAssembler::new(&packets[2]).unwrap();
+assembler.insert(&packets[0]).unwrap();
+assert!(!assembler.is_complete());
+assembler.insert(&packets[1]).unwrap();
+
+assert_eq!(response, assembler.assemble().unwrap());
+```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketAssembler<'a> {
+    id: i32,
+    total: u8,
+    compressed: bool,
+    fragments: Vec<Option<Cow<'a, [u8]>>>,
+    compression_data: Option<CompressionData>,
+    limits: AssemblyLimits,
+}
+
+impl<'a> PacketAssembler<'a> {
+    /// Same as [`PacketAssembler::with_limits`] with the default [`AssemblyLimits`]
+    pub fn new(packet: &impl SplitPacket) -> Result<Self, AssembleError> {
+        PacketAssembler::with_limits(packet, AssemblyLimits::default())
+    }
+
+    /// Starts a response from any of its packets
+    pub fn with_limits(
+        packet: &impl SplitPacket,
+        limits: AssemblyLimits,
+    ) -> Result<Self, AssembleError> {
+        let mut assembler = PacketAssembler::start(packet, limits)?;
+        assembler.insert(packet)?;
+
+        Ok(assembler)
+    }
+
+    /// Same as [`PacketAssembler::with_limits`] but keeps a reference to the payload of the packet instead of copying it
+    pub fn with_borrowed(
+        packet: &MultiPacket<'a>,
+        limits: AssemblyLimits,
+    ) -> Result<Self, AssembleError> {
+        let mut assembler = PacketAssembler::start(packet, limits)?;
+        assembler.insert_borrowed(packet)?;
+
+        Ok(assembler)
+    }
+
+    /// Assembler without any packet for the response of the packet
+    fn start(packet: &impl SplitPacket, limits: AssemblyLimits) -> Result<Self, AssembleError> {
+        if packet.total() > limits.max_packets {
+            return Err(AssembleError::TooManyPackets {
+                total: packet.total(),
+                limit: limits.max_packets,
+            });
+        }
+
+        Ok(PacketAssembler {
+            id: packet.id(),
+            total: packet.total(),
+            compressed: packet.is_compressed(),
+            fragments: vec![None; usize::from(packet.total())],
+            compression_data: None,
+            limits,
+        })
+    }
+
+    /// Id of the response the packets belong to
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Number of packets in the response
+    pub fn total(&self) -> u8 {
+        self.total
+    }
+
+    /// Number of distinct packets received so far
+    pub fn received(&self) -> usize {
+        self.fragments.iter().filter(|f| f.is_some()).count()
+    }
+
+    /// Numbers of the packets not received yet, in order
+    pub fn missing(&self) -> Vec<u8> {
+        self.fragments
+            .iter()
+            .enumerate()
+            .filter(|(_, fragment)| fragment.is_none())
+            .map(|(number, _)| number as u8)
+            .collect()
+    }
+
+    /// Number of payload bytes buffered so far
+    pub fn buffered(&self) -> usize {
+        self.fragments
+            .iter()
+            .flatten()
+            .map(|fragment| fragment.len())
+            .sum()
+    }
+
+    /// True if the response is compressed, only Source responses can be
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Compression data sent with the first packet, None until it is received or if the response isn't compressed
+    pub fn compression_data(&self) -> Option<&CompressionData> {
+        self.compression_data.as_ref()
+    }
+
+    /// Adds a packet of the response
+    pub fn insert(&mut self, packet: &impl SplitPacket) -> Result<(), AssembleError> {
+        *self.slot(packet)? = Some(Cow::Owned(packet.payload().to_vec()));
+
+        Ok(())
+    }
+
+    /// Same as [`PacketAssembler::insert`] but keeps a reference to the payload of the packet instead of copying it
+    pub fn insert_borrowed(&mut self, packet: &MultiPacket<'a>) -> Result<(), AssembleError> {
+        let payload = match packet {
+            MultiPacket::Source(packet) => packet.payload,
+            MultiPacket::GoldSource(packet) => packet.payload,
+        };
+        *self.slot(packet)? = Some(Cow::Borrowed(payload));
+
+        Ok(())
+    }
+
+    /// Checks the packet belongs to the response and fits the limits, returns the place of its payload
+    fn slot(
+        &mut self,
+        packet: &impl SplitPacket,
+    ) -> Result<&mut Option<Cow<'a, [u8]>>, AssembleError> {
+        if packet.id() != self.id {
+            return Err(AssembleError::WrongId(packet.id()));
+        }
+        if packet.total() != self.total {
+            return Err(AssembleError::TotalMismatch {
+                expected: self.total,
+                found: packet.total(),
+            });
+        }
+
+        let buffered = self.buffered();
+        let fragment = self.fragments.get(usize::from(packet.number())).ok_or(
+            AssembleError::NumberOutOfRange {
+                number: packet.number(),
+                total: self.total,
+            },
+        )?;
+        let replaced = fragment.as_ref().map_or(0, |fragment| fragment.len());
+        if buffered - replaced + packet.payload().len() > self.limits.max_bytes {
+            return Err(AssembleError::TooLarge(self.limits.max_bytes));
+        }
+        if let Some(data) = packet.compression_data() {
+            self.compression_data = Some(data.clone());
+        }
+
+        Ok(&mut self.fragments[usize::from(packet.number())])
+    }
+
+    /// True once every packet has been received
+    pub fn is_complete(&self) -> bool {
+        self.fragments.iter().all(Option::is_some)
+    }
+
+    /// Joins the payloads of the packets in order and decompresses and verifies them, returns the complete response starting with the `-1` prefix
+    pub fn assemble(self) -> Result<Vec<u8>, AssembleError> {
+        if !self.is_complete() {
+            return Err(AssembleError::Incomplete);
+        }
+
+        let fragments: Vec<Cow<'a, [u8]>> = self.fragments.into_iter().flatten().collect();
+        match (self.compressed, self.compression_data) {
+            (false, _) => Ok(fragments.concat()),
+            (true, Some(data)) if data.decompressed_size as i64 > self.limits.max_bytes as i64 => {
+                Err(AssembleError::TooLarge(self.limits.max_bytes))
+            }
+            (true, Some(data)) => match decompress(&fragments, self.limits.max_bytes)? {
+                response if data.verify(&response) => Ok(response),
+                _ => Err(AssembleError::ChecksumMismatch),
+            },
+            (true, None) => Err(AssembleError::MissingCompressionData),
+        }
+    }
+}
+
+/**
+Drives a [`PacketAssembler`] from raw datagrams, for receive loops that shouldn't deal with packet internals.
+
+Datagrams holding the whole response are returned straight away, split packets are told apart as Source or Gold Source
+packets with [`parse_multi_packet_auto`] and assembled. Later packets of a Gold Source response can pass for Source packets,
+so once one packet of a response is detected as Gold Source the packets received before it are parsed again in that format.
+A packet of another response replaces the packets received so far, as servers answer a resent request with a new response.
+
+# Examples
+```
+use a2s_parse::packet::{split_source_payload, DatagramAssembler, FeedResult};
+
+let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00, 0x00, 0x00, 0x00];
+let packets = split_source_payload(1, &response, 20).unwrap();
+let mut assembler = DatagramAssembler::new();
+
+assert_eq!(FeedResult::NeedMore, assembler.feed(&packets[1].to_bytes()));
+assert_eq!(FeedResult::Complete(response.clone()), assembler.feed(&packets[0].to_bytes()));
+```
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatagramAssembler {
+    limits: AssemblyLimits,
+    assembler: Option<PacketAssembler<'static>>,
+    /// Set once a packet of the response was detected as Gold Source
+    goldsource: bool,
+    /// Datagrams of the response taken for Source packets, parsed again if it turns out to be Gold Source
+    datagrams: Vec<Vec<u8>>,
+}
+
+impl DatagramAssembler {
+    /// Same as [`DatagramAssembler::with_limits`] with the default [`AssemblyLimits`]
+    pub fn new() -> Self {
+        DatagramAssembler::default()
+    }
+
+    /// Assembles split responses within the limits
+    pub fn with_limits(limits: AssemblyLimits) -> Self {
+        DatagramAssembler {
+            limits,
+            assembler: None,
+            goldsource: false,
+            datagrams: Vec::new(),
+        }
+    }
+
+    /// The response being assembled, None if no split packet is buffered
+    pub fn pending(&self) -> Option<&PacketAssembler<'static>> {
+        self.assembler.as_ref()
+    }
+
+    /// Handles a datagram including its `-1` or `-2` prefix
+    pub fn feed(&mut self, datagram: &[u8]) -> FeedResult {
+        match self.receive(datagram) {
+            Ok(Some(response)) => FeedResult::Complete(response),
+            Ok(None) => FeedResult::NeedMore,
+            Err(e) => {
+                *self = DatagramAssembler::with_limits(self.limits);
+                FeedResult::Error(e)
+            }
+        }
+    }
+
+    fn receive(&mut self, datagram: &[u8]) -> Result<Option<Vec<u8>>, AssembleError> {
+        let packet = match parse_framing(datagram) {
+            Ok((Framing::Single, _)) => {
+                *self = DatagramAssembler::with_limits(self.limits);
+                return Ok(Some(datagram.to_vec()));
+            }
+            Ok((Framing::Split, _)) => {
+                parse_multi_packet_auto(datagram).map_err(AssembleError::Malformed)?
+            }
+            Ok((Framing::Unframed, _)) => {
+                return Err(AssembleError::Malformed(A2SError::InvalidPrefix))
+            }
+            Err(e) => return Err(AssembleError::Malformed(e)),
+        };
+
+        if self.assembler.as_ref().map(PacketAssembler::id) != Some(packet.id()) {
+            *self = DatagramAssembler::with_limits(self.limits);
+        }
+        match packet {
+            MultiPacket::Source(_) if self.goldsource => {
+                self.insert(&parse_goldsource_datagram(datagram)?)?
+            }
+            MultiPacket::GoldSource(packet) if !self.goldsource => {
+                self.goldsource = true;
+                self.assembler = None;
+                for earlier in std::mem::take(&mut self.datagrams) {
+                    self.insert(&parse_goldsource_datagram(&earlier)?)?;
+                }
+                self.insert(&packet)?;
+            }
+            packet => {
+                self.insert(&packet)?;
+                if !self.goldsource {
+                    self.datagrams.push(datagram.to_vec());
+                }
+            }
+        }
+
+        match self.assembler.as_ref().map(PacketAssembler::is_complete) {
+            Some(true) => {
+                self.datagrams.clear();
+                self.assembler
+                    .take()
+                    .map(PacketAssembler::assemble)
+                    .transpose()
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Adds the packet to the response being assembled, starting it if there is none
+    fn insert(&mut self, packet: &impl SplitPacket) -> Result<(), AssembleError> {
+        match &mut self.assembler {
+            Some(assembler) => assembler.insert(packet),
+            None => {
+                self.assembler = Some(PacketAssembler::with_limits(packet, self.limits)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+// # Checksum verification
+/// Returns true if the CRC32 checksum of the payload matches the expected value,
+/// used to verify the decompressed payload of a compressed Source response.
+/// The checksum is sent as a signed value so it is compared as one.
+pub fn verify_crc(payload: &[u8], expected: i32) -> bool {
+    crc32fast::hash(payload) as i32 == expected
+}
+
+impl CompressionData {
+    /// Returns true if the decompressed payload has the expected size and checksum
+    pub fn verify(&self, decompressed: &[u8]) -> bool {
+        decompressed.len() as i64 == i64::from(self.decompressed_size)
+            && verify_crc(decompressed, self.crc32_checksum)
+    }
+}
+
+// # Private parsing helper functions
+/// Parses a split datagram including its `-2` prefix as a Gold Source packet
+fn parse_goldsource_datagram(datagram: &[u8]) -> Result<GoldsourceMultiPacket<'_>, AssembleError> {
+    parse_goldsource_multi_packet(&datagram[4..])
+        .map_err(|e| AssembleError::Malformed(e.offset_by(4)))
+}
+
+/// Decompresses the fragments of a bzip2 response in order without joining them, stopping once it exceeds `max_bytes`
+#[cfg(feature = "compression")]
+fn decompress(fragments: &[Cow<'_, [u8]>], max_bytes: usize) -> Result<Vec<u8>, AssembleError> {
+    use std::io::Read;
+
+    let compressed = FragmentReader {
+        fragments: fragments.iter(),
+        current: &[],
+    };
+    let mut decompressed = Vec::new();
+    bzip2::read::BzDecoder::new(compressed)
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| AssembleError::Decompress(e.to_string()))?;
+
+    match decompressed.len() > max_bytes {
+        true => Err(AssembleError::TooLarge(max_bytes)),
+        false => Ok(decompressed),
+    }
+}
+
+/// Reads the fragments of a response one after the other
+#[cfg(feature = "compression")]
+struct FragmentReader<'f> {
+    fragments: std::slice::Iter<'f, Cow<'f, [u8]>>,
+    current: &'f [u8],
+}
+
+#[cfg(feature = "compression")]
+impl std::io::Read for FragmentReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.fragments.next() {
+                Some(fragment) => self.current = fragment,
+                None => return Ok(0),
+            }
+        }
+        self.current.read(buf)
+    }
+}
+
+/// Compressed responses can't be read without the `compression` feature
+#[cfg(not(feature = "compression"))]
+fn decompress(_fragments: &[Cow<'_, [u8]>], _max_bytes: usize) -> Result<Vec<u8>, AssembleError> {
+    Err(AssembleError::Compressed)
+}
+
+/// Splits the payload into packets, the compression data takes up room in the first packet
+fn split_source(
+    id: i32,
+    payload: &[u8],
+    max_packet_size: usize,
+    compression_data: Option<CompressionData>,
+) -> Option<Vec<SourceMultiPacket<'_>>> {
+    let chunk_size = max_packet_size.checked_sub(SOURCE_SPLIT_HEADER_SIZE)?;
+    let first_size = match compression_data {
+        Some(_) => chunk_size.checked_sub(8)?,
+        None => chunk_size,
+    };
+    if first_size == 0 {
+        return None;
+    }
+
+    let (first, rest) = payload.split_at(first_size.min(payload.len()));
+    let chunks: Vec<&[u8]> = std::iter::once(first)
+        .chain(rest.chunks(chunk_size))
+        .collect();
+    if chunks.len() > usize::from(u8::MAX) {
+        return None;
+    }
+
+    let total = chunks.len() as u8;
+    let size = max_packet_size.min(i16::MAX as usize) as i16;
+    let mut compression_data = compression_data;
+    Some(
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(number, payload)| SourceMultiPacket {
+                id,
+                total,
+                number: number as u8,
+                size: Some(size),
+                compression_data: compression_data.take(),
+                payload,
+            })
+            .collect(),
+    )
+}
+
+fn p_goldsource_multi_packet<'a>(
+    reader: &mut ByteReader<'a>,
+) -> Result<GoldsourceMultiPacket<'a>, ReadError> {
+    let id = reader.i32()?;
+    let packet_number = reader.u8()?;
+    let current_packet = packet_number >> 4;
+    let total_packets = packet_number & 0x0F;
+    let payload = reader.rest();
+
+    Ok(GoldsourceMultiPacket {
+        id,
+        packet_number,
+        current_packet,
+        total_packets,
+        payload,
+    })
+}
+
+/// True if the size field of a packet is believable, used to detect packets sent without it. Packets without one pass
+fn plausible_split_size(packet: &SourceMultiPacket<'_>) -> bool {
+    let fits = match packet.size {
+        Some(size) if size > 0 && size <= MAX_SPLIT_SIZE => {
+            packet.payload.len() + SOURCE_SPLIT_HEADER_SIZE <= size as usize
+        }
+        Some(_) => false,
+        None => true,
+    };
+    let framed = packet.number != 0 || packet.id < 0 || packet.payload.starts_with(&[0xFF; 4]);
+
+    fits && framed
+}
+
+fn p_source_multi_packet<'a>(
+    reader: &mut ByteReader<'a>,
+    with_size: bool,
+) -> Result<SourceMultiPacket<'a>, ReadError> {
+    let id = reader.i32()?;
+    let total = reader.u8()?;
+    let number = reader.u8()?;
     // Wiki lists: 215, 17550, 17700, and 240 when protocol = 7 as not having the size field
-    let (input, size) = le_i16(input)?;
+    let size = match with_size {
+        true => Some(reader.i16()?),
+        false => None,
+    };
     // If it is packet 0 of the response and the most significant bit of id is 1 then the packet payload is compressed
     // MSB set means negative
-    let (input, compression_data) = compression_data(input, number == 0 && id < 0)?;
-    let (input, payload) = rest(input)?;
+    let compression_data = compression_data(reader, number == 0 && id < 0)?;
+    let payload = reader.rest();
 
-    Ok((
-        input,
-        SourceMultiPacket {
-            id,
-            total,
-            number,
-            size: Some(size),
-            compression_data,
-            payload,
-        },
-    ))
+    Ok(SourceMultiPacket {
+        id,
+        total,
+        number,
+        size,
+        compression_data,
+        payload,
+    })
 }
 
-fn p_is_split_payload(input: &[u8]) -> IResult<&[u8], bool> {
-    let (input, single_packet) = le_i32(input)?;
+fn p_is_split_payload(reader: &mut ByteReader) -> Result<bool, ReadError> {
+    let single_packet = reader.i32()?;
 
-    Ok((input, single_packet == -2))
+    Ok(single_packet == -2)
 }
 
-fn p_payload_header(input: &[u8]) -> IResult<&[u8], PayloadHeader> {
-    let (input, payload_header) = le_u8(input)?;
-
-    Ok((input, payload_header.into()))
+fn p_payload_header(reader: &mut ByteReader) -> Result<PayloadHeader, ReadError> {
+    reader.u8().map(PayloadHeader::from)
 }
 
-fn compression_data(input: &[u8], compressed: bool) -> IResult<&[u8], Option<CompressionData>> {
+fn compression_data(
+    reader: &mut ByteReader,
+    compressed: bool,
+) -> Result<Option<CompressionData>, ReadError> {
     match compressed {
         true => {
-            let (input, decompressed_size) = le_i32(input)?;
-            let (input, crc32_checksum) = le_i32(input)?;
+            let decompressed_size = reader.i32()?;
+            let crc32_checksum = reader.i32()?;
 
-            Ok((
-                input,
-                Some(CompressionData {
-                    decompressed_size,
-                    crc32_checksum,
-                }),
-            ))
+            Ok(Some(CompressionData {
+                decompressed_size,
+                crc32_checksum,
+            }))
         }
-        false => Ok((input, None)),
+        false => Ok(None),
     }
 }
 
 // # Tests
+/// Splits the response into Gold Source packets of `chunk_size` bytes of payload, including the `-2` prefix
+#[cfg(test)]
+pub(crate) fn split_goldsource_datagrams(
+    id: i32,
+    response: &[u8],
+    chunk_size: usize,
+) -> Vec<Vec<u8>> {
+    let total = response.len().div_ceil(chunk_size) as u8;
+    response
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(number, chunk)| {
+            let mut datagram = vec![0xFE, 0xFF, 0xFF, 0xFF];
+            datagram.extend_from_slice(&id.to_le_bytes());
+            datagram.push((number as u8) << 4 | total);
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect()
+}
+
+#[test]
+fn verify_checksum() {
+    // CRC32 of "123456789" is 0xCBF43926
+    let data = CompressionData {
+        decompressed_size: 9,
+        crc32_checksum: 0xCBF43926_u32 as i32,
+    };
+
+    assert!(verify_crc(b"123456789", 0xCBF43926_u32 as i32));
+    assert!(data.verify(b"123456789"));
+    assert!(!data.verify(b"123456780"));
+}
+
+#[test]
+fn unframed_goldsource_reply() {
+    let datagram: [u8; 2] = [0x6A, 0x00];
+
+    assert!(parse_framing(&datagram).is_err());
+    assert_eq!(
+        (Framing::Unframed, &datagram[..]),
+        parse_framing_lenient(&datagram).unwrap()
+    );
+    assert!(parse_framing_lenient(&[0x00, 0x6A]).is_err());
+}
+
+#[test]
+fn split_round_trip() {
+    let response: Vec<u8> = (0..=255).collect();
+
+    let packets = split_source_payload(-5, &response, 112).unwrap();
+
+    assert_eq!(3, packets.len());
+    for (number, packet) in packets.iter().enumerate() {
+        let datagram = packet.to_bytes();
+        assert_eq!(packet, &parse_source_multi_packet(&datagram[4..]).unwrap());
+        assert_eq!(number as u8, packet.number);
+        assert_eq!((i32::MAX - 4, 3), (packet.id, packet.total));
+    }
+    assert_eq!(None, split_source_payload(1, &response, 12));
+    assert_eq!(None, split_source_payload(1, &[0; 256], 13));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compressed_split_round_trip() {
+    use std::io::Read;
+
+    let response: Vec<u8> = (0..50_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+    let (compressed, data) = compress_payload(&response).unwrap();
+    let packets = split_compressed_source_payload(3, &compressed, data, 200).unwrap();
+    let datagrams: Vec<Vec<u8>> = packets.iter().map(|p| p.to_bytes()).collect();
+    let parsed: Vec<SourceMultiPacket> = datagrams
+        .iter()
+        .map(|d| parse_source_multi_packet(&d[4..]).unwrap())
+        .collect();
+
+    let joined: Vec<u8> = parsed.iter().flat_map(|p| p.payload.to_vec()).collect();
+    let mut decompressed = Vec::new();
+    bzip2::read::BzDecoder::new(&joined[..])
+        .read_to_end(&mut decompressed)
+        .unwrap();
+
+    assert_eq!(packets, parsed);
+    assert!(datagrams.iter().all(|d| d.len() <= 200));
+    assert!(parsed[0]
+        .compression_data
+        .as_ref()
+        .unwrap()
+        .verify(&decompressed));
+    assert!(parsed[1..].iter().all(|p| p.compression_data.is_none()));
+}
+
+#[test]
+fn assemble_out_of_order() {
+    let response: Vec<u8> = (0..=255).collect();
+    let packets = split_source_payload(9, &response, 112).unwrap();
+    let other = split_source_payload(10, &response, 112).unwrap();
+
+    let mut assembler = PacketAssembler::new(&packets[1]).unwrap();
+    assembler.insert(&packets[1]).unwrap();
+    assembler.insert(&packets[2]).unwrap();
+
+    assert_eq!(Err(AssembleError::WrongId(10)), assembler.insert(&other[0]));
+    assert_eq!(
+        Err(AssembleError::TotalMismatch {
+            expected: 3,
+            found: 4
+        }),
+        assembler.insert(&SourceMultiPacket {
+            total: 4,
+            ..packets[0].clone()
+        })
+    );
+    assert_eq!(
+        Err(AssembleError::NumberOutOfRange {
+            number: 3,
+            total: 3
+        }),
+        assembler.insert(&SourceMultiPacket {
+            number: 3,
+            ..packets[0].clone()
+        })
+    );
+    assert_eq!(2, assembler.received());
+    assert_eq!(vec![0], assembler.missing());
+    assert_eq!(Err(AssembleError::Incomplete), assembler.clone().assemble());
+
+    assembler.insert(&packets[0]).unwrap();
+    assert_eq!(response, assembler.assemble().unwrap());
+}
+
+#[test]
+fn assemble_goldsource() {
+    use crate::message::A2sMessage;
+    use crate::ping::PingResponse;
+
+    let response = PingResponse {
+        payload: "x".repeat(20),
+    }
+    .to_framed_bytes();
+    let datagrams: Vec<Vec<u8>> = response
+        .chunks(10)
+        .enumerate()
+        .map(|(number, chunk)| {
+            let mut datagram = 77_i32.to_le_bytes().to_vec();
+            datagram.push((number as u8) << 4 | 3);
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect();
+    let packets: Vec<GoldsourceMultiPacket> = datagrams
+        .iter()
+        .map(|d| parse_goldsource_multi_packet(d).unwrap())
+        .collect();
+
+    let mut assembler = PacketAssembler::new(&packets[2]).unwrap();
+    assembler.insert(&packets[0]).unwrap();
+    assembler.insert(&packets[1]).unwrap();
+
+    assert!(!assembler.is_compressed());
+    assert_eq!(response, assembler.assemble().unwrap());
+}
+
+#[test]
+fn assemble_borrowed() {
+    let response: Vec<u8> = [&[0xFF; 4][..], &[0x45; 200]].concat();
+    // Every datagram stays in its own receive buffer
+    let datagrams: Vec<Vec<u8>> = split_source_payload(5, &response, 80)
+        .unwrap()
+        .iter()
+        .map(SourceMultiPacket::to_bytes)
+        .collect();
+    let packets: Vec<MultiPacket> = datagrams
+        .iter()
+        .map(|d| parse_multi_packet_auto(d).unwrap())
+        .collect();
+
+    let limits = AssemblyLimits::default();
+    let mut assembler = PacketAssembler::with_borrowed(&packets[2], limits).unwrap();
+    assembler.insert_borrowed(&packets[0]).unwrap();
+    assembler.insert_borrowed(&packets[1]).unwrap();
+    assert!(matches!(assembler.fragments[0], Some(Cow::Borrowed(_))));
+
+    let small = AssemblyLimits {
+        max_bytes: 10,
+        ..limits
+    };
+    assert_eq!(
+        Err(AssembleError::TooLarge(10)),
+        PacketAssembler::with_borrowed(&packets[0], small)
+    );
+    assert_eq!(response, assembler.assemble().unwrap());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn assemble_compressed() {
+    let response: Vec<u8> = (0..10_000u32).map(|i| (i % 13) as u8).collect();
+    let (compressed, data) = compress_payload(&response).unwrap();
+    let packets = split_compressed_source_payload(4, &compressed, data, 60).unwrap();
+
+    let mut assembler = PacketAssembler::new(&packets[packets.len() - 1]).unwrap();
+    for packet in &packets {
+        assembler.insert(packet).unwrap();
+    }
+    let mut missing = assembler.clone();
+    missing.compression_data = None;
+    let mut corrupt = assembler.clone();
+    if let Some(data) = &mut corrupt.compression_data {
+        data.crc32_checksum ^= 1;
+    }
+
+    assert!(assembler.is_compressed());
+    assert_eq!(response, assembler.assemble().unwrap());
+    assert_eq!(
+        Err(AssembleError::MissingCompressionData),
+        missing.assemble()
+    );
+    assert_eq!(Err(AssembleError::ChecksumMismatch), corrupt.assemble());
+}
+
+#[test]
+fn detect_missing_size_field() {
+    let response = [&[0xFF; 4][..], b"Erules and more rules"].concat();
+    let with_size = split_source_payload(3, &response, 20).unwrap();
+    let without_size: Vec<SourceMultiPacket> = with_size
+        .iter()
+        .map(|p| SourceMultiPacket {
+            size: None,
+            ..p.clone()
+        })
+        .collect();
+
+    for (with_size, without_size) in with_size.iter().zip(&without_size) {
+        let with_bytes = with_size.to_bytes();
+        let without_bytes = without_size.to_bytes();
+        assert_eq!(
+            with_size,
+            &parse_source_multi_packet_lenient(&with_bytes[4..]).unwrap()
+        );
+        assert_eq!(
+            without_size,
+            &parse_source_multi_packet_lenient(&without_bytes[4..]).unwrap()
+        );
+        assert_eq!(
+            without_size,
+            &parse_source_multi_packet_for(&without_bytes[4..], 240, 7).unwrap()
+        );
+    }
+    assert!(has_split_size_field(240, 17));
+    assert!(!has_split_size_field(17550, 17));
+}
+
+#[test]
+fn assembly_limits() {
+    let response = vec![0xAB; 1000];
+    let packets = split_source_payload(1, &response, 112).unwrap();
+    let limits = AssemblyLimits {
+        max_packets: 10,
+        max_bytes: 250,
+        ..AssemblyLimits::default()
+    };
+
+    assert_eq!(
+        Err(AssembleError::TooManyPackets {
+            total: 10,
+            limit: 9
+        }),
+        PacketAssembler::with_limits(
+            &packets[0],
+            AssemblyLimits {
+                max_packets: 9,
+                ..limits
+            }
+        )
+    );
+
+    let mut assembler = PacketAssembler::with_limits(&packets[0], limits).unwrap();
+    assembler.insert(&packets[1]).unwrap();
+    assembler.insert(&packets[1]).unwrap();
+    assert_eq!(200, assembler.buffered());
+    assert_eq!(
+        Err(AssembleError::TooLarge(250)),
+        assembler.insert(&packets[2])
+    );
+}
+
+#[test]
+fn feed_datagrams() {
+    let response = [&[0xFF; 4][..], &[0x45; 252][..]].concat();
+    let first = split_source_payload(1, &response, 112).unwrap();
+    let resent = split_source_payload(2, &response, 112).unwrap();
+    let mut assembler = DatagramAssembler::new();
+
+    assert_eq!(FeedResult::NeedMore, assembler.feed(&first[0].to_bytes()));
+    assert_eq!(FeedResult::NeedMore, assembler.feed(&resent[2].to_bytes()));
+    assert_eq!(FeedResult::NeedMore, assembler.feed(&resent[0].to_bytes()));
+    assert_eq!(vec![1], assembler.pending().unwrap().missing());
+    assert_eq!(
+        FeedResult::Complete(response.clone()),
+        assembler.feed(&resent[1].to_bytes())
+    );
+    assert_eq!(None, assembler.pending());
+
+    assert_eq!(
+        FeedResult::Complete(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00]),
+        assembler.feed(&[0xFF, 0xFF, 0xFF, 0xFF, 0x6A, 0x00])
+    );
+    assert!(matches!(
+        assembler.feed(&[0x6A, 0x00]),
+        FeedResult::Error(AssembleError::Malformed(_))
+    ));
+}
+
+#[test]
+fn feed_goldsource_datagrams() {
+    // The byte following the packet number of the second packet passes for a Source packet number below the total
+    let response = [&[0xFF; 4][..], b"E\x02\x00rul\x01value\x00"].concat();
+    let datagrams = split_goldsource_datagrams(5, &response, 10);
+    let mut assembler = DatagramAssembler::new();
+
+    assert!(matches!(
+        parse_multi_packet_auto(&datagrams[1]),
+        Ok(MultiPacket::Source(_))
+    ));
+    assert_eq!(FeedResult::NeedMore, assembler.feed(&datagrams[1]));
+    assert_eq!(
+        FeedResult::Complete(response.clone()),
+        assembler.feed(&datagrams[0])
+    );
+
+    assert_eq!(FeedResult::NeedMore, assembler.feed(&datagrams[0]));
+    assert_eq!(
+        FeedResult::Complete(response),
+        assembler.feed(&datagrams[1])
+    );
+}
+
+#[test]
+fn detect_split_format() {
+    let response = [&[0xFF; 4][..], b"Erules and more rules"].concat();
+    let source: Vec<Vec<u8>> = split_source_payload(3, &response, 20)
+        .unwrap()
+        .iter()
+        .map(SourceMultiPacket::to_bytes)
+        .collect();
+    let goldsource = split_goldsource_datagrams(3, &response, 10);
+
+    for datagram in &source {
+        assert!(matches!(
+            parse_multi_packet_auto(datagram),
+            Ok(MultiPacket::Source(_))
+        ));
+    }
+    for datagram in &goldsource {
+        assert!(matches!(
+            parse_multi_packet_auto(datagram),
+            Ok(MultiPacket::GoldSource(_))
+        ));
+    }
+    assert!(
+        parse_multi_packet_auto(&[0xFE, 0xFF, 0xFF, 0xFF, 0x03, 0x00, 0x00, 0x00, 0x00]).is_err()
+    );
+    assert!(parse_multi_packet_auto(&response).is_err());
+}
@@ -1,14 +1,43 @@
-use nom::{
-    bytes::complete::take_till,
-    character::complete::char,
-    combinator::opt,
-    number::complete::le_u8,
-    sequence::terminated,
-    IResult,
-};
+use std::convert::TryInto;
 
 // # Struct / Enums
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+Upper bounds the parsers enforce on lengths and counts claimed by a response, so a hostile datagram can't make them
+allocate or loop far beyond what any real server sends.
+
+Parsers without a `_with_limits` suffix use [`ParseLimits::default`], values above a limit fail with
+[`A2SError::LimitExceeded`](crate::error::A2SError::LimitExceeded).
+
+# Examples
+```
+use a2s_parse::error::A2SError;
+use a2s_parse::parser_util::ParseLimits;
+use a2s_parse::rules::parse_rule_with_limits;
+
+// A2S_RULES response with one rule, the value is 6 bytes long
+let payload = b"\x01\x00tags\0hidden\0";
+let limits = ParseLimits { max_string_len: 6, ..ParseLimits::default() };
+
+assert!(parse_rule_with_limits(payload, &limits).is_ok());
+assert_eq!(
+    Err(A2SError::LimitExceeded { offset: 7, field: Some("rule[0].value".to_string()) }),
+    parse_rule_with_limits(payload, &ParseLimits { max_string_len: 5, ..limits })
+);
+```
+*/
+pub struct ParseLimits {
+    /// Longest string in bytes, without the null terminator
+    pub max_string_len: usize,
+    /// Most players an A2S_PLAYER response may claim
+    pub max_players: usize,
+    /// Most rules an A2S_RULES response may claim
+    pub max_rules: usize,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Indicates the type of the server  
 /// Gold Source uses the capital (uppercase?) version of the characters  
 /// Used in [`info_goldsource`](crate::info_goldsource), [`info_source`](crate::info_source)
@@ -43,6 +72,7 @@ impl From<u8> for ServerType {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Indicates the Operating System the server is running on  
 /// Gold Source uses the capital (uppercase?) version of the characters  
 /// Used in [`info_goldsource`](crate::info_goldsource), [`info_source`](crate::info_source)
@@ -77,37 +107,483 @@ impl From<u8> for Environment {
     }
 }
 
-// TODO: Tests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Indicates whether the server is secured with [VAC](https://developer.valvesoftware.com/wiki/Valve_Anti-Cheat)  
+/// Used in [`info_goldsource`](crate::info_goldsource), [`info_source`](crate::info_source)
+pub enum VacStatus {
+    /// Not secured -> 0
+    Insecure,
+    /// Secured -> 1
+    Secured,
+    /// Any other value, holds the byte that was sent
+    Unknown(u8),
+}
+
+impl From<u8> for VacStatus {
+    fn from(input: u8) -> Self {
+        match input {
+            0 => VacStatus::Insecure,
+            1 => VacStatus::Secured,
+            _ => VacStatus::Unknown(input),
+        }
+    }
+}
+
+impl From<VacStatus> for u8 {
+    fn from(input: VacStatus) -> Self {
+        match input {
+            VacStatus::Insecure => 0,
+            VacStatus::Secured => 1,
+            VacStatus::Unknown(input) => input,
+        }
+    }
+}
+
+impl VacStatus {
+    /// True only if the server reported being secured, unknown values are not treated as secured
+    pub fn is_secured(self) -> bool {
+        self == VacStatus::Secured
+    }
+}
+
+impl ServerType {
+    /// Byte representing the server type, Source uses lowercase and Gold Source uppercase letters
+    pub(crate) fn to_byte(&self, uppercase: bool) -> u8 {
+        let byte = match self {
+            ServerType::Dedicated => b'd',
+            ServerType::NonDedicated => b'l',
+            ServerType::SourceTV => b'p',
+            ServerType::Other(input) => return *input,
+        };
+
+        match uppercase {
+            true => byte.to_ascii_uppercase(),
+            false => byte,
+        }
+    }
+}
+
+impl Environment {
+    /// Byte representing the environment, Source uses lowercase and Gold Source uppercase letters
+    pub(crate) fn to_byte(&self, uppercase: bool) -> u8 {
+        let byte = match self {
+            Environment::Linux => b'l',
+            Environment::Windows => b'w',
+            Environment::MacOS => b'm',
+            Environment::Other(input) => return *input,
+        };
+
+        match uppercase {
+            true => byte.to_ascii_uppercase(),
+            false => byte,
+        }
+    }
+}
+
+impl Default for ParseLimits {
+    /// Generous limits no real server is known to exceed: 1 MiB strings, as Unity based servers send rule values
+    /// of around 100 KB, 255 players and 4096 rules
+    fn default() -> Self {
+        ParseLimits {
+            max_string_len: 1 << 20,
+            max_players: 255,
+            max_rules: 4096,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Why a [`ByteReader`] couldn't read a field
+pub(crate) enum ReadErrorKind {
+    /// The input ended inside the field
+    Eof,
+    /// The input ended before the null terminator of a string, or before a null byte
+    MissingNull,
+    /// A null byte was expected but the byte isn't null
+    NotNull,
+    /// A length or count exceeds its [`ParseLimits`]
+    TooLarge,
+    /// The given number of bytes is left after the message
+    Trailing(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// What a lenient [`ByteReader`] worked around instead of failing
+pub(crate) enum Recovery {
+    /// A count or string exceeding its [`ParseLimits`] was cut to the limit
+    Clamped,
+    /// An optional field was cut off, it and the optional fields after it were left out
+    Dropped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Error of a [`ByteReader`], converted to an [`A2SError`](crate::error::A2SError) once the parse failed
+pub(crate) struct ReadError {
+    /// Offset from the start of the reader's input, where the field starts or where the input ended inside a string
+    pub(crate) offset: usize,
+    pub(crate) kind: ReadErrorKind,
+}
+
+/**
+Cursor over the input of a parser, reading the little endian numbers and null terminated strings of the protocol.
+
+Every read is bounds checked and fails with the offset of the field from the start of the input, a failed read doesn't
+consume anything. The reader is cheap to clone, which is how optional and repeated fields are attempted.
+
+A [`lenient`](ByteReader::lenient) reader clamps counts and strings exceeding their limits and drops optional fields
+that are cut off instead of failing, keeping the offset of everything it recovered from.
+*/
+#[derive(Clone, Debug)]
+pub(crate) struct ByteReader<'a> {
+    input: &'a [u8],
+    offset: usize,
+    /// What a lenient reader recovered from and where, None for strict readers
+    recovered: Option<Vec<(usize, Recovery)>>,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        ByteReader {
+            input,
+            offset: 0,
+            recovered: None,
+        }
+    }
+
+    /// Reader recovering from exceeded limits and cut off optional fields where it can
+    pub(crate) fn lenient(input: &'a [u8]) -> Self {
+        ByteReader {
+            recovered: Some(Vec::new()),
+            ..ByteReader::new(input)
+        }
+    }
+
+    /// Offsets and kinds of what the reader recovered from, in the order they were read
+    pub(crate) fn into_recovered(self) -> Vec<(usize, Recovery)> {
+        self.recovered.unwrap_or_default()
+    }
+
+    /// Bytes read so far
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Input that wasn't read yet
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.input[self.offset..]
+    }
+
+    fn error(&self, kind: ReadErrorKind) -> ReadError {
+        ReadError {
+            offset: self.offset,
+            kind,
+        }
+    }
+
+    /// Error for the length or count at the offset that exceeds its [`ParseLimits`]. Repeated fields read with
+    /// [`ByteReader::many`] fail with it instead of stopping
+    fn too_large(&self, offset: usize) -> ReadError {
+        ReadError {
+            offset,
+            kind: ReadErrorKind::TooLarge,
+        }
+    }
+
+    /// Checks the count read at the offset against its limit, lenient readers clamp it to the limit
+    pub(crate) fn limit(
+        &mut self,
+        count: usize,
+        max: usize,
+        offset: usize,
+    ) -> Result<usize, ReadError> {
+        if count <= max {
+            return Ok(count);
+        }
+        match &mut self.recovered {
+            Some(recovered) => {
+                recovered.push((offset, Recovery::Clamped));
+                Ok(max)
+            }
+            None => Err(self.too_large(offset)),
+        }
+    }
+
+    /// Passes on the result of reading optional fields, lenient readers drop the fields that are cut off and
+    /// leave the rest of the input unread
+    pub(crate) fn optional(&mut self, read: Result<(), ReadError>) -> Result<(), ReadError> {
+        match (&mut self.recovered, read) {
+            (Some(recovered), Err(e))
+                if matches!(e.kind, ReadErrorKind::Eof | ReadErrorKind::MissingNull) =>
+            {
+                recovered.push((self.offset, Recovery::Dropped));
+                Ok(())
+            }
+            (_, read) => read,
+        }
+    }
+
+    /// Reads the next `len` bytes
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        match self.remaining().get(..len) {
+            Some(bytes) => {
+                self.offset += len;
+                Ok(bytes)
+            }
+            None => Err(self.error(ReadErrorKind::Eof)),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        self.bytes(N)
+            .map(|bytes| bytes.try_into().expect("slice of N bytes"))
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ReadError> {
+        self.array().map(|[byte]| byte)
+    }
+
+    pub(crate) fn i16(&mut self) -> Result<i16, ReadError> {
+        self.array().map(i16::from_le_bytes)
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, ReadError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, ReadError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, ReadError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    /// Reads a byte if there is one left
+    pub(crate) fn opt_u8(&mut self) -> Option<u8> {
+        self.u8().ok()
+    }
+
+    /// Reads one byte, false if it is 0 and true otherwise
+    pub(crate) fn bool(&mut self) -> Result<bool, ReadError> {
+        self.u8().map(|byte| byte != 0)
+    }
+
+    /// Reads a C style string up to its null terminator, the terminator is consumed but not returned.
+    /// All data transmitted by the protocol should be UTF-8, invalid sequences are replaced
+    pub(crate) fn c_string(&mut self) -> Result<String, ReadError> {
+        self.limited_c_string(usize::MAX)
+    }
+
+    /// Same as [`ByteReader::c_string`] but strings longer than `max_len` bytes fail with [`ReadErrorKind::TooLarge`]
+    /// before they are copied. Lenient readers keep the first `max_len` bytes of terminated strings instead
+    pub(crate) fn limited_c_string(&mut self, max_len: usize) -> Result<String, ReadError> {
+        let remaining = self.remaining();
+        let len = remaining
+            .iter()
+            .position(|&byte| byte == 0x00)
+            .unwrap_or(remaining.len());
+        if len > max_len && self.recovered.is_none() {
+            return Err(self.too_large(self.offset));
+        }
+        if len == remaining.len() {
+            return Err(ReadError {
+                offset: self.input.len(),
+                kind: ReadErrorKind::MissingNull,
+            });
+        }
+
+        let kept = self.limit(len, max_len, self.offset)?;
+        self.offset += len + 1;
+        Ok(String::from_utf8_lossy(&remaining[..kept]).into_owned())
+    }
+
+    /// Reads one null byte, any other byte is an error
+    pub(crate) fn null(&mut self) -> Result<(), ReadError> {
+        match self.remaining().first() {
+            Some(0x00) => {
+                self.offset += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.error(ReadErrorKind::NotNull)),
+            None => Err(self.error(ReadErrorKind::MissingNull)),
+        }
+    }
+
+    /// Skips any run of null bytes, some server plugins pad their responses with them
+    pub(crate) fn null_padding(&mut self) {
+        while let Some(0x00) = self.remaining().first() {
+            self.offset += 1;
+        }
+    }
+
+    /// Reads everything that is left
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = self.remaining();
+        self.offset = self.input.len();
+        rest
+    }
+
+    /// Reads up to `max` items, stopping before the first one that can't be read completely as connecting players
+    /// and truncated responses leave out items. Items exceeding a limit still fail the read
+    pub(crate) fn many<T>(
+        &mut self,
+        max: usize,
+        mut item: impl FnMut(&mut Self) -> Result<T, ReadError>,
+    ) -> Result<Vec<T>, ReadError> {
+        let mut items = Vec::new();
+        while items.len() < max {
+            let mut attempt = self.clone();
+            match item(&mut attempt) {
+                Ok(value) => {
+                    *self = attempt;
+                    items.push(value);
+                }
+                Err(e) if e.kind == ReadErrorKind::TooLarge => return Err(e),
+                Err(_) => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Fails with [`ReadErrorKind::Trailing`] if anything is left, so data after a message can be told apart from a
+    /// field that was cut off. Lenient readers leave it unread
+    pub(crate) fn finish(&self) -> Result<(), ReadError> {
+        match self.remaining().len() {
+            0 => Ok(()),
+            _ if self.recovered.is_some() => Ok(()),
+            len => Err(self.error(ReadErrorKind::Trailing(len))),
+        }
+    }
+}
+
 // # General Helper functions used across several parsers
-/// Reads one byte from the input slice and returns the ServerType
-pub(crate) fn server_type(input: &[u8]) -> IResult<&[u8], ServerType> {
-    le_u8(input).map(|(next, res)| (next, res.into()))
+/// Runs the parser over the whole input, anything left after it fails with [`ReadErrorKind::Trailing`]
+pub(crate) fn all_consuming<'a, T>(
+    input: &'a [u8],
+    parser: impl FnOnce(&mut ByteReader<'a>) -> Result<T, ReadError>,
+) -> Result<T, ReadError> {
+    let mut reader = ByteReader::new(input);
+    let output = parser(&mut reader)?;
+    reader.finish()?;
+
+    Ok(output)
+}
+
+/// Runs the parser at the start of the input, returns the output along with the input that is left
+pub(crate) fn with_remaining<'a, T>(
+    input: &'a [u8],
+    parser: impl FnOnce(&mut ByteReader<'a>) -> Result<T, ReadError>,
+) -> Result<(T, &'a [u8]), ReadError> {
+    let mut reader = ByteReader::new(input);
+    let output = parser(&mut reader)?;
+
+    Ok((output, reader.remaining()))
+}
+
+/// Reads one byte and returns the ServerType
+pub(crate) fn server_type(reader: &mut ByteReader) -> Result<ServerType, ReadError> {
+    reader.u8().map(ServerType::from)
 }
 
-/// Reads one byte from the input slice and returns the Environment
-pub(crate) fn environment(input: &[u8]) -> IResult<&[u8], Environment> {
-    le_u8(input).map(|(next, res)| (next, res.into()))
+/// Reads one byte and returns the Environment
+pub(crate) fn environment(reader: &mut ByteReader) -> Result<Environment, ReadError> {
+    reader.u8().map(Environment::from)
 }
 
-/// Parses a C style String
-/// Reads all bytes until a null terminator is reached.
-/// All data transmitted by the protocol should be UTF-8. from_utf8_lossy is used as it can take a slice.
-pub(crate) fn c_string(input: &[u8]) -> IResult<&[u8], String> {
-    terminated(take_till(|c| c == 0x00u8), char(0x00 as char))(input)
-        .map(|(next, res)| (next, String::from_utf8_lossy(res).into_owned()))
+/// Reads one byte and returns the VacStatus
+pub(crate) fn vac_status(reader: &mut ByteReader) -> Result<VacStatus, ReadError> {
+    reader.u8().map(VacStatus::from)
 }
 
-/// Attempts to parse a byte, if the parser fails None is returned
-pub(crate) fn opt_le_u8(input: &[u8]) -> IResult<&[u8], Option<u8>> {
-    opt(le_u8)(input)
+// # General Helper functions used across several writers
+/// Writes a C style String, the bytes of the string followed by a null terminator
+pub(crate) fn write_c_string(output: &mut Vec<u8>, value: &str) {
+    output.extend_from_slice(value.as_bytes());
+    output.push(0x00);
 }
 
-/// Reads one null byte (0x00) from input. If the next byte is not null an Error is returned.
-pub(crate) fn parse_null(input: &[u8]) -> IResult<&[u8], char> {
-    char(0x00 as char)(input)
+/// Writes one byte, 1 if the value is true and 0 otherwise
+pub(crate) fn write_bool(output: &mut Vec<u8>, value: bool) {
+    output.push(value as u8);
 }
 
-/// Reads one byte from the input and returns false if it is equal to 0, 1 otherwise.
-pub(crate) fn parse_bool(input: &[u8]) -> IResult<&[u8], bool> {
-    le_u8(input).map(|(next, res)| (next, res != 0))
+// # General helper functions used across several modules
+/// 64 bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/index.html) hash over the concatenation of all parts.
+/// Used where a hash has to stay the same across runs and Rust versions, it is not a cryptographic hash.
+pub(crate) fn fnv1a(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+// # Tests
+#[test]
+fn read_fields() {
+    let input = [0x01, 0x02, 0x00, 0x61, 0x62, 0x00, 0x00, 0x00, 0x63];
+    let mut reader = ByteReader::new(&input);
+
+    assert_eq!(Ok(0x0201), reader.i16());
+    assert_eq!(Ok(()), reader.null());
+    assert_eq!(
+        Err(ReadError {
+            offset: 3,
+            kind: ReadErrorKind::TooLarge
+        }),
+        reader.limited_c_string(1)
+    );
+    assert_eq!(Ok("ab".to_string()), reader.c_string());
+    reader.null_padding();
+    assert_eq!(
+        Err(ReadError {
+            offset: 8,
+            kind: ReadErrorKind::Eof
+        }),
+        reader.i32()
+    );
+    assert_eq!(
+        Err(ReadError {
+            offset: 8,
+            kind: ReadErrorKind::NotNull
+        }),
+        reader.null()
+    );
+    assert_eq!(
+        Err(ReadError {
+            offset: 9,
+            kind: ReadErrorKind::MissingNull
+        }),
+        reader.c_string()
+    );
+    assert_eq!(
+        Err(ReadError {
+            offset: 8,
+            kind: ReadErrorKind::Trailing(1)
+        }),
+        reader.finish()
+    );
+    assert_eq!(&[0x63], reader.rest());
+    assert_eq!(Ok(()), reader.finish());
+}
+
+#[test]
+fn read_many() {
+    let input = [0x01, 0x02, 0x03];
+
+    // Stops before the item that is cut off, leaving it unread
+    let mut reader = ByteReader::new(&input);
+    assert_eq!(Ok(vec![0x0201]), reader.many(5, ByteReader::i16));
+    assert_eq!(&[0x03], reader.remaining());
+
+    let mut reader = ByteReader::new(&input);
+    assert_eq!(Ok(vec![0x01, 0x02]), reader.many(2, ByteReader::u8));
+
+    // Limits still fail the read
+    let mut reader = ByteReader::new(&input);
+    let error = reader.many(5, |reader| reader.limited_c_string(0));
+    assert_eq!(ReadErrorKind::TooLarge, error.unwrap_err().kind);
 }
@@ -0,0 +1,389 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// [LINKTYPE_RAW](https://www.tcpdump.org/linktypes.html), each record starts with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u32 = 101;
+/// UDP protocol number used in the IP headers
+const PROTOCOL_UDP: u8 = 17;
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const UDP_HEADER_LEN: usize = 8;
+
+// # Traits
+/**
+Records the datagrams clients exchange with servers, set it as [`QueryOptions::capture`](crate::client::QueryOptions::capture)
+to see every request and response including resent requests, challenges and dropped late responses.
+
+Implemented for a [`PcapWriter`] behind a mutex, implement it to log or count datagrams instead.
+
+# Examples
+```no_run
+use a2s_parse::client::{A2SClient, QueryOptions};
+use a2s_parse::pcap::PcapWriter;
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+
+let capture = Arc::new(Mutex::new(PcapWriter::new(File::create("queries.pcap")?)?));
+let options = QueryOptions {
+    capture: Some(capture.clone()),
+    ..QueryOptions::default()
+};
+
+A2SClient::with_options("127.0.0.1:27015", options)?.info()?;
+capture.lock().unwrap().flush()?;
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub trait Capture: fmt::Debug + Send + Sync {
+    /// Called with every datagram sent from the local address to the server
+    fn sent(&self, local: SocketAddr, server: SocketAddr, datagram: &[u8]);
+
+    /// Called with every datagram received from the server, whether the query used it or not
+    fn received(&self, local: SocketAddr, server: SocketAddr, datagram: &[u8]);
+}
+
+// # Structs
+/**
+Writes UDP datagrams to a [pcap](https://wiki.wireshark.org/Development/LibpcapFileFormat) capture so query traffic can be
+inspected and shared with standard tooling such as Wireshark or tcpdump.
+
+IP and UDP headers are generated for every datagram, including valid checksums.
+
+# Examples
+```
+use a2s_parse::pcap::PcapWriter;
+use std::time::SystemTime;
+
+let mut writer = PcapWriter::new(Vec::new()).unwrap();
+
+// A2S_INFO request
+let request = b"\xFF\xFF\xFF\xFFTSource Engine Query\0";
+writer
+    .write_datagram(
+        "127.0.0.1:50000".parse().unwrap(),
+        "127.0.0.1:27015".parse().unwrap(),
+        SystemTime::now(),
+        request,
+    )
+    .unwrap();
+
+let capture = writer.into_inner();
+```
+*/
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the pcap global header to the writer
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+        // Version 2.4
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        // Timezone offset and timestamp accuracy, always 0
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        // Snapshot length
+        header.extend_from_slice(&(u16::MAX as u32).to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+
+        writer.write_all(&header)?;
+
+        Ok(PcapWriter { writer })
+    }
+
+    /// Records a single datagram sent from `source` to `destination` at `timestamp`.
+    /// Both addresses must be of the same family, otherwise an [`InvalidInput`](io::ErrorKind::InvalidInput) error is returned.
+    pub fn write_datagram(
+        &mut self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        timestamp: SystemTime,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let packet = ip_packet(source, destination, payload)?;
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+        // Captured and original length are the same as nothing is truncated
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+
+        self.writer.write_all(&record)
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> fmt::Debug for PcapWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapWriter").finish_non_exhaustive()
+    }
+}
+
+/// Timestamps the datagrams with the system time as they are captured. Errors writing them are dropped so they can't fail
+/// queries, the writer has to be flushed to see them
+impl<W: Write + Send> Capture for Mutex<PcapWriter<W>> {
+    fn sent(&self, local: SocketAddr, server: SocketAddr, datagram: &[u8]) {
+        let mut writer = self.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.write_datagram(local, server, SystemTime::now(), datagram);
+    }
+
+    fn received(&self, local: SocketAddr, server: SocketAddr, datagram: &[u8]) {
+        let mut writer = self.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.write_datagram(server, local, SystemTime::now(), datagram);
+    }
+}
+
+// # Private helper functions
+/// Builds the IP packet containing the UDP datagram
+fn ip_packet(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let udp_len = UDP_HEADER_LEN + payload.len();
+
+    let (header, pseudo_header) = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let total_len = IPV4_HEADER_LEN + udp_len;
+            if total_len > u16::MAX as usize {
+                return Err(too_large());
+            }
+
+            let mut header = Vec::with_capacity(IPV4_HEADER_LEN);
+            // Version 4, 5 * 4 byte header
+            header.push(0x45);
+            header.push(0);
+            header.extend_from_slice(&(total_len as u16).to_be_bytes());
+            // Identification, flags and fragment offset
+            header.extend_from_slice(&[0, 0, 0, 0]);
+            // TTL
+            header.push(64);
+            header.push(PROTOCOL_UDP);
+            // Checksum is calculated once the rest of the header is filled in
+            header.extend_from_slice(&[0, 0]);
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+
+            let checksum = checksum(&[&header]);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+            let mut pseudo_header = Vec::with_capacity(12);
+            pseudo_header.extend_from_slice(&src.octets());
+            pseudo_header.extend_from_slice(&dst.octets());
+            pseudo_header.extend_from_slice(&[0, PROTOCOL_UDP]);
+            pseudo_header.extend_from_slice(&(udp_len as u16).to_be_bytes());
+
+            (header, pseudo_header)
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            if udp_len > u16::MAX as usize {
+                return Err(too_large());
+            }
+
+            let mut header = Vec::with_capacity(IPV6_HEADER_LEN);
+            // Version 6, no traffic class or flow label
+            header.extend_from_slice(&[0x60, 0, 0, 0]);
+            header.extend_from_slice(&(udp_len as u16).to_be_bytes());
+            header.push(PROTOCOL_UDP);
+            // Hop limit
+            header.push(64);
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+
+            let mut pseudo_header = Vec::with_capacity(40);
+            pseudo_header.extend_from_slice(&src.octets());
+            pseudo_header.extend_from_slice(&dst.octets());
+            pseudo_header.extend_from_slice(&(udp_len as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, PROTOCOL_UDP]);
+
+            (header, pseudo_header)
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "source and destination address families differ",
+            ))
+        }
+    };
+
+    let mut udp = Vec::with_capacity(udp_len);
+    udp.extend_from_slice(&source.port().to_be_bytes());
+    udp.extend_from_slice(&destination.port().to_be_bytes());
+    udp.extend_from_slice(&(udp_len as u16).to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+
+    // A checksum of 0 means no checksum, so it is sent as all ones instead
+    let udp_checksum = match checksum(&[&pseudo_header, &udp]) {
+        0 => 0xFFFF,
+        c => c,
+    };
+    udp[6..8].copy_from_slice(&udp_checksum.to_be_bytes());
+
+    let mut packet = header;
+    packet.extend_from_slice(&udp);
+
+    Ok(packet)
+}
+
+/// Internet checksum (RFC 1071) over the concatenation of all parts
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut bytes = parts.iter().flat_map(|part| part.iter());
+
+    while let Some(high) = bytes.next() {
+        let low = bytes.next().copied().unwrap_or(0);
+        sum += u32::from(u16::from_be_bytes([*high, low]));
+    }
+
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "datagram is too large to fit in a single IP packet",
+    )
+}
+
+// # Tests
+#[test]
+fn ipv4_record() {
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+    let payload = [0xFF, 0xFF, 0xFF, 0xFF, 0x69];
+
+    writer
+        .write_datagram(
+            "10.0.0.1:50000".parse().unwrap(),
+            "10.0.0.2:27015".parse().unwrap(),
+            UNIX_EPOCH + std::time::Duration::from_micros(1_500_000),
+            &payload,
+        )
+        .unwrap();
+
+    let capture = writer.into_inner();
+    let record = &capture[24..];
+    let packet = &record[16..];
+
+    assert_eq!(24 + 16 + 20 + 8 + 5, capture.len());
+    assert_eq!([0xD4, 0xC3, 0xB2, 0xA1], capture[..4]);
+    // Seconds and microseconds
+    assert_eq!([1, 0, 0, 0, 0x20, 0xA1, 0x07, 0x00], record[..8]);
+    // A valid header checksums to 0
+    assert_eq!(0, checksum(&[&packet[..20]]));
+    // UDP checksum including the pseudo header
+    assert_eq!(
+        0,
+        checksum(&[&packet[12..20], &[0, PROTOCOL_UDP, 0, 13], &packet[20..]])
+    );
+    assert_eq!(payload, packet[28..]);
+}
+
+#[test]
+fn ipv6_record() {
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+
+    writer
+        .write_datagram(
+            "[::1]:50000".parse().unwrap(),
+            "[::1]:27015".parse().unwrap(),
+            SystemTime::now(),
+            &[0x69],
+        )
+        .unwrap();
+
+    let capture = writer.into_inner();
+
+    assert_eq!(24 + 16 + 40 + 8 + 1, capture.len());
+    assert_eq!(0x60, capture[40]);
+}
+
+#[test]
+fn mixed_address_families() {
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+
+    let error = writer
+        .write_datagram(
+            "10.0.0.1:50000".parse().unwrap(),
+            "[::1]:27015".parse().unwrap(),
+            SystemTime::now(),
+            &[0x69],
+        )
+        .unwrap_err();
+
+    assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+}
+
+#[test]
+fn capture_queries() {
+    use crate::challenge::build_challenge_response;
+    use crate::client::{serve, A2SClient, QueryOptions};
+    use crate::pool::QueryPool;
+    use std::sync::Arc;
+
+    // Source ports of the records, found behind the 16 byte record and 20 byte IPv4 header
+    let ports = |capture: &[u8]| {
+        let mut ports = Vec::new();
+        let mut records = &capture[24..];
+        while !records.is_empty() {
+            let len =
+                u32::from_le_bytes([records[8], records[9], records[10], records[11]]) as usize;
+            ports.push(u16::from_be_bytes([records[36], records[37]]));
+            records = &records[16 + len..];
+        }
+        ports
+    };
+    let response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let (address, server) = serve(vec![build_challenge_response(3), response.clone()]);
+
+    let capture = Arc::new(Mutex::new(PcapWriter::new(Vec::new()).unwrap()));
+    let options = || QueryOptions {
+        capture: Some(capture.clone()),
+        ..QueryOptions::default()
+    };
+    A2SClient::with_options(address, options())
+        .unwrap()
+        .players()
+        .unwrap();
+    server.join().unwrap();
+    let (address, server) = serve(vec![response]);
+    QueryPool::with_options(1, options())
+        .unwrap()
+        .players(&[address])
+        .into_iter()
+        .for_each(|result| drop(result.unwrap()));
+    server.join().unwrap();
+
+    // The client and pool are dropped, leaving the only reference
+    let capture = Arc::try_unwrap(capture)
+        .unwrap()
+        .into_inner()
+        .unwrap()
+        .into_inner();
+    let ports = ports(&capture);
+    assert_eq!(6, ports.len());
+    // Requests from the client alternate with the responses from the server
+    assert_ne!(ports[0], ports[1]);
+    assert_eq!(ports[1], ports[3]);
+    assert_eq!(address.port(), ports[5]);
+}
@@ -1,6 +1,17 @@
-use nom::{combinator::all_consuming, error::Error, Finish, IResult};
-
-use crate::parser_util::c_string;
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::parser_util::{all_consuming, with_remaining, write_c_string, ByteReader, ReadError};
+
+use std::convert::TryFrom;
+
+// # Structs
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Payload of an [A2A_PING Response](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format_4)
+pub struct PingResponse {
+    /// `"00000000000000"` for Source servers and `""` for Gold Source servers
+    pub payload: String,
+}
 
 // # Exposed final parser
 /**
@@ -9,10 +20,10 @@ Attempts to parse the provided payload into a ping response
 [Wiki Page](https://developer.valvesoftware.com/wiki/Server_queries#A2A_PING)
 
 Source servers respond with `"00000000000000"`, while Gold Source servers respond with `""`.
-Any other response should be considered invalid.
+Any other response should be considered invalid. Trailing null bytes after the string are ignored.
 
 # Errors
-A [`nom::error::Error`](https://docs.rs/nom/6.1.2/nom/error/struct.Error.html) results if the parse fails for any reason
+An [`A2SError`] results if the parse fails for any reason
 
 # Examples
 
@@ -30,19 +41,57 @@ let response = parse_ping(&payload).unwrap();
 assert_eq!("00000000000000".to_string(), response);
 ```
  */
+pub fn parse_ping(input: &[u8]) -> Result<String, A2SError> {
+    match all_consuming(input, p_ping) {
+        Ok(payload) => Ok(payload),
+        Err(e) => Err(A2SError::from(e).with_field(&[PingResponse::HEADER], input)),
+    }
+}
+
+/// Same as [`parse_ping`] for a complete datagram starting with the `-1` prefix and 'j' header
+pub fn parse_framed_ping(input: &[u8]) -> Result<String, A2SError> {
+    PingResponse::parse_framed(input).map(|response| response.payload)
+}
 
-pub fn parse_ping(input: &[u8]) -> Result<String, Error<&[u8]>> {
-    match p_ping(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+/// Same as [`parse_ping`] but any data after the string is returned instead of causing an error,
+/// null padding is included in the returned data
+pub fn parse_ping_with_remaining(input: &[u8]) -> Result<(String, Vec<u8>), A2SError> {
+    match with_remaining(input, ByteReader::c_string) {
+        Ok((payload, remaining)) => Ok((payload, remaining.to_vec())),
+        Err(e) => Err(A2SError::from(e).with_field(&[PingResponse::HEADER], input)),
     }
 }
 
 // # Private parsing helper functions
-/// Make sure that all of the input data was consumed. If it is not the response should be considered invalid as the
-/// spec lists only a C style string as the response.
-fn p_ping(input: &[u8]) -> IResult<&[u8], String> {
-    all_consuming(c_string)(input)
+/// Reads the string and skips any null padding after it. Run with `all_consuming` as the spec lists only a C style
+/// string as the response, data after it should be considered invalid. Trailing null padding is not considered extra data.
+fn p_ping(reader: &mut ByteReader) -> Result<String, ReadError> {
+    let payload = reader.c_string()?;
+    reader.null_padding();
+
+    Ok(payload)
+}
+
+// # Message implementation
+impl A2sMessage for PingResponse {
+    const HEADER: u8 = 0x6A;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_ping(input).map(|payload| PingResponse { payload })
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        write_c_string(output, &self.payload);
+    }
+}
+
+/// Parses the payload following the header byte, same as [`A2sMessage::parse`]
+impl<'a> TryFrom<&'a [u8]> for PingResponse {
+    type Error = A2SError;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        <Self as A2sMessage>::parse(input)
+    }
 }
 
 // # Test
@@ -66,7 +115,10 @@ fn no_payload() {
 
     // using [..] transforms it into a slice
     let response = parse_ping(&payload[..]).unwrap_err();
-    let error = nom::error::Error::new(&payload[..], nom::error::ErrorKind::Char);
+    let error = A2SError::TruncatedField {
+        offset: 0,
+        field: Some("payload".to_string()),
+    };
     assert_eq!(error, response);
 }
 
@@ -79,8 +131,17 @@ fn extra_payload() {
     ];
 
     let response = parse_ping(&payload).unwrap_err();
-    // [..1] tricks it into being a slice
-    let error = nom::error::Error::new(&payload[..1], nom::error::ErrorKind::Eof);
+    // The last byte is left over
+    let error = A2SError::TrailingData { offset: 15, len: 1 };
 
     assert_eq!(error, response);
 }
+
+#[test]
+fn null_padded_payload() {
+    let payload: [u8; 3] = [0x00, 0x00, 0x00];
+
+    let response = parse_ping(&payload).unwrap();
+
+    assert_eq!("".to_string(), response);
+}
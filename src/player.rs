@@ -1,57 +1,152 @@
-use crate::parser_util::c_string;
-
-use nom::{
-    combinator::all_consuming,
-    error::Error,
-    multi::many_m_n,
-    number::complete::{le_f32, le_i32, le_u8},
-    Finish, IResult,
+use crate::conformance::{count_violation, parse_with_warnings, ParseWarning, Violation};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::parser_util::{
+    all_consuming, with_remaining, write_c_string, ByteReader, ParseLimits, ReadError,
 };
 
+use std::convert::TryFrom;
+
 // # Structs
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Data contained within an [A2S_PLAYER Response](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format_2)
 pub struct ResponsePlayer {
+    /// Number of players whose information was gathered, includes connecting players
     pub players: u8,
+    /// Data for each player that has fully connected
     pub player_data: Vec<PlayerData>,
 }
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Data about a single player
 pub struct PlayerData {
+    /// Index of the player chunk, Source servers send 0 for every player
     pub index: u8,
+    /// Name of the player
     pub name: String,
+    /// Player's score (usually "frags" or "kills")
     pub score: i32,
+    /// Time (in seconds) the player has been connected to the server
     pub duration: f32,
-    // The ship is special and sends data after the standard fields
+    /// The ship is special and sends data after the standard fields
     pub ship_data: Option<TheShipData>,
 }
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Additional player data sent by [The Ship](https://developer.valvesoftware.com/wiki/The_Ship)
 pub struct TheShipData {
+    /// Player's deaths
     pub deaths: i32,
+    /// Player's money
     pub money: i32,
 }
 
+impl ResponsePlayer {
+    /**
+    Serializes the response into the A2S_PLAYER payload following the 'D' header, the counterpart to [`parse_player`].
+
+    The Ship data is written after the regular player data if any player has it, players without it get 0 deaths and money.
+
+    # Examples
+    ```
+    use a2s_parse::player::{parse_player, PlayerData, ResponsePlayer};
+
+    let response = ResponsePlayer {
+        players: 1,
+        player_data: vec![PlayerData {
+            index: 0,
+            name: "Alice".to_string(),
+            score: 5,
+            duration: 60.0,
+            ship_data: None,
+        }],
+    };
+
+    assert_eq!(response, parse_player(&response.to_bytes()).unwrap());
+    ```
+    */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write(&mut output);
+
+        output
+    }
+
+    /// Checks that [`player_data`](ResponsePlayer::player_data) holds no more players than the player count,
+    /// an empty Vec means the response is consistent. Fewer are fine as connecting players are counted but not listed
+    pub fn validate(&self) -> Vec<Violation> {
+        count_violation("players", self.players.into(), self.player_data.len())
+            .filter(|_| self.player_data.len() > self.players.into())
+            .into_iter()
+            .collect()
+    }
+}
+
 // # Exposed final parser
 // TODO: comment better
-// Returns the player info or an error if the parsing failed or there was remaining data in the input
-pub fn parse_player(input: &[u8]) -> Result<ResponsePlayer, Error<&[u8]>> {
-    match p_player(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+/// Returns the player info or an error if the parsing failed or there was remaining data in the input.
+/// Trailing null bytes are ignored as some server plugins pad their responses, as long as there are too few of them
+/// to be read as another player or The Ship data.
+pub fn parse_player(input: &[u8]) -> Result<ResponsePlayer, A2SError> {
+    parse_player_with_limits(input, &ParseLimits::default())
+}
+
+/// Same as [`parse_player`] enforcing the given limits instead of the default ones,
+/// the player count is checked against [`max_players`](ParseLimits::max_players) before any player is read
+pub fn parse_player_with_limits(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<ResponsePlayer, A2SError> {
+    match all_consuming(input, |reader| p_player(reader, limits)) {
+        Ok(response) => Ok(response),
+        Err(e) => Err(A2SError::from(e).with_field(&[ResponsePlayer::HEADER], input)),
     }
 }
 
+/// Same as [`parse_player`] for a complete datagram starting with the `-1` prefix and 'D' header
+pub fn parse_framed_player(input: &[u8]) -> Result<ResponsePlayer, A2SError> {
+    ResponsePlayer::parse_framed(input)
+}
+
+/// Same as [`parse_player`] but any data after the last player is returned instead of causing an error,
+/// null padding is included in the returned data
+pub fn parse_player_with_remaining(input: &[u8]) -> Result<(ResponsePlayer, Vec<u8>), A2SError> {
+    match with_remaining(input, |reader| player(reader, &ParseLimits::default())) {
+        Ok((response, remaining)) => Ok((response, remaining.to_vec())),
+        Err(e) => Err(A2SError::from(e).with_field(&[ResponsePlayer::HEADER], input)),
+    }
+}
+
+/// Same as [`parse_player_with_limits`] but works around what it can instead of failing, see
+/// [`parse_lenient`](crate::conformance::parse_lenient). The player count and names over the limits are clamped
+pub fn parse_player_lenient(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<(ResponsePlayer, Vec<ParseWarning>), A2SError> {
+    parse_with_warnings(ResponsePlayer::HEADER, input, |reader| {
+        p_player(reader, limits)
+    })
+}
+
 // # Private parsing helper functions
-// Makes sure that all of the input data was consumed, if not to much data was fed or something
-pub fn p_player(input: &[u8]) -> IResult<&[u8], ResponsePlayer> {
-    all_consuming(player)(input)
+/// Reads the players and skips any null padding after them, run with `all_consuming` so too much data fails the parse
+fn p_player(reader: &mut ByteReader, limits: &ParseLimits) -> Result<ResponsePlayer, ReadError> {
+    let response = player(reader, limits)?;
+    reader.null_padding();
+
+    Ok(response)
 }
 
 // Does the bulk of the parsing
-fn player(input: &[u8]) -> IResult<&[u8], ResponsePlayer> {
-    let (input, players) = le_u8(input)?;
-    let (input, mut player_data) = many_player_data(input, players)?;
+fn player(reader: &mut ByteReader, limits: &ParseLimits) -> Result<ResponsePlayer, ReadError> {
+    let count_offset = reader.offset();
+    let players = reader.u8()?;
+    let players = reader.limit(players as usize, limits.max_players, count_offset)? as u8;
+    let mut player_data = many_player_data(reader, players, limits)?;
 
     // The Ship adds fields after the regular player data
-    let (input, ship_data) = many_the_ship_data(input, players)?;
+    let ship_data = many_the_ship_data(reader, players)?;
 
     // If there is ship data, add it to already collected player data
     if !ship_data.is_empty() {
@@ -65,47 +160,91 @@ fn player(input: &[u8]) -> IResult<&[u8], ResponsePlayer> {
             });
     }
 
-    Ok((
-        input,
-        ResponsePlayer {
-            players,
-            player_data,
-        },
-    ))
+    Ok(ResponsePlayer {
+        players,
+        player_data,
+    })
 }
 
-// Uses many_m_n over count as connecting players are included in the players count but no data is stored.
-fn many_player_data(input: &[u8], player_count: u8) -> IResult<&[u8], Vec<PlayerData>> {
-    many_m_n(0, player_count as usize, player_data)(input)
+// Reads up to count players as connecting players are included in the players count but no data is stored.
+fn many_player_data(
+    reader: &mut ByteReader,
+    player_count: u8,
+    limits: &ParseLimits,
+) -> Result<Vec<PlayerData>, ReadError> {
+    reader.many(player_count as usize, |reader| player_data(reader, limits))
 }
 
-fn player_data(input: &[u8]) -> IResult<&[u8], PlayerData> {
-    let (input, index) = le_u8(input)?;
-    let (input, name) = c_string(input)?;
-    let (input, score) = le_i32(input)?;
-    let (input, duration) = le_f32(input)?;
+fn player_data(reader: &mut ByteReader, limits: &ParseLimits) -> Result<PlayerData, ReadError> {
+    let index = reader.u8()?;
+    let name = reader.limited_c_string(limits.max_string_len)?;
+    let score = reader.i32()?;
+    let duration = reader.f32()?;
 
-    Ok((
-        input,
-        PlayerData {
-            index,
-            name,
-            score,
-            duration,
-            ship_data: None,
-        },
-    ))
+    Ok(PlayerData {
+        index,
+        name,
+        score,
+        duration,
+        ship_data: None,
+    })
 }
 
-fn many_the_ship_data(input: &[u8], players: u8) -> IResult<&[u8], Vec<TheShipData>> {
-    many_m_n(0, players as usize, ship_data)(input)
+fn many_the_ship_data(reader: &mut ByteReader, players: u8) -> Result<Vec<TheShipData>, ReadError> {
+    reader.many(players as usize, ship_data)
 }
 
-fn ship_data(input: &[u8]) -> IResult<&[u8], TheShipData> {
-    let (input, deaths) = le_i32(input)?;
-    let (input, money) = le_i32(input)?;
+fn ship_data(reader: &mut ByteReader) -> Result<TheShipData, ReadError> {
+    let deaths = reader.i32()?;
+    let money = reader.i32()?;
 
-    Ok((input, TheShipData { deaths, money }))
+    Ok(TheShipData { deaths, money })
+}
+
+// # Message implementation
+impl A2sMessage for ResponsePlayer {
+    const HEADER: u8 = 0x44;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_player(input)
+    }
+
+    fn parse_lenient(input: &[u8]) -> Result<(Self, Vec<ParseWarning>), A2SError> {
+        parse_player_lenient(input, &ParseLimits::default())
+    }
+
+    /// If any player has The Ship data it is written for every player, players without it are written as 0 deaths and money
+    fn write(&self, output: &mut Vec<u8>) {
+        output.push(self.players);
+
+        for player in &self.player_data {
+            output.push(player.index);
+            write_c_string(output, &player.name);
+            output.extend_from_slice(&player.score.to_le_bytes());
+            output.extend_from_slice(&player.duration.to_le_bytes());
+        }
+
+        // The Ship sends its data after all of the regular player data
+        if self.player_data.iter().any(|p| p.ship_data.is_some()) {
+            for player in &self.player_data {
+                let (deaths, money) = match &player.ship_data {
+                    Some(ship) => (ship.deaths, ship.money),
+                    None => (0, 0),
+                };
+                output.extend_from_slice(&deaths.to_le_bytes());
+                output.extend_from_slice(&money.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Parses the payload following the header byte, same as [`parse_player`]
+impl<'a> TryFrom<&'a [u8]> for ResponsePlayer {
+    type Error = A2SError;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_player(input)
+    }
 }
 
 // # Test
@@ -140,7 +279,8 @@ fn two_player() {
     ];
 
     assert_eq!(2, response.players);
-    assert_eq!(expected_players, response.player_data)
+    assert_eq!(expected_players, response.player_data);
+    assert_eq!(Ok(response), ResponsePlayer::try_from(&player[..]));
 }
 
 #[test]
@@ -164,6 +304,8 @@ fn connecting_player() {
 
     assert_eq!(2, response.players);
     assert_eq!(expected_player, response.player_data);
+    // The connecting player is counted but not listed
+    assert!(response.validate().is_empty());
 }
 
 #[test]
@@ -240,3 +382,137 @@ fn the_ship_player_data() {
     assert_eq!(6, response.players);
     assert_eq!(expected_players, response.player_data);
 }
+
+#[test]
+fn the_ship_round_trip() {
+    // Two players followed by The Ship data for each of them
+    let payload: [u8; 39] = [
+        0x02, 0x00, 0x61, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0xBF, 0x01, 0x62, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x03, 0x00, 0x00, 0x00, 0xC4, 0x09, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xE8, 0x03, 0x00, 0x00,
+    ];
+
+    let response = ResponsePlayer::parse(&payload).unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output);
+
+    assert_eq!(
+        Some(TheShipData {
+            deaths: 3,
+            money: 2500
+        }),
+        response.player_data[0].ship_data
+    );
+    assert_eq!(&payload[..], &output[..]);
+    assert_eq!(output, response.to_bytes());
+}
+
+#[test]
+fn null_padded_player() {
+    // One player followed by three bytes of padding
+    let payload: [u8; 15] = [
+        0x01, 0x00, 0x61, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00,
+    ];
+
+    let response = parse_player(&payload).unwrap();
+
+    assert_eq!(1, response.player_data.len());
+    assert_eq!(None, response.player_data[0].ship_data);
+    assert!(parse_player(&[
+        0x01, 0x00, 0x61, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x00, 0x01
+    ])
+    .is_err());
+}
+
+#[test]
+fn retained_header_skipped() {
+    // 'D' followed by a response with no players
+    let payload: [u8; 2] = [0x44, 0x00];
+
+    assert_eq!(
+        0,
+        ResponsePlayer::parse_skipping_header(&payload)
+            .unwrap()
+            .players
+    );
+    // Parsed as is the header is read as the player count
+    assert_eq!(68, ResponsePlayer::parse(&payload).unwrap().players);
+}
+
+#[test]
+fn player_with_remaining() {
+    // No players followed by padding and a stray byte
+    let payload: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+
+    let (response, remaining) = parse_player_with_remaining(&payload).unwrap();
+
+    assert!(parse_player(&payload).is_err());
+    assert_eq!(0, response.players);
+    assert_eq!(vec![0x00, 0x00, 0x01], remaining);
+}
+
+#[test]
+fn player_name_over_limit() {
+    use crate::parser_util::ParseLimits;
+
+    let payload: [u8; 13] = [
+        0x01, 0x00, 0x41, 0x42, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F,
+    ];
+    let limits = ParseLimits {
+        max_string_len: 1,
+        ..ParseLimits::default()
+    };
+
+    assert!(parse_player(&payload).is_ok());
+    assert_eq!(
+        Err(A2SError::LimitExceeded {
+            offset: 2,
+            field: Some("player[0].name".to_string())
+        }),
+        parse_player_with_limits(&payload, &limits)
+    );
+    assert!(matches!(
+        parse_player_with_limits(
+            &payload,
+            &ParseLimits {
+                max_players: 0,
+                ..limits
+            }
+        ),
+        Err(A2SError::LimitExceeded { offset: 0, .. })
+    ));
+}
+
+#[test]
+fn lenient_player_over_limit() {
+    // Two players, the first named "AB"
+    let payload: [u8; 24] = [
+        0x02, 0x00, 0x41, 0x42, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F, 0x01, 0x43,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F,
+    ];
+    let limits = ParseLimits {
+        max_string_len: 1,
+        max_players: 1,
+        ..ParseLimits::default()
+    };
+
+    assert!(parse_player_with_limits(&payload, &limits).is_err());
+    let (response, warnings) = parse_player_lenient(&payload, &limits).unwrap();
+
+    assert_eq!(1, response.players);
+    assert_eq!(1, response.player_data.len());
+    assert_eq!("A", response.player_data[0].name);
+    assert_eq!(
+        vec![
+            ParseWarning::Clamped {
+                field: Some("players".to_string()),
+                offset: 0
+            },
+            ParseWarning::Clamped {
+                field: Some("player[0].name".to_string()),
+                offset: 2
+            },
+        ],
+        warnings
+    );
+}
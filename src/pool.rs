@@ -0,0 +1,592 @@
+use crate::backoff::Backoff;
+use crate::cache::ResponseCache;
+use crate::client::{is_port_closed, ClientError, QueryOptions, BUFFER_SIZE};
+use crate::clock::Clock;
+use crate::info_source::SourceResponseInfo;
+use crate::message::A2sMessage;
+use crate::observer::QueryObserver;
+use crate::pcap::Capture;
+use crate::player::ResponsePlayer;
+use crate::rate_limit::RateLimiter;
+use crate::rules::ResponseRule;
+use crate::schedule::{InputOrder, Schedule};
+use crate::session::{Event, QuerySession};
+
+use std::collections::hash_map::{DefaultHasher, Entry, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time to wait between polls of the sockets when nothing was received
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// # Structs
+/**
+Queries many servers at once over a small set of shared sockets.
+
+Every server is assigned one of the sockets as picked by the [`SocketAssignment`], responses are routed back to the pending
+query by the address they came from. Bind the sockets to several local addresses with [`QueryPool::with_addresses`] to
+spread the queries over the source IPs of a multi-homed host. The requests are sent in the order of the servers,
+set a [`Schedule`] with [`QueryPool::with_schedule`] to interleave hosting ranges instead.
+Challenges, retries after timeouts and split responses are handled for each server as [`A2SClient`](crate::client::A2SClient) does,
+the backoff of the [`QueryOptions`] is shared by all servers. Requests held back by the rate limit are sent once it allows.
+The sockets aren't connected to any server, most platforms don't report closed ports to them so those queries time out
+instead of failing with [`ClientError::PortClosed`]. Query single servers with an [`A2SClient`](crate::client::A2SClient)
+to tell closed ports apart.
+
+# Examples
+```no_run
+use a2s_parse::pool::QueryPool;
+
+let pool = QueryPool::new(4)?;
+let servers = ["127.0.0.1:27015".parse()?, "127.0.0.1:27016".parse()?];
+
+for (server, info) in servers.iter().zip(pool.info(&servers)) {
+    match info {
+        Ok(info) => println!("{}: {}", server, info.name),
+        Err(e) => println!("{}: {}", server, e),
+    }
+}
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug)]
+pub struct QueryPool {
+    sockets_v4: Vec<UdpSocket>,
+    sockets_v6: Vec<UdpSocket>,
+    assignment: SocketAssignment,
+    schedule: Box<dyn Schedule>,
+    /// Next socket assigned round-robin
+    next_socket: AtomicUsize,
+    timeout: Duration,
+    retries: u32,
+    backoff: Mutex<Box<dyn Backoff>>,
+    clock: Arc<dyn Clock>,
+    rate_limit: Option<Arc<RateLimiter>>,
+    cache: Option<Arc<ResponseCache>>,
+    capture: Option<Arc<dyn Capture>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How a [`QueryPool`] picks the socket each server is queried from
+pub enum SocketAssignment {
+    /// Each server gets the next socket in turn, spreading the queries evenly over all sockets
+    RoundRobin,
+    /// Servers are assigned by a hash of their IP, so every query of a host is sent from the same socket.
+    /// Hosts that rate limit per source address see a single steady source instead of all of them
+    HashByDestination,
+}
+
+/// State of the query of one server
+struct Pending<T> {
+    server: SocketAddr,
+    socket: usize,
+    session: QuerySession<T>,
+    retries: u32,
+    /// Set when the request has to be sent
+    send_at: Option<Instant>,
+    deadline: Instant,
+    result: Option<Result<T, ClientError>>,
+}
+
+impl QueryPool {
+    /// Same as [`QueryPool::with_options`] with the default [`QueryOptions`]
+    pub fn new(sockets: usize) -> io::Result<Self> {
+        QueryPool::with_options(sockets, QueryOptions::default())
+    }
+
+    /// Binds `sockets` IPv4 sockets and as many IPv6 sockets, at least one of each, assigned round-robin.
+    /// The IPv6 sockets are left out if the host doesn't support IPv6, queries of IPv6 servers then fail
+    pub fn with_options(sockets: usize, options: QueryOptions) -> io::Result<Self> {
+        let sockets = sockets.max(1);
+        let sockets_v4 = (0..sockets)
+            .map(|_| bind((Ipv4Addr::UNSPECIFIED, 0).into()))
+            .collect::<io::Result<_>>()?;
+        let sockets_v6 = (0..sockets)
+            .map(|_| bind((Ipv6Addr::UNSPECIFIED, 0).into()))
+            .collect::<io::Result<_>>()
+            .unwrap_or_default();
+
+        Ok(QueryPool::from_sockets(
+            sockets_v4,
+            sockets_v6,
+            SocketAssignment::RoundRobin,
+            options,
+        ))
+    }
+
+    /**
+    Binds a socket to each local address, servers are queried from the sockets of their IP version picked by the assignment.
+
+    Bind to the source IPs of a multi-homed host to stay under the per-source rate limits of hosts, list an address
+    several times with port 0 for several sockets on it. Queries of servers whose IP version has no socket fail with
+    [`io::ErrorKind::AddrNotAvailable`].
+
+    # Examples
+    ```no_run
+    use a2s_parse::client::QueryOptions;
+    use a2s_parse::pool::{QueryPool, SocketAssignment};
+
+    let addresses = ["192.0.2.10:0".parse()?, "192.0.2.11:0".parse()?];
+    let pool = QueryPool::with_addresses(
+        &addresses,
+        SocketAssignment::HashByDestination,
+        QueryOptions::default(),
+    )?;
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    pub fn with_addresses(
+        addresses: &[SocketAddr],
+        assignment: SocketAssignment,
+        options: QueryOptions,
+    ) -> io::Result<Self> {
+        let mut sockets_v4 = Vec::new();
+        let mut sockets_v6 = Vec::new();
+        for &address in addresses {
+            match address {
+                SocketAddr::V4(_) => sockets_v4.push(bind(address)?),
+                SocketAddr::V6(_) => sockets_v6.push(bind(address)?),
+            }
+        }
+
+        Ok(QueryPool::from_sockets(
+            sockets_v4, sockets_v6, assignment, options,
+        ))
+    }
+
+    /// Creates the pool sending from the bound sockets
+    fn from_sockets(
+        sockets_v4: Vec<UdpSocket>,
+        sockets_v6: Vec<UdpSocket>,
+        assignment: SocketAssignment,
+        options: QueryOptions,
+    ) -> Self {
+        QueryPool {
+            sockets_v4,
+            sockets_v6,
+            assignment,
+            schedule: Box::new(InputOrder),
+            next_socket: AtomicUsize::new(0),
+            timeout: options.timeout,
+            retries: options.retries,
+            backoff: Mutex::new(options.backoff),
+            clock: options.clock,
+            rate_limit: options.rate_limit,
+            cache: options.cache,
+            capture: options.capture,
+            observer: options.observer,
+        }
+    }
+
+    /// Sends the requests in the order picked by the schedule rather than the order of the servers
+    pub fn with_schedule(mut self, schedule: impl Schedule + 'static) -> Self {
+        self.schedule = Box::new(schedule);
+        self
+    }
+
+    /// Local addresses of the sockets, the IPv4 ones first
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.sockets_v4
+            .iter()
+            .chain(&self.sockets_v6)
+            .map(UdpSocket::local_addr)
+            .collect()
+    }
+
+    /// Queries A2S_INFO of every server, the results are in the same order as the servers
+    pub fn info(&self, servers: &[SocketAddr]) -> Vec<Result<SourceResponseInfo, ClientError>> {
+        self.query(servers, QuerySession::info)
+    }
+
+    /// Queries A2S_PLAYER of every server, the results are in the same order as the servers
+    pub fn players(&self, servers: &[SocketAddr]) -> Vec<Result<ResponsePlayer, ClientError>> {
+        self.query(servers, QuerySession::players)
+    }
+
+    /// Queries A2S_RULES of every server, the results are in the same order as the servers
+    pub fn rules(&self, servers: &[SocketAddr]) -> Vec<Result<ResponseRule, ClientError>> {
+        self.query(servers, QuerySession::rules)
+    }
+
+    /// Sends the requests to all servers in the order of the schedule and polls the sockets until every query is answered
+    /// or timed out.
+    /// A server listed more than once is only queried for its first entry, the others fail with [`io::ErrorKind::InvalidInput`].
+    /// Servers with a fresh cached response aren't queried
+    fn query<T: A2sMessage + Clone + Send + Sync + 'static>(
+        &self,
+        servers: &[SocketAddr],
+        session: impl Fn() -> QuerySession<T>,
+    ) -> Vec<Result<T, ClientError>> {
+        let mut backoff = self.backoff.lock().unwrap_or_else(|e| e.into_inner());
+        backoff.reset();
+
+        let start = self.clock.now();
+        let mut routes = HashMap::new();
+        let mut pending: Vec<Pending<T>> = servers
+            .iter()
+            .enumerate()
+            .map(|(index, &server)| {
+                let sockets = self.sockets(&server);
+                let cached = self.cache.as_ref().and_then(|cache| cache.get(server));
+                let result = match routes.entry(server) {
+                    Entry::Occupied(_) => Some(Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "server is listed more than once",
+                    )
+                    .into())),
+                    Entry::Vacant(_) if cached.is_some() => cached.map(Ok),
+                    Entry::Vacant(_) if sockets.is_empty() => {
+                        Some(Err(io::Error::from(io::ErrorKind::AddrNotAvailable).into()))
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(index);
+                        None
+                    }
+                };
+
+                Pending {
+                    server,
+                    socket: self.assign(&server, sockets.len()),
+                    session: session(),
+                    retries: self.retries,
+                    send_at: Some(start),
+                    deadline: start,
+                    result,
+                }
+            })
+            .collect();
+
+        let order = self.schedule.order(servers);
+        let mut buffer = [0; BUFFER_SIZE];
+        while pending.iter().any(|query| query.result.is_none()) {
+            let now = self.clock.now();
+
+            for &index in &order {
+                let query = &mut pending[index];
+                if query.result.is_some() {
+                    continue;
+                }
+                match query.send_at {
+                    Some(send_at) if send_at <= now => {
+                        if let Some(limiter) = &self.rate_limit {
+                            if !limiter.try_acquire() {
+                                continue;
+                            }
+                        }
+                        let socket = &self.sockets(&query.server)[query.socket];
+                        let request = query.session.transmit().data;
+                        match socket.send_to(&request, query.server) {
+                            Ok(_) => {
+                                self.capture(socket, |capture, local| {
+                                    capture.sent(local, query.server, &request)
+                                });
+                                if let Some(observer) = &self.observer {
+                                    observer.on_send(Some(query.server), &request);
+                                }
+                                query.send_at = None;
+                                query.deadline = now + self.timeout;
+                            }
+                            Err(e) => query.result = Some(Err(e.into())),
+                        }
+                    }
+                    None if query.deadline <= now => match query.retries {
+                        0 => {
+                            query.result =
+                                Some(Err(io::Error::from(io::ErrorKind::TimedOut).into()))
+                        }
+                        _ => {
+                            query.retries -= 1;
+                            let delay = backoff.next_delay();
+                            if let Some(observer) = &self.observer {
+                                observer.on_retry(Some(query.server), delay);
+                            }
+                            query.send_at = Some(now + delay);
+                        }
+                    },
+                    _ => {}
+                }
+            }
+
+            let mut received_any = false;
+            for socket in self.sockets_v4.iter().chain(&self.sockets_v6) {
+                loop {
+                    match socket.recv_from(&mut buffer) {
+                        Ok((received, peer)) => {
+                            received_any = true;
+                            self.capture(socket, |capture, local| {
+                                capture.received(local, peer, &buffer[..received])
+                            });
+                            if let Some(observer) = &self.observer {
+                                observer.on_receive(Some(peer), &buffer[..received]);
+                            }
+                            if let Some(&index) = routes.get(&peer) {
+                                receive(&mut pending[index], &buffer[..received], now);
+                            }
+                        }
+                        Err(e) if is_port_closed(&e) => self.port_closed(&mut pending, socket),
+                        Err(_) => break,
+                    }
+                }
+            }
+
+            if !received_any {
+                self.clock.sleep(POLL_INTERVAL);
+            }
+        }
+
+        if let Some(cache) = &self.cache {
+            for (&server, &index) in &routes {
+                if let Some(Ok(response)) = &pending[index].result {
+                    cache.insert(server, response.clone());
+                }
+            }
+        }
+        if let Some(observer) = &self.observer {
+            for query in &pending {
+                if let Some(Err(ClientError::Parse(e))) = &query.result {
+                    observer.on_parse_error(Some(query.server), e);
+                }
+            }
+        }
+
+        pending
+            .into_iter()
+            .filter_map(|query| query.result)
+            .collect()
+    }
+
+    /// Sockets of the same IP version as the server
+    fn sockets(&self, server: &SocketAddr) -> &[UdpSocket] {
+        match server {
+            SocketAddr::V4(_) => &self.sockets_v4,
+            SocketAddr::V6(_) => &self.sockets_v6,
+        }
+    }
+
+    /// Index of the socket the server is queried from out of the `sockets` of its IP version
+    fn assign(&self, server: &SocketAddr, sockets: usize) -> usize {
+        let pick = match self.assignment {
+            SocketAssignment::RoundRobin => self.next_socket.fetch_add(1, Ordering::Relaxed),
+            SocketAssignment::HashByDestination => {
+                let mut hasher = DefaultHasher::new();
+                server.ip().hash(&mut hasher);
+                hasher.finish() as usize
+            }
+        };
+
+        pick % sockets.max(1)
+    }
+
+    /// Hands a datagram sent or received on the socket to the capture if there is one
+    fn capture(&self, socket: &UdpSocket, record: impl FnOnce(&dyn Capture, SocketAddr)) {
+        if let Some(capture) = &self.capture {
+            if let Ok(local) = socket.local_addr() {
+                record(capture.as_ref(), local);
+            }
+        }
+    }
+
+    /// Fails the query waiting on the socket with [`ClientError::PortClosed`]. The error of an unconnected socket doesn't
+    /// tell which server refused, so it is only attributed if a single query is waiting for a response on the socket,
+    /// the others time out
+    fn port_closed<T>(&self, pending: &mut [Pending<T>], socket: &UdpSocket) {
+        let mut waiting = pending.iter_mut().filter(|query| {
+            query.result.is_none()
+                && query.send_at.is_none()
+                && std::ptr::eq(&self.sockets(&query.server)[query.socket], socket)
+        });
+
+        if let (Some(query), None) = (waiting.next(), waiting.next()) {
+            query.result = Some(Err(ClientError::PortClosed));
+        }
+    }
+}
+
+// # Private helper functions
+/// Binds a non-blocking socket so all sockets can be polled from one thread
+fn bind(address: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(address)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket)
+}
+
+/// Handles a datagram received from the server of the query
+fn receive<T: A2sMessage>(query: &mut Pending<T>, datagram: &[u8], now: Instant) {
+    match query.session.handle_datagram(datagram) {
+        // Resent from the send loop so the rate limit applies
+        Event::Transmit(_) => query.send_at = Some(now),
+        Event::Pending => {}
+        Event::Response(response) => query.result = Some(Ok(response)),
+        Event::Error(e) => query.result = Some(Err(e)),
+    }
+}
+
+// # Tests
+#[test]
+fn query_many_servers() {
+    use crate::challenge::build_challenge_response;
+    use crate::requests::build_player_request;
+
+    let players = ResponsePlayer {
+        players: 0,
+        player_data: Vec::new(),
+    };
+    let servers: Vec<UdpSocket> = (0..2)
+        .map(|_| UdpSocket::bind("127.0.0.1:0").unwrap())
+        .collect();
+    // Nobody answers on this one
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut addresses: Vec<SocketAddr> = servers
+        .iter()
+        .chain(Some(&silent))
+        .map(|server| server.local_addr().unwrap())
+        .collect();
+    addresses.push(addresses[0]);
+
+    let handles: Vec<_> = servers
+        .into_iter()
+        .enumerate()
+        .map(|(index, server)| {
+            let response = players.to_framed_bytes();
+            std::thread::spawn(move || {
+                let mut buffer = [0; BUFFER_SIZE];
+                let (_, peer) = server.recv_from(&mut buffer).unwrap();
+                if index == 1 {
+                    server.send_to(&build_challenge_response(9), peer).unwrap();
+                    let (received, _) = server.recv_from(&mut buffer).unwrap();
+                    assert_eq!(build_player_request(9), buffer[..received]);
+                }
+                server.send_to(&response, peer).unwrap();
+                server
+            })
+        })
+        .collect();
+
+    let options = QueryOptions {
+        timeout: Duration::from_millis(100),
+        retries: 0,
+        ..QueryOptions::default()
+    };
+    let pool = QueryPool::with_options(2, options).unwrap();
+    let results = pool.players(&addresses);
+
+    assert_eq!(&players, results[0].as_ref().unwrap());
+    assert_eq!(&players, results[1].as_ref().unwrap());
+    assert!(matches!(&results[2], Err(ClientError::Io(e)) if e.kind() == io::ErrorKind::TimedOut));
+    assert!(
+        matches!(&results[3], Err(ClientError::Io(e)) if e.kind() == io::ErrorKind::InvalidInput)
+    );
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn scheduled_requests() {
+    use crate::schedule::Interleave;
+
+    /// Lists the servers requests were sent to
+    #[derive(Debug, Default)]
+    struct Sent(Mutex<Vec<SocketAddr>>);
+
+    impl Capture for Sent {
+        fn sent(&self, _local: SocketAddr, server: SocketAddr, _datagram: &[u8]) {
+            self.0.lock().unwrap().push(server);
+        }
+
+        fn received(&self, _local: SocketAddr, _server: SocketAddr, _datagram: &[u8]) {}
+    }
+
+    // Never answer
+    let servers = [
+        UdpSocket::bind("127.0.0.1:0").unwrap(),
+        UdpSocket::bind("127.0.0.1:0").unwrap(),
+        UdpSocket::bind("[::1]:0").unwrap(),
+    ];
+    let addresses: Vec<SocketAddr> = servers
+        .iter()
+        .map(|server| server.local_addr().unwrap())
+        .collect();
+
+    let sent = Arc::new(Sent::default());
+    let options = QueryOptions {
+        timeout: Duration::from_millis(10),
+        retries: 0,
+        capture: Some(sent.clone()),
+        ..QueryOptions::default()
+    };
+    let pool = QueryPool::with_options(1, options)
+        .unwrap()
+        .with_schedule(Interleave::Host);
+
+    assert!(pool.info(&addresses).iter().all(Result::is_err));
+    // The second server of 127.0.0.1 waits for the IPv6 one
+    assert_eq!(
+        vec![addresses[0], addresses[2], addresses[1]],
+        *sent.0.lock().unwrap()
+    );
+}
+
+#[test]
+fn assign_sockets() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().build().to_framed_bytes();
+    // Servers answering every query with the info, returning the addresses the queries came from
+    let serve = |queries: usize| {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let info = info.clone();
+        let handle = std::thread::spawn(move || {
+            let mut buffer = [0; BUFFER_SIZE];
+            (0..queries)
+                .map(|_| {
+                    let (_, peer) = server.recv_from(&mut buffer).unwrap();
+                    server.send_to(&info, peer).unwrap();
+                    peer
+                })
+                .collect::<Vec<_>>()
+        });
+        (address, handle)
+    };
+    let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+    let pool = QueryPool::with_addresses(
+        &[local, local],
+        SocketAssignment::RoundRobin,
+        QueryOptions::default(),
+    )
+    .unwrap();
+    let (first, first_peers) = serve(2);
+    let (second, second_peers) = serve(2);
+    assert!(pool.info(&[first, second]).iter().all(Result::is_ok));
+    assert!(pool.info(&[second, first]).iter().all(Result::is_ok));
+
+    // Each server is queried from both sockets in turn
+    let mut peers = first_peers.join().unwrap();
+    peers.sort();
+    let mut expected = pool.local_addrs().unwrap();
+    expected.sort();
+    assert_eq!(expected, peers);
+    second_peers.join().unwrap();
+
+    let pool = QueryPool::with_addresses(
+        &[local, local],
+        SocketAssignment::HashByDestination,
+        QueryOptions::default(),
+    )
+    .unwrap();
+    let (first, first_peers) = serve(1);
+    let (second, second_peers) = serve(1);
+    assert!(pool.info(&[first, second]).iter().all(Result::is_ok));
+
+    // Both servers share an IP so they share a socket
+    assert_eq!(first_peers.join().unwrap(), second_peers.join().unwrap());
+    assert!(pool.info(&["[::1]:27015".parse().unwrap()])[0].is_err());
+}
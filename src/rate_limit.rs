@@ -0,0 +1,116 @@
+use crate::clock::{Clock, SystemClock};
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// # Structs
+/**
+Token bucket limiting how many requests are sent per second.
+
+Holds up to `burst` tokens and refills `per_second` tokens every second, every request takes one.
+Share one limiter between clients and pools through [`QueryOptions`](crate::client::QueryOptions) to limit all of them together.
+
+# Examples
+```
+use a2s_parse::clock::{Clock, ManualClock};
+use a2s_parse::rate_limit::RateLimiter;
+use std::sync::Arc;
+use std::time::Duration;
+
+let clock = ManualClock::new();
+let limiter = RateLimiter::with_clock(10, 2, Arc::new(clock.clone()));
+
+assert!(limiter.try_acquire());
+assert!(limiter.try_acquire());
+assert!(!limiter.try_acquire());
+
+clock.advance(Duration::from_millis(100));
+assert!(limiter.try_acquire());
+```
+*/
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: u32,
+    burst: u32,
+    clock: Arc<dyn Clock>,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allows `per_second` requests per second on average and up to `burst` at once, at least one of each
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        RateLimiter::with_clock(per_second, burst, Arc::new(SystemClock))
+    }
+
+    /// Same as [`RateLimiter::new`] measuring time with the clock
+    pub fn with_clock(per_second: u32, burst: u32, clock: Arc<dyn Clock>) -> Self {
+        let burst = burst.max(1);
+        let bucket = Bucket {
+            tokens: f64::from(burst),
+            updated: clock.now(),
+        };
+
+        RateLimiter {
+            per_second: per_second.max(1),
+            burst,
+            clock,
+            bucket: Mutex::new(bucket),
+        }
+    }
+
+    /// Takes a token if one is available, returns false if the request has to wait
+    pub fn try_acquire(&self) -> bool {
+        self.take() == Duration::from_secs(0)
+    }
+
+    /// Takes a token, blocking until one is available
+    pub fn acquire(&self) {
+        loop {
+            match self.take() {
+                wait if wait == Duration::from_secs(0) => return,
+                wait => self.clock.sleep(wait),
+            }
+        }
+    }
+
+    /// Refills the bucket and takes a token, returns zero if one was taken or else the time until the next token
+    pub(crate) fn take(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        let per_second = f64::from(self.per_second);
+
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * per_second).min(f64::from(self.burst));
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / per_second)
+        }
+    }
+}
+
+// # Tests
+#[test]
+fn acquire_waits_for_token() {
+    use crate::clock::ManualClock;
+
+    let clock = ManualClock::new();
+    let start = clock.now();
+    let limiter = RateLimiter::with_clock(4, 1, Arc::new(clock.clone()));
+
+    limiter.acquire();
+    limiter.acquire();
+    limiter.acquire();
+
+    assert_eq!(Duration::from_millis(500), clock.now() - start);
+}
@@ -1,47 +1,303 @@
-use nom::{error::Error, number::complete::le_i32, Finish, IResult};
-
-use crate::parser_util::c_string;
+use crate::challenge::{append_challenge, challenge, Challenge};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::packet::{parse_framing, Framing};
+use crate::parser_util::{with_remaining, write_c_string, ByteReader, ReadError};
 
 // TODO:
 
 // # Structs
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [A2S_INFO Request](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format)
 pub struct InfoRequest {
+    /// Should always be "Source Engine Query"
     pub payload: String,
-    pub challenge: i32,
+    /// Challenge value, None if the request was sent without one
+    pub challenge: Option<i32>,
 }
 // All but the info request are generic in just having a header and a challenge value
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ChallengeRequest {
-    challenge: i32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [A2S_PLAYER Request](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format_2)
+pub struct PlayerRequest {
+    /// Challenge value, -1 to request a challenge
+    pub challenge: i32,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [A2S_RULES Request](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format_3)
+pub struct RulesRequest {
+    /// Challenge value, -1 to request a challenge
+    pub challenge: i32,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [A2A_PING Request](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format_4), contains no payload
+pub struct PingRequest;
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// [A2S_SERVERQUERY_GETCHALLENGE Request](https://developer.valvesoftware.com/wiki/Server_queries#Request_Format_5), contains no payload
+pub struct GetChallengeRequest;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Any request a client sends, returned by [`parse_request`]
+pub enum Request {
+    /// A2S_INFO request
+    Info(InfoRequest),
+    /// A2S_PLAYER request
+    Players(PlayerRequest),
+    /// A2S_RULES request
+    Rules(RulesRequest),
+    /// A2A_PING request
+    Ping(PingRequest),
+    /// A2S_SERVERQUERY_GETCHALLENGE request
+    GetChallenge(GetChallengeRequest),
 }
 
 // # Added Parsing requests for completeness, only challenge request is likely to be used
 // Info may have additional info after the defined fields so it is also returned
 // TODO: take a look at these once full match parsing implemented
-pub fn parse_info_request(input: &[u8]) -> Result<(&[u8], InfoRequest), Error<&[u8]>> {
-    p_info_request(input).finish()
+/// Parses an A2S_INFO request, any data after the challenge is returned alongside the request
+pub fn parse_info_request(input: &[u8]) -> Result<(&[u8], InfoRequest), A2SError> {
+    with_remaining(input, p_info_request)
+        .map(|(request, remaining)| (remaining, request))
+        .map_err(|e| A2SError::from(e).with_field(&[InfoRequest::HEADER], input))
+}
+
+/// Parses an A2S_PLAYER request
+pub fn parse_player_request(input: &[u8]) -> Result<PlayerRequest, A2SError> {
+    match p_challenge(&mut ByteReader::new(input)) {
+        Ok(challenge) => Ok(PlayerRequest { challenge }),
+        Err(e) => Err(A2SError::from(e).with_field(&[PlayerRequest::HEADER], input)),
+    }
+}
+
+/// Parses an A2S_RULES request
+pub fn parse_rules_request(input: &[u8]) -> Result<RulesRequest, A2SError> {
+    match p_challenge(&mut ByteReader::new(input)) {
+        Ok(challenge) => Ok(RulesRequest { challenge }),
+        Err(e) => Err(A2SError::from(e).with_field(&[RulesRequest::HEADER], input)),
+    }
+}
+
+/**
+Parses a complete request datagram as a server receives it, checking the `-1` prefix and dispatching on the header byte.
+Datagrams without the `-1` prefix fail with [`A2SError::InvalidPrefix`], unknown header bytes with [`A2SError::UnexpectedHeader`].
+
+# Examples
+```
+use a2s_parse::requests::{build_player_request, parse_request, PlayerRequest, Request};
+
+let request = parse_request(&build_player_request(-1)).unwrap();
+
+assert_eq!(Request::Players(PlayerRequest { challenge: -1 }), request);
+```
+*/
+pub fn parse_request(input: &[u8]) -> Result<Request, A2SError> {
+    let payload = match parse_framing(input)? {
+        (Framing::Single, payload) => payload,
+        _ => return Err(A2SError::InvalidPrefix),
+    };
+    let (&header, payload) = payload.split_first().ok_or(A2SError::TruncatedField {
+        offset: 4,
+        field: Some("header".to_string()),
+    })?;
+
+    let request = match header {
+        InfoRequest::HEADER => InfoRequest::parse(payload).map(Request::Info),
+        PlayerRequest::HEADER => PlayerRequest::parse(payload).map(Request::Players),
+        RulesRequest::HEADER => RulesRequest::parse(payload).map(Request::Rules),
+        PingRequest::HEADER => PingRequest::parse(payload).map(Request::Ping),
+        GetChallengeRequest::HEADER => {
+            GetChallengeRequest::parse(payload).map(Request::GetChallenge)
+        }
+        _ => Err(A2SError::UnexpectedHeader(header)),
+    };
+    request.map_err(|e| e.offset_by(5))
 }
 
-pub fn parse_player_request(input: &[u8]) -> Result<ChallengeRequest, Error<&[u8]>> {
-    match p_challenge_request(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+// # Request builders
+/// Payload every A2S_INFO request carries
+pub const INFO_PAYLOAD: &str = "Source Engine Query";
+
+/**
+Builds a complete A2S_INFO datagram including the `-1` prefix and 'T' header, ready to be sent.
+The challenge is left out if None, servers that require one answer with a challenge to resend the request with.
+
+# Examples
+```
+use a2s_parse::requests::build_info_request;
+
+let datagram = build_info_request(None);
+
+assert_eq!(b"\xFF\xFF\xFF\xFFTSource Engine Query\0", &datagram[..]);
+```
+*/
+pub fn build_info_request(challenge: Option<i32>) -> Vec<u8> {
+    InfoRequest {
+        payload: INFO_PAYLOAD.to_string(),
+        challenge,
     }
+    .to_framed_bytes()
+}
+
+/// Builds a complete A2S_PLAYER datagram including the `-1` prefix and 'U' header, pass -1 to request a challenge
+pub fn build_player_request(challenge: i32) -> Vec<u8> {
+    PlayerRequest { challenge }.to_framed_bytes()
+}
+
+/// Builds a complete A2S_RULES datagram including the `-1` prefix and 'V' header, pass -1 to request a challenge
+pub fn build_rules_request(challenge: i32) -> Vec<u8> {
+    RulesRequest { challenge }.to_framed_bytes()
 }
 
 // # Parsing functions
-fn p_info_request(input: &[u8]) -> IResult<&[u8], InfoRequest> {
-    let (input, payload) = c_string(input)?;
-    let (input, challenge) = le_i32(input)?;
+fn p_info_request(reader: &mut ByteReader) -> Result<InfoRequest, ReadError> {
+    let payload = reader.c_string()?;
+    // Servers only started requiring a challenge in 2020, older requests end after the payload
+    let challenge = challenge(reader).ok();
 
-    Ok((input, InfoRequest { payload, challenge }))
+    Ok(InfoRequest {
+        payload,
+        challenge: challenge.map(i32::from),
+    })
 }
 
-fn p_challenge_request(input: &[u8]) -> IResult<&[u8], ChallengeRequest> {
-    let (input, challenge) = le_i32(input)?;
+fn p_challenge(reader: &mut ByteReader) -> Result<i32, ReadError> {
+    challenge(reader).map(i32::from)
+}
 
-    Ok((input, ChallengeRequest { challenge }))
+// # Message implementations
+impl A2sMessage for InfoRequest {
+    const HEADER: u8 = 0x54;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_info_request(input).map(|(_, request)| request)
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        write_c_string(output, &self.payload);
+        if let Some(challenge) = self.challenge {
+            append_challenge(output, Challenge(challenge));
+        }
+    }
 }
 
-// TODO: Tests + Implementations
+impl A2sMessage for PlayerRequest {
+    const HEADER: u8 = 0x55;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_player_request(input)
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        append_challenge(output, Challenge(self.challenge));
+    }
+}
+
+impl A2sMessage for RulesRequest {
+    const HEADER: u8 = 0x56;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_rules_request(input)
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        append_challenge(output, Challenge(self.challenge));
+    }
+}
+
+impl A2sMessage for PingRequest {
+    const HEADER: u8 = 0x69;
+
+    fn parse(_input: &[u8]) -> Result<Self, A2SError> {
+        Ok(PingRequest)
+    }
+
+    fn write(&self, _output: &mut Vec<u8>) {}
+}
+
+impl A2sMessage for GetChallengeRequest {
+    const HEADER: u8 = 0x57;
+
+    fn parse(_input: &[u8]) -> Result<Self, A2SError> {
+        Ok(GetChallengeRequest)
+    }
+
+    fn write(&self, _output: &mut Vec<u8>) {}
+}
+
+// # Tests
+#[test]
+fn info_request_round_trip() {
+    let payload: [u8; 24] = [
+        0x53, 0x6F, 0x75, 0x72, 0x63, 0x65, 0x20, 0x45, 0x6E, 0x67, 0x69, 0x6E, 0x65, 0x20, 0x51,
+        0x75, 0x65, 0x72, 0x79, 0x00, 0x0A, 0x08, 0x5E, 0xEA,
+    ];
+
+    let request = InfoRequest::parse(&payload).unwrap();
+    let mut output = Vec::new();
+    request.write(&mut output);
+
+    assert_eq!("Source Engine Query", request.payload);
+    assert_eq!(Some(-362936310), request.challenge);
+    assert_eq!(&payload[..], &output[..]);
+}
+
+#[test]
+fn rules_request() {
+    let payload: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    let request = parse_rules_request(&payload).unwrap();
+
+    assert_eq!(RulesRequest { challenge: -1 }, request);
+}
+
+#[test]
+fn build_requests() {
+    assert_eq!(
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x55, 0xFF, 0xFF, 0xFF, 0xFF],
+        build_player_request(-1)
+    );
+    assert_eq!(
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x56, 0x0A, 0x08, 0x5E, 0xEA],
+        build_rules_request(-362936310)
+    );
+
+    let datagram = build_info_request(Some(-362936310));
+    let request = InfoRequest::parse_framed(&datagram).unwrap();
+    assert_eq!(Some(-362936310), request.challenge);
+    assert_eq!(
+        None,
+        InfoRequest::parse_framed(&build_info_request(None))
+            .unwrap()
+            .challenge
+    );
+}
+
+#[test]
+fn dispatch_requests() {
+    let info = parse_request(&build_info_request(None)).unwrap();
+    let challenge = parse_request(&GetChallengeRequest.to_framed_bytes()).unwrap();
+
+    assert!(matches!(
+        info,
+        Request::Info(InfoRequest {
+            challenge: None,
+            ..
+        })
+    ));
+    assert_eq!(
+        Request::Rules(RulesRequest { challenge: 9 }),
+        parse_request(&build_rules_request(9)).unwrap()
+    );
+    assert_eq!(Request::GetChallenge(GetChallengeRequest), challenge);
+    assert_eq!(
+        Request::Ping(PingRequest),
+        parse_request(&[0xFF, 0xFF, 0xFF, 0xFF, 0x69]).unwrap()
+    );
+    assert!(parse_request(&[0xFF, 0xFF, 0xFF, 0xFF, 0x44]).is_err());
+    assert!(parse_request(&[0x55, 0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+}
@@ -0,0 +1,198 @@
+use crate::challenge::ChallengeResponse;
+use crate::error::A2SError;
+use crate::info_goldsource::GoldSourceResponseInfo;
+use crate::info_source::SourceResponseInfo;
+use crate::message::A2sMessage;
+use crate::packet::{parse_framing, Framing};
+use crate::ping::PingResponse;
+use crate::player::ResponsePlayer;
+use crate::rules::ResponseRule;
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Any response a server sends, returned by [`parse_any`]
+pub enum Response {
+    /// A2S_INFO response of a Source server, 'I'
+    SourceInfo(SourceResponseInfo),
+    /// Obsolete A2S_INFO response of a Gold Source server, 'm'
+    GoldSourceInfo(GoldSourceResponseInfo),
+    /// A2S_PLAYER response, 'D'
+    Players(ResponsePlayer),
+    /// A2S_RULES response, 'E'
+    Rules(ResponseRule),
+    /// Challenge the request has to be resent with, 'A'
+    Challenge(ChallengeResponse),
+    /// A2A_PING response, 'j'
+    Ping(PingResponse),
+    /// One packet of a split response holding the data following the `-2` prefix,
+    /// feed the datagrams to a [`DatagramAssembler`](crate::packet::DatagramAssembler) and parse the assembled response
+    Split(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A2S_INFO response of either engine generation, returned by [`parse_info_auto`]
+pub enum InfoResponse {
+    /// Response in the Source layout
+    Source(SourceResponseInfo),
+    /// Response in the obsolete Gold Source layout
+    GoldSource(GoldSourceResponseInfo),
+}
+
+// # Exposed final parser
+/**
+Parses any datagram a server sends, dispatching on the `-1` or `-2` prefix and the header byte.
+
+Split packets are returned as they are, they only make up a response once reassembled.
+Datagrams without a prefix fail with [`A2SError::InvalidPrefix`], unknown header bytes with [`A2SError::UnexpectedHeader`].
+
+# Examples
+```
+use a2s_parse::response::{parse_any, Response};
+
+match parse_any(&[0xFF, 0xFF, 0xFF, 0xFF, 0x41, 0x0A, 0x00, 0x00, 0x00]).unwrap() {
+    Response::Challenge(challenge) => assert_eq!(10, challenge.challenge),
+    response => panic!("unexpected {:?}", response),
+}
+```
+*/
+pub fn parse_any(input: &[u8]) -> Result<Response, A2SError> {
+    let payload = match parse_framing(input)? {
+        (Framing::Single, payload) => payload,
+        (Framing::Split, packet) => return Ok(Response::Split(packet.to_vec())),
+        (Framing::Unframed, _) => return Err(A2SError::InvalidPrefix),
+    };
+
+    let (&header, payload) = payload.split_first().ok_or(A2SError::TruncatedField {
+        offset: 4,
+        field: Some("header".to_string()),
+    })?;
+    let response = match header {
+        SourceResponseInfo::HEADER => SourceResponseInfo::parse(payload).map(Response::SourceInfo),
+        GoldSourceResponseInfo::HEADER => {
+            GoldSourceResponseInfo::parse(payload).map(Response::GoldSourceInfo)
+        }
+        ResponsePlayer::HEADER => ResponsePlayer::parse(payload).map(Response::Players),
+        ResponseRule::HEADER => ResponseRule::parse(payload).map(Response::Rules),
+        ChallengeResponse::HEADER => ChallengeResponse::parse(payload).map(Response::Challenge),
+        PingResponse::HEADER => PingResponse::parse(payload).map(Response::Ping),
+        _ => Err(A2SError::UnexpectedHeader(header)),
+    };
+    response.map_err(|e| e.offset_by(5))
+}
+
+/**
+Parses an A2S_INFO payload starting with its header byte without knowing the engine generation of the server.
+
+The layout the header byte announces is tried first, 'I' for Source and 'm' for Gold Source, then the other one.
+Payloads with any other header byte aren't info responses and fail with [`A2SError::UnexpectedHeader`].
+The error of the first layout is returned if neither parses.
+
+# Examples
+```
+use a2s_parse::info_source::ServerInfoBuilder;
+use a2s_parse::message::A2sMessage;
+use a2s_parse::response::{parse_info_auto, InfoResponse};
+
+let info = ServerInfoBuilder::new().map("cs_office").build();
+let datagram = info.to_framed_bytes();
+
+assert_eq!(InfoResponse::Source(info), parse_info_auto(&datagram[4..]).unwrap());
+```
+*/
+pub fn parse_info_auto(input: &[u8]) -> Result<InfoResponse, A2SError> {
+    let (&header, payload) = input.split_first().ok_or(A2SError::TruncatedField {
+        offset: 0,
+        field: Some("header".to_string()),
+    })?;
+    let source = || SourceResponseInfo::parse(payload).map(InfoResponse::Source);
+    let goldsource = || GoldSourceResponseInfo::parse(payload).map(InfoResponse::GoldSource);
+
+    let info = match header {
+        SourceResponseInfo::HEADER => source().or_else(|e| goldsource().map_err(|_| e)),
+        GoldSourceResponseInfo::HEADER => goldsource().or_else(|e| source().map_err(|_| e)),
+        _ => return Err(A2SError::UnexpectedHeader(header)),
+    };
+    info.map_err(|e| e.offset_by(1))
+}
+
+// # Tests
+#[test]
+fn dispatch_on_header() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let info = ServerInfoBuilder::new().map("cp_badlands").build();
+    let ping = PingResponse {
+        payload: String::new(),
+    };
+
+    assert_eq!(
+        Response::SourceInfo(info.clone()),
+        parse_any(&info.to_framed_bytes()).unwrap()
+    );
+    assert_eq!(
+        Response::Ping(ping.clone()),
+        parse_any(&ping.to_framed_bytes()).unwrap()
+    );
+    assert_eq!(
+        Response::Split(vec![0x01]),
+        parse_any(&[0xFE, 0xFF, 0xFF, 0xFF, 0x01]).unwrap()
+    );
+    assert_eq!(
+        Err(A2SError::UnexpectedHeader(0x54)),
+        parse_any(&[0xFF, 0xFF, 0xFF, 0xFF, 0x54])
+    );
+    assert!(parse_any(&[0x6A, 0x00]).is_err());
+    assert!(parse_any(&[0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+}
+
+#[test]
+fn info_with_wrong_header() {
+    use crate::info_goldsource::GoldSourceResponseInfo;
+
+    // Half-Life mod on a dedicated linux server
+    let datagram: [u8; 42] = [
+        0x6D, 0x31, 0x2E, 0x32, 0x2E, 0x33, 0x2E, 0x34, 0x3A, 0x35, 0x00, 0x61, 0x00, 0x62, 0x00,
+        0x63, 0x00, 0x64, 0x00, 0x01, 0x02, 0x2F, 0x44, 0x4C, 0x00, 0x01, 0x65, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00,
+    ];
+    let goldsource = GoldSourceResponseInfo::parse(&datagram[1..]).unwrap();
+    let mut relabeled = datagram.to_vec();
+    relabeled[0] = SourceResponseInfo::HEADER;
+
+    assert_eq!(
+        InfoResponse::GoldSource(goldsource.clone()),
+        parse_info_auto(&datagram).unwrap()
+    );
+    assert_eq!(
+        InfoResponse::GoldSource(goldsource),
+        parse_info_auto(&relabeled).unwrap()
+    );
+    assert!(parse_info_auto(&[]).is_err());
+
+    // A player response is no info response, even if its payload happens to parse as one
+    relabeled[0] = ResponsePlayer::HEADER;
+    assert_eq!(
+        Err(A2SError::UnexpectedHeader(ResponsePlayer::HEADER)),
+        parse_info_auto(&relabeled)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    use crate::info_source::ServerInfoBuilder;
+
+    let response = Response::SourceInfo(
+        ServerInfoBuilder::new()
+            .map("cp_badlands")
+            .keywords("secure")
+            .build(),
+    );
+
+    let json = serde_json::to_string(&response).unwrap();
+
+    assert!(json.contains("\"map\":\"cp_badlands\""));
+    assert_eq!(response, serde_json::from_str(&json).unwrap());
+}
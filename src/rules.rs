@@ -1,15 +1,15 @@
-use nom::{
-    combinator::{all_consuming, rest},
-    error::Error,
-    multi::many_m_n,
-    number::complete::le_i16,
-    Finish, IResult,
+use crate::conformance::{count_violation, parse_with_warnings, ParseWarning, Violation};
+use crate::error::A2SError;
+use crate::message::A2sMessage;
+use crate::parser_util::{
+    all_consuming, with_remaining, write_c_string, ByteReader, ParseLimits, ReadError,
 };
 
-use crate::parser_util::c_string;
+use std::convert::TryFrom;
 
 // # Structs
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Contains the data specified in an [`A2S_RULES response`](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format_3)  
 /// Older games / engines may respond with a single packet response that truncates the rules somewhere in a rule : value pair.
 /// This truncated data is retained withing the remaining data field.
@@ -23,6 +23,7 @@ pub struct ResponseRule {
     pub remaining_data: String,
 }
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Pairs of rules : values
 pub struct RuleData {
     /// Rule name
@@ -31,63 +32,161 @@ pub struct RuleData {
     pub value: String,
 }
 
+impl ResponseRule {
+    /// Checks that the rule count matches the number of rules in [`rule_data`](ResponseRule::rule_data),
+    /// an empty Vec means the response is consistent. Responses truncated by older engines report a mismatch
+    pub fn validate(&self) -> Vec<Violation> {
+        count_violation("rules", self.rules.into(), self.rule_data.len())
+            .into_iter()
+            .collect()
+    }
+}
+
 // # Exposed final parser
 /// Parse the data specified in an [`A2S_RULES response`](https://developer.valvesoftware.com/wiki/Server_queries#Response_Format_3)  
 /// Older games / engines may respond with a single packet response that truncates the rules somewhere in a rule : value pair.
 /// This truncated data is retained withing the remaining data field.
 /// TODO: If there is remaining data after parsing the correct number of rules then raise an error
-pub fn parse_rule(input: &[u8]) -> Result<ResponseRule, Error<&[u8]>> {
-    match p_rules(input).finish() {
-        Ok(v) => Ok(v.1),
-        Err(e) => Err(e),
+pub fn parse_rule(input: &[u8]) -> Result<ResponseRule, A2SError> {
+    parse_rule_with_limits(input, &ParseLimits::default())
+}
+
+/// Same as [`parse_rule`] enforcing the given limits instead of the default ones,
+/// the rule count is checked against [`max_rules`](ParseLimits::max_rules) before any rule is read
+pub fn parse_rule_with_limits(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<ResponseRule, A2SError> {
+    match all_consuming(input, |reader| rules(reader, limits)) {
+        Ok(response) => Ok(response),
+        Err(e) => Err(A2SError::from(e).with_field(&[ResponseRule::HEADER], input)),
     }
 }
 
-// # Private parsing helper functions
-/// Make sure all data consumed (Which it really should be because of using rest() in the rule parser)
-fn p_rules(input: &[u8]) -> IResult<&[u8], ResponseRule> {
-    all_consuming(rules)(input)
+/// Same as [`parse_rule`] for a complete datagram starting with the `-1` prefix and 'E' header,
+/// rules are usually split across several packets which have to be reassembled first
+pub fn parse_framed_rule(input: &[u8]) -> Result<ResponseRule, A2SError> {
+    ResponseRule::parse_framed(input)
 }
 
-/// Does the parsing
-fn rules(input: &[u8]) -> IResult<&[u8], ResponseRule> {
-    let (input, num_rules) = le_i16(input)?;
-    // Parse a maximum of num_rules, rules from the payload
-    let (input, rule_data) = many_rule_data(input, num_rules)?;
-    // Grab the rest of the input, this clears input for us so we don't have to after the match
-    // This is done to satisfy the all_consuming
-    let (input, remaining_data) = rest(input)?;
+/// Same as [`parse_rule`] but any data after the last rule is returned instead of causing an error.
+/// For truncated responses the returned data is the same as the remaining data field, without the lossy UTF-8 conversion.
+pub fn parse_rule_with_remaining(input: &[u8]) -> Result<(ResponseRule, Vec<u8>), A2SError> {
+    match with_remaining(input, |reader| rule_list(reader, &ParseLimits::default())) {
+        Ok(((rules, rule_data), remaining)) => {
+            let remaining_data = match rule_data.len() as i16 == rules {
+                true => String::new(),
+                false => String::from_utf8_lossy(remaining).into_owned(),
+            };
 
-    let remaining_data = String::from_utf8_lossy(remaining_data).into_owned();
+            Ok((
+                ResponseRule {
+                    rules,
+                    rule_data,
+                    remaining_data,
+                },
+                remaining.to_vec(),
+            ))
+        }
+        Err(e) => Err(A2SError::from(e).with_field(&[ResponseRule::HEADER], input)),
+    }
+}
+
+/// Same as [`parse_rule_with_limits`] but works around what it can instead of failing, see
+/// [`parse_lenient`](crate::conformance::parse_lenient). The rule count and strings over the limits are clamped
+pub fn parse_rule_lenient(
+    input: &[u8],
+    limits: &ParseLimits,
+) -> Result<(ResponseRule, Vec<ParseWarning>), A2SError> {
+    parse_with_warnings(ResponseRule::HEADER, input, |reader| rules(reader, limits))
+}
+
+// # Private parsing helper functions
+/// Does the parsing, the rest of a truncated response is kept so the whole input is consumed
+fn rules(reader: &mut ByteReader, limits: &ParseLimits) -> Result<ResponseRule, ReadError> {
+    let (num_rules, rule_data) = rule_list(reader, limits)?;
 
     // TODO: If there is remaining data after the number of rules was successfully parsed then something went wrong!
-    if rule_data.len() as i16 == num_rules && !remaining_data.is_empty() {
-        return Err(nom::Err::Error(Error::new(
-            input,
-            nom::error::ErrorKind::NonEmpty,
-        )));
+    let remaining_data = match rule_data.len() as i16 == num_rules {
+        true => {
+            reader.finish()?;
+            String::new()
+        }
+        false => String::from_utf8_lossy(reader.rest()).into_owned(),
+    };
+
+    Ok(ResponseRule {
+        rules: num_rules,
+        rule_data,
+        remaining_data,
+    })
+}
+
+/// Reads the number of rules and as many of the rules as are in the payload
+fn rule_list(
+    reader: &mut ByteReader,
+    limits: &ParseLimits,
+) -> Result<(i16, Vec<RuleData>), ReadError> {
+    let count_offset = reader.offset();
+    let mut num_rules = reader.i16()?;
+    if num_rules > 0 {
+        num_rules = reader.limit(num_rules as usize, limits.max_rules, count_offset)? as i16;
     }
+    // Parse a maximum of num_rules, rules from the payload. Negative counts read as many rules as the limit allows
+    let rule_data = many_rule_data(reader, num_rules, limits)?;
 
-    Ok((
-        input,
-        ResponseRule {
-            rules: num_rules,
-            rule_data,
-            remaining_data,
-        },
-    ))
+    Ok((num_rules, rule_data))
+}
+
+// Reads up to count rules, truncated responses end in the middle of a rule
+fn many_rule_data(
+    reader: &mut ByteReader,
+    rules: i16,
+    limits: &ParseLimits,
+) -> Result<Vec<RuleData>, ReadError> {
+    let max = (rules as usize).min(limits.max_rules);
+    reader.many(max, |reader| rule_data(reader, limits))
 }
 
-// Uses many_m_n over count as connecting players are included in the players count but no data is stored.
-fn many_rule_data(input: &[u8], rules: i16) -> IResult<&[u8], Vec<RuleData>> {
-    many_m_n(0, rules as usize, rule_data)(input)
+fn rule_data(reader: &mut ByteReader, limits: &ParseLimits) -> Result<RuleData, ReadError> {
+    let name = reader.limited_c_string(limits.max_string_len)?;
+    let value = reader.limited_c_string(limits.max_string_len)?;
+
+    Ok(RuleData { name, value })
+}
+
+// # Message implementation
+impl A2sMessage for ResponseRule {
+    const HEADER: u8 = 0x45;
+
+    fn parse(input: &[u8]) -> Result<Self, A2SError> {
+        parse_rule(input)
+    }
+
+    fn parse_lenient(input: &[u8]) -> Result<(Self, Vec<ParseWarning>), A2SError> {
+        parse_rule_lenient(input, &ParseLimits::default())
+    }
+
+    /// Any remaining data is written after the rules as is
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.rules.to_le_bytes());
+
+        for rule in &self.rule_data {
+            write_c_string(output, &rule.name);
+            write_c_string(output, &rule.value);
+        }
+
+        output.extend_from_slice(self.remaining_data.as_bytes());
+    }
 }
 
-fn rule_data(input: &[u8]) -> IResult<&[u8], RuleData> {
-    let (input, name) = c_string(input)?;
-    let (input, value) = c_string(input)?;
+/// Parses the payload following the header byte, same as [`parse_rule`]
+impl<'a> TryFrom<&'a [u8]> for ResponseRule {
+    type Error = A2SError;
 
-    Ok((input, RuleData { name, value }))
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_rule(input)
+    }
 }
 
 // # Test
@@ -332,8 +431,106 @@ fn payload_after_rules() {
         0x31, 0x00, 0xFF,
     ];
     let response = parse_rule(&payload).unwrap_err();
-    // []..0] is an empty slice
-    let error = nom::error::Error::new(&payload[..0], nom::error::ErrorKind::NonEmpty);
+    // The 0xFF after the last rule is left over
+    let error = A2SError::TrailingData {
+        offset: payload.len() - 1,
+        len: 1,
+    };
 
     assert_eq!(error, response)
 }
+
+#[test]
+fn truncated_rules_round_trip() {
+    // Two rules are announced but the second one is cut off
+    let payload: [u8; 9] = [0x02, 0x00, 0x61, 0x00, 0x31, 0x00, 0x62, 0x62, 0x62];
+
+    let response = ResponseRule::parse(&payload).unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output);
+
+    assert_eq!("bbb", response.remaining_data);
+    assert_eq!(&payload[..], &output[..]);
+}
+
+#[test]
+fn rules_with_remaining() {
+    // One rule followed by two bytes
+    let payload: [u8; 8] = [0x01, 0x00, 0x61, 0x00, 0x31, 0x00, 0xAB, 0xCD];
+
+    let (response, remaining) = parse_rule_with_remaining(&payload).unwrap();
+
+    assert!(parse_rule(&payload).is_err());
+    assert_eq!(1, response.rule_data.len());
+    assert_eq!("", response.remaining_data);
+    assert_eq!(vec![0xAB, 0xCD], remaining);
+}
+
+#[test]
+fn very_long_rule_value() {
+    // Unity based servers send values far longer than the engine limits, followed here by a short rule
+    let value = "x".repeat(100_000);
+    let mut payload = vec![0x02, 0x00, 0x61, 0x00];
+    payload.extend_from_slice(value.as_bytes());
+    payload.extend_from_slice(&[0x00, 0x62, 0x00, 0x31, 0x00]);
+
+    let response = parse_rule(&payload).unwrap();
+
+    assert_eq!(value, response.rule_data[0].value);
+    assert_eq!("b", response.rule_data[1].name);
+    assert_eq!("1", response.rule_data[1].value);
+
+    // Truncated in the middle of the long value the partial rule is kept as remaining data
+    let response = parse_rule(&payload[..50_000]).unwrap();
+
+    assert!(response.rule_data.is_empty());
+    assert_eq!(49_998, response.remaining_data.len());
+}
+
+#[test]
+fn rule_count_over_limit() {
+    use crate::parser_util::ParseLimits;
+
+    // Claims 3 rules but only holds one
+    let payload: [u8; 6] = [0x03, 0x00, 0x61, 0x00, 0x31, 0x00];
+    let limits = ParseLimits {
+        max_rules: 2,
+        ..ParseLimits::default()
+    };
+
+    assert_eq!(
+        Err(A2SError::LimitExceeded {
+            offset: 0,
+            field: Some("rules".to_string())
+        }),
+        parse_rule_with_limits(&payload, &limits)
+    );
+    assert_eq!(1, parse_rule(&payload).unwrap().rule_data.len());
+}
+
+#[test]
+fn lenient_rules_over_limit() {
+    // Three complete rules
+    let payload: [u8; 14] = [
+        0x03, 0x00, 0x61, 0x00, 0x31, 0x00, 0x62, 0x00, 0x32, 0x00, 0x63, 0x00, 0x33, 0x00,
+    ];
+    let limits = ParseLimits {
+        max_rules: 2,
+        ..ParseLimits::default()
+    };
+
+    let (response, warnings) = parse_rule_lenient(&payload, &limits).unwrap();
+
+    assert_eq!(2, response.rules);
+    assert_eq!("2", response.rule_data[1].value);
+    assert!(response.remaining_data.is_empty());
+    assert_eq!(
+        vec![ParseWarning::Clamped {
+            field: Some("rules".to_string()),
+            offset: 0
+        }],
+        warnings
+    );
+    // The default limits don't clamp anything
+    assert!(ResponseRule::parse_lenient(&payload).unwrap().1.is_empty());
+}
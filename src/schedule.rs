@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+// # Traits
+/**
+Picks the order the requests of a batch query are sent in, set it on a [`QueryPool`](crate::pool::QueryPool) with
+[`with_schedule`](crate::pool::QueryPool::with_schedule) or pass it to [`query_many`](crate::client::query_many).
+
+Server lists are often sorted by address, sending in that order bursts every server of a hosting range one after the
+other, which gets crawlers dropped by the range's firewall. [`Interleave`] spreads them out, implement the trait for
+other orders. The results of the batch are in the order of the servers no matter the order the requests are sent in.
+*/
+pub trait Schedule: fmt::Debug + Send + Sync {
+    /// Indices into `servers` in the order their requests are sent, every index has to be listed once
+    fn order(&self, servers: &[SocketAddr]) -> Vec<usize>;
+}
+
+// # Structs / Enums
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Sends the requests in the order of the servers, the default
+pub struct InputOrder;
+
+impl Schedule for InputOrder {
+    fn order(&self, servers: &[SocketAddr]) -> Vec<usize> {
+        (0..servers.len()).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/**
+Interleaves the servers of different hosts or networks, taking one server of each in turn.
+
+Servers of a group keep their order, the groups are visited in the order their first server is listed.
+
+# Examples
+```
+use a2s_parse::schedule::{Interleave, Schedule};
+use std::net::SocketAddr;
+
+let servers: Vec<SocketAddr> = vec![
+    "192.0.2.1:27015".parse().unwrap(),
+    "192.0.2.1:27016".parse().unwrap(),
+    "192.0.2.2:27015".parse().unwrap(),
+    "198.51.100.1:27015".parse().unwrap(),
+];
+
+assert_eq!(vec![0, 2, 3, 1], Interleave::Host.order(&servers));
+assert_eq!(vec![0, 3, 2, 1], Interleave::Network.order(&servers));
+```
+*/
+pub enum Interleave {
+    /// One server of each IP in turn, spreading the ports of a host
+    Host,
+    /// One server of each /24 IPv4 or /64 IPv6 network in turn, the hosts within a network are interleaved as well
+    Network,
+}
+
+impl Schedule for Interleave {
+    fn order(&self, servers: &[SocketAddr]) -> Vec<usize> {
+        let by_host = interleave((0..servers.len()).collect(), |index| servers[index].ip());
+
+        match self {
+            Interleave::Host => by_host,
+            Interleave::Network => interleave(by_host, |index| network(servers[index].ip())),
+        }
+    }
+}
+
+// # Private helper functions
+/// Takes one index of each group in turn, keeping the order within the groups
+fn interleave<K: Eq + Hash>(indices: Vec<usize>, key: impl Fn(usize) -> K) -> Vec<usize> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of = HashMap::new();
+    for index in indices {
+        let group = *group_of.entry(key(index)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+
+    let rounds = groups.iter().map(Vec::len).max().unwrap_or(0);
+    (0..rounds)
+        .flat_map(|round| groups.iter().filter_map(move |group| group.get(round)))
+        .copied()
+        .collect()
+}
+
+/// The /24 or /64 network of the IP, the host bits are cleared
+fn network(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => Ipv4Addr::from(u32::from(ip) & !0xFF).into(),
+        IpAddr::V6(ip) => Ipv6Addr::from(u128::from(ip) & !u128::from(u64::MAX)).into(),
+    }
+}
+
+// # Tests
+#[test]
+fn interleave_servers() {
+    let servers: Vec<SocketAddr> = [
+        "192.0.2.1:1",
+        "192.0.2.1:2",
+        "192.0.2.1:3",
+        "192.0.2.2:1",
+        "[2001:db8::1]:1",
+        "[2001:db8::2]:1",
+        "198.51.100.1:1",
+    ]
+    .iter()
+    .map(|server| server.parse().unwrap())
+    .collect();
+
+    assert_eq!(vec![0, 1, 2, 3, 4, 5, 6], InputOrder.order(&servers));
+    assert_eq!(vec![0, 3, 4, 5, 6, 1, 2], Interleave::Host.order(&servers));
+    assert_eq!(
+        vec![0, 4, 6, 3, 5, 1, 2],
+        Interleave::Network.order(&servers)
+    );
+    assert!(Interleave::Network.order(&[]).is_empty());
+}
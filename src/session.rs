@@ -0,0 +1,294 @@
+use crate::challenge::ChallengeResponse;
+use crate::client::{parse_answer, Answer, ClientError, ResponseFilter, MAX_CHALLENGES};
+use crate::info_source::SourceResponseInfo;
+use crate::message::A2sMessage;
+use crate::player::ResponsePlayer;
+use crate::requests::{build_info_request, build_player_request, build_rules_request};
+use crate::rules::ResponseRule;
+
+use std::marker::PhantomData;
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Datagram the session wants sent to the server
+pub struct OutgoingDatagram {
+    /// Complete datagram including the `-1` prefix
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+/// Outcome of handing a datagram to a [`QuerySession`]
+pub enum Event<T> {
+    /// The server asked for a challenge, send the datagram and keep waiting
+    Transmit(OutgoingDatagram),
+    /// More packets of a split response are needed, or the datagram was dropped as a late answer to an earlier request
+    /// or because the session already finished
+    Pending,
+    /// The final response, the session is finished
+    Response(T),
+    /// The query failed, the session is finished
+    Error(ClientError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/**
+How the server got to answering a [`QuerySession`], returned by [`QuerySession::path`].
+
+Servers differ per query type: some answer A2S_PLAYER sent with the challenge `-1` right away but ask for a challenge
+for A2S_RULES, others challenge A2S_INFO but nothing else. Each session records the path of its own query type.
+*/
+pub enum ChallengePath {
+    /// Answered the request sent without a challenge, `-1` for A2S_PLAYER and A2S_RULES and none for A2S_INFO
+    Direct,
+    /// Answered the request carrying the challenge the session started with, see [`QuerySession::with_challenge`]
+    Reused,
+    /// Answered with a challenge first and the request resent with it
+    Challenged,
+}
+
+/// Where a [`QuerySession`] is in the handshake
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Handshake {
+    /// Waiting for the answer to the request sent without a challenge
+    Unchallenged,
+    /// Waiting for the answer to a request carrying a challenge, `handed_out` is the number of challenges the server
+    /// answered with so far, zero if the session started with one
+    Challenged { handed_out: usize },
+    /// The response or an error was returned, errors have no path
+    Finished(Option<ChallengePath>),
+}
+
+/**
+Protocol state of a single query without any I/O, for driving queries from custom event loops or I/O stacks.
+
+Send the datagram from [`transmit`](QuerySession::transmit) and feed every datagram received from the server to
+[`handle_datagram`](QuerySession::handle_datagram) until it returns the response or an error. Challenges are answered
+by returning the request to resend and split responses are reassembled. Timeouts are up to the caller, call
+[`transmit`](QuerySession::transmit) again to resend the request. Once answered, [`path`](QuerySession::path) tells
+whether the server answered right away or asked for a challenge first.
+
+# Examples
+```
+use a2s_parse::challenge::build_challenge_response;
+use a2s_parse::session::{Event, QuerySession};
+
+let mut session = QuerySession::players();
+let request = session.transmit();
+// socket.send(&request.data)
+
+match session.handle_datagram(&build_challenge_response(42)) {
+    // socket.send(&resend.data)
+    Event::Transmit(_resend) => assert_eq!(Some(42), session.challenge()),
+    _ => unreachable!(),
+}
+```
+*/
+#[derive(Debug)]
+pub struct QuerySession<T> {
+    build_request: fn(Option<i32>) -> Vec<u8>,
+    challenge: Option<i32>,
+    handshake: Handshake,
+    filter: ResponseFilter,
+    response: PhantomData<T>,
+}
+
+impl QuerySession<SourceResponseInfo> {
+    /// Session querying A2S_INFO
+    pub fn info() -> Self {
+        QuerySession::new(build_info_request)
+    }
+}
+
+impl QuerySession<ResponsePlayer> {
+    /// Session querying A2S_PLAYER
+    pub fn players() -> Self {
+        QuerySession::new(|challenge| build_player_request(challenge.unwrap_or(-1)))
+    }
+}
+
+impl QuerySession<ResponseRule> {
+    /// Session querying A2S_RULES
+    pub fn rules() -> Self {
+        QuerySession::new(|challenge| build_rules_request(challenge.unwrap_or(-1)))
+    }
+}
+
+impl<T: A2sMessage> QuerySession<T> {
+    fn new(build_request: fn(Option<i32>) -> Vec<u8>) -> Self {
+        QuerySession {
+            build_request,
+            challenge: None,
+            handshake: Handshake::Unchallenged,
+            filter: ResponseFilter::answer(T::HEADER),
+            response: PhantomData,
+        }
+    }
+
+    /// Starts with a challenge received earlier, e.g. by another query of the same server, saving a round trip
+    pub fn with_challenge(mut self, challenge: i32) -> Self {
+        self.challenge = Some(challenge);
+        self.handshake = Handshake::Challenged { handed_out: 0 };
+        self
+    }
+
+    /// Latest challenge handed out by the server
+    pub fn challenge(&self) -> Option<i32> {
+        self.challenge
+    }
+
+    /// True once the response or an error was returned
+    pub fn is_finished(&self) -> bool {
+        matches!(self.handshake, Handshake::Finished(_))
+    }
+
+    /// How the server got to the response, None until the response was returned or if the query failed
+    pub fn path(&self) -> Option<ChallengePath> {
+        match self.handshake {
+            Handshake::Finished(path) => path,
+            _ => None,
+        }
+    }
+
+    /// The request to send, to start the query and again after a timeout. Packets of a partially received
+    /// split response are dropped as the server sends the whole response again
+    pub fn transmit(&mut self) -> OutgoingDatagram {
+        self.filter = ResponseFilter::answer(T::HEADER);
+
+        OutgoingDatagram {
+            data: (self.build_request)(self.challenge),
+        }
+    }
+
+    /// Handles a datagram received from the server
+    pub fn handle_datagram(&mut self, datagram: &[u8]) -> Event<T> {
+        let handed_out = match self.handshake {
+            Handshake::Unchallenged => 0,
+            Handshake::Challenged { handed_out } => handed_out,
+            Handshake::Finished(_) => return Event::Pending,
+        };
+
+        let payload = match self.filter.receive(datagram) {
+            Ok(Some(payload)) => Ok(payload),
+            Ok(None) => return Event::Pending,
+            Err(e) => Err(e),
+        };
+
+        match payload.and_then(|payload| parse_answer(&payload)) {
+            Ok(Answer::Challenge(challenge)) if handed_out < MAX_CHALLENGES => {
+                self.handshake = Handshake::Challenged {
+                    handed_out: handed_out + 1,
+                };
+                self.challenge = Some(challenge);
+                Event::Transmit(self.transmit())
+            }
+            Ok(Answer::Challenge(_)) => {
+                self.handshake = Handshake::Finished(None);
+                Event::Error(ClientError::UnexpectedHeader(ChallengeResponse::HEADER))
+            }
+            Ok(Answer::Response(response)) => {
+                let path = match self.handshake {
+                    Handshake::Unchallenged => ChallengePath::Direct,
+                    _ if handed_out == 0 => ChallengePath::Reused,
+                    _ => ChallengePath::Challenged,
+                };
+                self.handshake = Handshake::Finished(Some(path));
+                Event::Response(response)
+            }
+            Err(e) => {
+                self.handshake = Handshake::Finished(None);
+                Event::Error(e)
+            }
+        }
+    }
+}
+
+// # Tests
+#[test]
+fn split_response_after_challenge() {
+    use crate::challenge::build_challenge_response;
+    use crate::packet::split_source_payload;
+    use crate::rules::RuleData;
+
+    let rules = ResponseRule {
+        rules: 1,
+        rule_data: vec![RuleData {
+            name: "mp_timelimit".to_string(),
+            value: "30".to_string(),
+        }],
+        remaining_data: String::new(),
+    };
+    let response = rules.to_framed_bytes();
+    let packets = split_source_payload(1, &response, 20).unwrap();
+
+    let mut session = QuerySession::rules();
+    assert_eq!(build_rules_request(-1), session.transmit().data);
+    match session.handle_datagram(&build_challenge_response(8)) {
+        Event::Transmit(datagram) => assert_eq!(build_rules_request(8), datagram.data),
+        event => panic!("{:?}", event),
+    }
+
+    // A late answer to another query doesn't end the session
+    assert!(matches!(
+        session.handle_datagram(&[0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00]),
+        Event::Pending
+    ));
+    for packet in &packets[1..] {
+        assert!(matches!(
+            session.handle_datagram(&packet.to_bytes()),
+            Event::Pending
+        ));
+    }
+    match session.handle_datagram(&packets[0].to_bytes()) {
+        Event::Response(response) => assert_eq!(rules, response),
+        event => panic!("{:?}", event),
+    }
+    assert!(session.is_finished());
+    assert_eq!(Some(ChallengePath::Challenged), session.path());
+    assert!(matches!(session.handle_datagram(&response), Event::Pending));
+}
+
+#[test]
+fn challenge_paths() {
+    use crate::challenge::build_challenge_response;
+
+    let players = [0xFF, 0xFF, 0xFF, 0xFF, 0x44, 0x00];
+    let rules = [0xFF, 0xFF, 0xFF, 0xFF, 0x45, 0x00, 0x00];
+
+    // A2S_PLAYER is answered to `-1` while A2S_RULES needs a challenge
+    let mut session = QuerySession::players();
+    session.transmit();
+    assert_eq!(None, session.path());
+    assert!(matches!(
+        session.handle_datagram(&players),
+        Event::Response(_)
+    ));
+    assert_eq!(Some(ChallengePath::Direct), session.path());
+
+    let mut session = QuerySession::rules();
+    session.transmit();
+    session.handle_datagram(&build_challenge_response(3));
+    assert_eq!(None, session.path());
+    assert!(matches!(
+        session.handle_datagram(&rules),
+        Event::Response(_)
+    ));
+    assert_eq!(Some(ChallengePath::Challenged), session.path());
+
+    let mut session = QuerySession::rules().with_challenge(3);
+    assert_eq!(build_rules_request(3), session.transmit().data);
+    assert!(matches!(
+        session.handle_datagram(&rules),
+        Event::Response(_)
+    ));
+    assert_eq!(Some(ChallengePath::Reused), session.path());
+
+    // Failed queries have no path
+    let mut session = QuerySession::players();
+    session.transmit();
+    for challenge in 0..=MAX_CHALLENGES {
+        session.handle_datagram(&build_challenge_response(challenge as i32));
+    }
+    assert!(session.is_finished());
+    assert_eq!(None, session.path());
+}
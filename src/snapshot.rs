@@ -0,0 +1,173 @@
+use crate::info_source::{SourceResponseInfo, TheShipFields};
+use crate::parser_util::{Environment, ServerType, VacStatus};
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// # Structs
+/**
+Deduplicates strings so equal values are stored once and shared.
+
+Most string fields of a server rarely change between polls and values like the folder or game
+are the same across thousands of servers, interning them keeps one copy of each in memory.
+
+# Examples
+```
+use a2s_parse::snapshot::StringPool;
+use std::sync::Arc;
+
+let mut pool = StringPool::new();
+
+let a = pool.intern("cstrike");
+let b = pool.intern("cstrike");
+
+assert!(Arc::ptr_eq(&a, &b));
+```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct StringPool {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringPool {
+    /// Creates an empty pool
+    pub fn new() -> Self {
+        StringPool::default()
+    }
+
+    /// Returns the shared copy of the string, adding it to the pool if it isn't in it yet
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        match self.strings.get(value) {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared: Arc<str> = Arc::from(value);
+                self.strings.insert(Arc::clone(&shared));
+                shared
+            }
+        }
+    }
+
+    /// Number of distinct strings in the pool
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// True if the pool holds no strings
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Drops strings that are no longer used by any snapshot
+    pub fn purge(&mut self) {
+        self.strings.retain(|shared| Arc::strong_count(shared) > 1);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// [`SourceResponseInfo`] with its strings shared through a [`StringPool`], for monitors keeping snapshots of many servers
+pub struct SharedSourceInfo {
+    /// Protocol version used by the server
+    pub protocol: u8,
+    /// Name of the server
+    pub name: Arc<str>,
+    /// Map the server has currently loaded
+    pub map: Arc<str>,
+    /// Name of the folder containing the game files
+    pub folder: Arc<str>,
+    /// Full name of the game
+    pub game: Arc<str>,
+    /// Steam Application ID of game
+    pub app_id: i16,
+    /// Number of players on the server
+    pub players: u8,
+    /// Maximum number of players the server reports it can hold
+    pub max_players: u8,
+    /// Number of bots on the server
+    pub bots: u8,
+    /// Indicates the type of server
+    pub server_type: ServerType,
+    /// Operating system the server is running on
+    pub environment: Environment,
+    /// Is the server private
+    pub visibility: bool,
+    /// Whether the server is secured with VAC
+    pub vac: VacStatus,
+    /// Optional data transmitted by The Ship
+    pub the_ship: Option<TheShipFields>,
+    /// Version of the game installed on the server
+    pub version: Arc<str>,
+    /// Extra Data Flag, indicates which of the following fields were sent
+    pub extra_data_flag: u8,
+    /// Servers port
+    pub port: Option<i16>,
+    /// Server SteamID
+    pub steam_id: Option<u64>,
+    /// Port for Source TV
+    pub source_tv_port: Option<i16>,
+    /// Name of the Spectator server for Source TV
+    pub source_tv_name: Option<Arc<str>>,
+    /// Tags that describe the game
+    pub keywords: Option<Arc<str>>,
+    /// 64bit GameID
+    pub game_id: Option<u64>,
+}
+
+impl SharedSourceInfo {
+    /// Copies the response, taking its strings from the pool
+    pub fn new(info: &SourceResponseInfo, pool: &mut StringPool) -> Self {
+        let fields = &info.extra_data_fields;
+
+        SharedSourceInfo {
+            protocol: info.protocol,
+            name: pool.intern(&info.name),
+            map: pool.intern(&info.map),
+            folder: pool.intern(&info.folder),
+            game: pool.intern(&info.game),
+            app_id: info.app_id,
+            players: info.players,
+            max_players: info.max_players,
+            bots: info.bots,
+            server_type: info.server_type.clone(),
+            environment: info.environment.clone(),
+            visibility: info.visibility,
+            vac: info.vac,
+            the_ship: info.the_ship.clone(),
+            version: pool.intern(&info.version),
+            extra_data_flag: info.extra_data_flag,
+            port: fields.port,
+            steam_id: fields.steam_id,
+            source_tv_port: fields.source_tv_port,
+            source_tv_name: fields
+                .source_tv_name
+                .as_deref()
+                .map(|name| pool.intern(name)),
+            keywords: fields
+                .keywords
+                .as_deref()
+                .map(|keywords| pool.intern(keywords)),
+            game_id: fields.game_id,
+        }
+    }
+}
+
+// # Tests
+#[test]
+fn snapshots_share_strings() {
+    use crate::info_source::{info_payload, parse_source_info};
+
+    let payload = info_payload();
+    let info = parse_source_info(&payload).unwrap();
+    let mut pool = StringPool::new();
+
+    let first = SharedSourceInfo::new(&info, &mut pool);
+    let second = SharedSourceInfo::new(&info, &mut pool);
+
+    assert_eq!(first, second);
+    assert!(Arc::ptr_eq(&first.map, &second.map));
+    assert_eq!(5, pool.len());
+
+    drop(first);
+    drop(second);
+    pool.purge();
+    assert!(pool.is_empty());
+}
@@ -0,0 +1,211 @@
+use crate::player::ResponsePlayer;
+
+/// Durations are allowed to go backwards by this many seconds between polls before a player is considered to have rejoined
+const DURATION_TOLERANCE: f32 = 1.0;
+
+// # Structs / Enums
+#[derive(Clone, Debug, PartialEq)]
+/// A player that has been seen in consecutive polls
+pub struct TrackedPlayer {
+    /// Identifier that stays the same for as long as the player is connected, assigned by the tracker
+    pub id: u64,
+    /// Name of the player
+    pub name: String,
+    /// Time in seconds the player has been connected as of the last poll
+    pub duration: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Change in the players connected to a server between two polls
+pub enum PlayerEvent {
+    /// Player was not connected during the previous poll
+    Joined(TrackedPlayer),
+    /// Player is no longer connected, contains the player as of the last poll they were seen in
+    Left(TrackedPlayer),
+}
+
+/**
+Matches players across consecutive A2S_PLAYER responses.
+
+Source servers send an index of 0 for every player and the order of players can change between responses,
+so players are matched by name and a connection duration that only increases. A player with the same name
+but a shorter duration than before has reconnected and is reported as leaving and joining again.
+
+# Examples
+```
+use a2s_parse::player::{PlayerData, ResponsePlayer};
+use a2s_parse::tracker::{PlayerEvent, PlayerTracker};
+
+fn response(players: &[(&str, f32)]) -> ResponsePlayer {
+    ResponsePlayer {
+        players: players.len() as u8,
+        player_data: players
+            .iter()
+            .map(|(name, duration)| PlayerData {
+                index: 0,
+                name: name.to_string(),
+                score: 0,
+                duration: *duration,
+                ship_data: None,
+            })
+            .collect(),
+    }
+}
+
+let mut tracker = PlayerTracker::new();
+tracker.update(&response(&[("Alice", 10.0)]));
+
+let events = tracker.update(&response(&[("Bob", 1.0), ("Alice", 20.0)]));
+
+assert_eq!(1, events.len());
+assert!(matches!(&events[0], PlayerEvent::Joined(p) if p.name == "Bob"));
+```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct PlayerTracker {
+    players: Vec<TrackedPlayer>,
+    next_id: u64,
+}
+
+impl PlayerTracker {
+    /// Creates a tracker with no players
+    pub fn new() -> Self {
+        PlayerTracker::default()
+    }
+
+    /// Players connected as of the last poll
+    pub fn players(&self) -> &[TrackedPlayer] {
+        &self.players
+    }
+
+    /// Matches the players in the response against the previous poll and returns the players that left followed by the players that joined
+    pub fn update(&mut self, response: &ResponsePlayer) -> Vec<PlayerEvent> {
+        let mut previous: Vec<Option<TrackedPlayer>> = self.players.drain(..).map(Some).collect();
+
+        // Longest connected players are matched first so players sharing a name each keep their own id
+        let mut current: Vec<(&str, f32)> = response
+            .player_data
+            .iter()
+            .map(|p| (p.name.as_str(), p.duration))
+            .collect();
+        current.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut joined = Vec::new();
+        for (name, duration) in current {
+            let matched = previous
+                .iter_mut()
+                .filter(|p| match p {
+                    Some(p) => p.name == name && p.duration <= duration + DURATION_TOLERANCE,
+                    None => false,
+                })
+                .max_by(|a, b| {
+                    let (a, b) = (a.as_ref().unwrap(), b.as_ref().unwrap());
+                    a.duration
+                        .partial_cmp(&b.duration)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .and_then(|p| p.take());
+
+            match matched {
+                Some(mut player) => {
+                    player.duration = duration;
+                    self.players.push(player);
+                }
+                None => {
+                    let player = TrackedPlayer {
+                        id: self.next_id,
+                        name: name.to_string(),
+                        duration,
+                    };
+                    self.next_id += 1;
+                    joined.push(PlayerEvent::Joined(player.clone()));
+                    self.players.push(player);
+                }
+            }
+        }
+
+        previous
+            .into_iter()
+            .flatten()
+            .map(PlayerEvent::Left)
+            .chain(joined)
+            .collect()
+    }
+}
+
+// # Tests
+#[cfg(test)]
+fn response(players: &[(&str, f32)]) -> ResponsePlayer {
+    use crate::player::PlayerData;
+
+    ResponsePlayer {
+        players: players.len() as u8,
+        player_data: players
+            .iter()
+            .map(|(name, duration)| PlayerData {
+                index: 0,
+                name: name.to_string(),
+                score: 0,
+                duration: *duration,
+                ship_data: None,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn join_leave_and_rejoin() {
+    let mut tracker = PlayerTracker::new();
+
+    let events = tracker.update(&response(&[("a", 10.0), ("b", 5.0)]));
+    assert_eq!(2, events.len());
+
+    let events = tracker.update(&response(&[("c", 1.0), ("a", 20.0)]));
+    assert_eq!(
+        vec![
+            PlayerEvent::Left(TrackedPlayer {
+                id: 1,
+                name: "b".to_string(),
+                duration: 5.0
+            }),
+            PlayerEvent::Joined(TrackedPlayer {
+                id: 2,
+                name: "c".to_string(),
+                duration: 1.0
+            }),
+        ],
+        events
+    );
+
+    // "a" reconnected between polls so the duration went down
+    let events = tracker.update(&response(&[("a", 2.0), ("c", 11.0)]));
+    assert_eq!(
+        vec![
+            PlayerEvent::Left(TrackedPlayer {
+                id: 0,
+                name: "a".to_string(),
+                duration: 20.0
+            }),
+            PlayerEvent::Joined(TrackedPlayer {
+                id: 3,
+                name: "a".to_string(),
+                duration: 2.0
+            }),
+        ],
+        events
+    );
+}
+
+#[test]
+fn shared_names_keep_ids() {
+    let mut tracker = PlayerTracker::new();
+    tracker.update(&response(&[("player", 100.0), ("player", 5.0)]));
+
+    let events = tracker.update(&response(&[("player", 15.0), ("player", 110.0)]));
+    let mut players = tracker.players().to_vec();
+    players.sort_by_key(|p| p.id);
+
+    assert!(events.is_empty());
+    assert_eq!(110.0, players[0].duration);
+    assert_eq!(15.0, players[1].duration);
+}